@@ -21,13 +21,19 @@ extern crate error_chain;
 extern crate rsudoku;
 
 use std::fs::File;
-use std::io::{self, BufReader, Read};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
 
 use clap::{Arg, ArgMatches, App, SubCommand};
 
 use rsudoku::Sudoku;
 use rsudoku::errors::*;
+use rsudoku::game::Game;
+use rsudoku::stats::{self, Stats};
+use rsudoku::sudoku::{constraint, Engine, Shape};
+use rsudoku::sudoku::logic::Difficulty;
 use rsudoku::tui;
+use rsudoku::util;
 
 quick_main!(run);
 
@@ -35,16 +41,103 @@ fn run() -> Result<()> {
     let matches = App::new("RSudoku")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Ian Johnson <ianprime0509@gmail.com>")
+        .subcommand(
+            SubCommand::with_name("count")
+                .about("Counts the solutions of a Sudoku puzzle")
+                .arg(
+                    Arg::with_name("limit")
+                        .short("l")
+                        .long("limit")
+                        .takes_value(true)
+                        .default_value("2")
+                        .help("Stops counting after this many solutions"),
+                )
+                .arg(box_arg())
+                .arg(variant_arg())
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Sets the input file to use")
+                        .default_value("-"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("generate")
                 .about("Generates a random Sudoku grid")
+                .arg(box_arg().conflicts_with("size"))
+                .arg(
+                    Arg::with_name("difficulty")
+                        .short("d")
+                        .long("difficulty")
+                        .takes_value(true)
+                        .help(
+                            "Sets the difficulty of the generated puzzle (easy, medium, hard, \
+                             expert or diabolical)",
+                        ),
+                )
+                .arg(jigsaw_arg())
+                .arg(killer_arg())
                 .arg(Arg::with_name("pretty").short("p").long("pretty").help(
                     "Pretty prints the output",
-                )),
+                ))
+                .arg(
+                    Arg::with_name("seed")
+                        .short("s")
+                        .long("seed")
+                        .takes_value(true)
+                        .help("Sets the seed to use for generation"),
+                )
+                .arg(size_arg())
+                .arg(variant_arg()),
         )
         .subcommand(
             SubCommand::with_name("play")
                 .about("Plays the interactive console game")
+                .arg(box_arg().conflicts_with("size"))
+                .arg(
+                    Arg::with_name("difficulty")
+                        .short("d")
+                        .long("difficulty")
+                        .takes_value(true)
+                        .conflicts_with("INPUT")
+                        .help(
+                            "Sets the difficulty of the generated puzzle (easy, medium, hard, \
+                             expert or diabolical)",
+                        ),
+                )
+                .arg(jigsaw_arg().conflicts_with("INPUT"))
+                .arg(killer_arg().conflicts_with("INPUT"))
+                .arg(
+                    Arg::with_name("record")
+                        .long("record")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help(
+                            "Saves the game to FILE after every move, so that it can be replayed \
+                             later using the `replay` subcommand",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .short("r")
+                        .long("resume")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .conflicts_with_all(&[
+                            "INPUT", "box", "difficulty", "jigsaw", "killer", "seed", "size",
+                            "variant",
+                        ])
+                        .help("Resumes a game saved using the `:w` command"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .short("s")
+                        .long("seed")
+                        .takes_value(true)
+                        .conflicts_with("INPUT")
+                        .help("Sets the seed to use for generating the game board"),
+                )
+                .arg(size_arg().conflicts_with("INPUT"))
+                .arg(variant_arg())
                 .arg(Arg::with_name("INPUT").help(
                     "Sets the input file to use for the game board",
                 )),
@@ -52,43 +145,144 @@ fn run() -> Result<()> {
         .subcommand(
             SubCommand::with_name("print")
                 .about("Prints a Sudoku grid")
+                .arg(box_arg())
                 .arg(Arg::with_name("pretty").short("p").long("pretty").help(
                     "Pretty prints the output",
                 ))
+                .arg(variant_arg())
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Sets the input file to use")
+                        .default_value("-"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rate")
+                .about("Rates the difficulty of a Sudoku puzzle")
+                .arg(box_arg())
+                .arg(variant_arg())
                 .arg(
                     Arg::with_name("INPUT")
                         .help("Sets the input file to use")
                         .default_value("-"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Replays a recorded game in the interactive console game")
+                .arg(
+                    Arg::with_name("speed")
+                        .long("speed")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Sets how many times faster than real time to replay the moves"),
+                )
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help(
+                            "Sets the recorded game to replay (a saved game or a move log \
+                             exported using the `:export` command)",
+                        )
+                        .required(true),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("solve")
                 .about("Solves a Sudoku puzzle")
+                .arg(box_arg())
                 .arg(Arg::with_name("all").short("a").long("all").help(
                     "Prints all solutions",
                 ))
+                .arg(
+                    Arg::with_name("engine")
+                        .long("engine")
+                        .takes_value(true)
+                        .possible_values(&["backtrack", "dlx"])
+                        .default_value("backtrack")
+                        .help("Sets the solver engine to use"),
+                )
+                .arg(
+                    Arg::with_name("explain")
+                        .short("e")
+                        .long("explain")
+                        .conflicts_with("all")
+                        .help("Explains each step of the solution using logical techniques"),
+                )
                 .arg(Arg::with_name("pretty").short("p").long("pretty").help(
                     "Pretty prints the output",
                 ))
+                .arg(variant_arg())
                 .arg(
                     Arg::with_name("INPUT")
                         .help("Sets the input file to use")
                         .default_value("-"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Prints statistics about the games finished in the interactive console game")
+                .arg(
+                    Arg::with_name("top")
+                        .short("t")
+                        .long("top")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help(
+                            "Sets how many of the fastest games to list for each size and \
+                             difficulty",
+                        ),
+                ),
+        )
         .get_matches();
 
     match matches.subcommand() {
+        ("count", Some(m)) => count(m),
         ("generate", Some(m)) => generate(m),
         ("play", Some(m)) => play(m),
         ("print", Some(m)) => print(m),
+        ("rate", Some(m)) => rate(m),
+        ("replay", Some(m)) => replay(m),
         ("solve", Some(m)) => solve(m),
+        ("stats", Some(m)) => stats(m),
         _ => play(&ArgMatches::new()),
     }
 }
 
+fn count(m: &ArgMatches) -> Result<()> {
+    // We can safely unwrap here since we set a default value
+    let limit = m.value_of("limit").unwrap();
+    let limit = match limit.parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => bail!("invalid limit `{}`; must be a positive number", limit),
+    };
+    let s = read_sudoku(m)?;
+
+    let n = s.count_solutions(limit);
+    if n == limit {
+        println!("at least {}", n);
+    } else {
+        println!("{}", n);
+    }
+
+    Ok(())
+}
+
 fn generate(m: &ArgMatches) -> Result<()> {
-    let s = Sudoku::generate();
+    let seed = parse_seed(m)?;
+    let template = parse_template(m)?;
+    let difficulty = match m.value_of("difficulty") {
+        None => None,
+        Some(d) => Some(d.parse::<Difficulty>()?),
+    };
+    let mut rng = util::seeded_rng(seed);
+    let s = match (m.is_present("jigsaw"), m.is_present("killer")) {
+        (true, true) => template.generate_killer_jigsaw_like(difficulty, &mut rng)?,
+        (true, false) => template.generate_jigsaw_like(difficulty, &mut rng)?,
+        (false, true) => template.generate_killer_like(difficulty, &mut rng)?,
+        (false, false) => template.generate_like(difficulty, &mut rng)?,
+    };
+    // The seed goes to standard error so that the output can still be parsed as a sudoku
+    eprintln!("Seed: {}", seed);
     if m.is_present("pretty") {
         println!("{:#}", s);
     } else {
@@ -99,21 +293,53 @@ fn generate(m: &ArgMatches) -> Result<()> {
 }
 
 fn play(m: &ArgMatches) -> Result<()> {
-    let s = match m.value_of("INPUT") {
-        None => Sudoku::generate(),
+    let record = m.value_of("record").map(PathBuf::from);
+    if let Some(file) = m.value_of("resume") {
+        let game = Game::load(file)?;
+        return match resume_autosave(&game)? {
+            Some(saved) => tui::Game::run(saved, None, record),
+            None => tui::Game::run(game, Some(file.into()), record),
+        };
+    }
+    let game = match m.value_of("INPUT") {
+        None => {
+            // Only a plain `rsudoku play` picks up where the last game left off; asking for a
+            // particular kind of puzzle starts one, which may still have its own autosave
+            let generation = ["box", "difficulty", "jigsaw", "killer", "seed", "size", "variant"];
+            if !generation.iter().any(|&arg| m.is_present(arg)) {
+                if let Some(game) = resume_latest_autosave()? {
+                    return tui::Game::run(game, None, record);
+                }
+            }
+            let seed = parse_seed(m)?;
+            let template = parse_template(m)?;
+            let difficulty = match m.value_of("difficulty") {
+                None => None,
+                Some(d) => Some(d.parse::<Difficulty>()?),
+            };
+            let killer = m.is_present("killer");
+            if m.is_present("jigsaw") {
+                Game::from_seed_jigsaw(seed, &template, killer, difficulty)?
+            } else if killer {
+                Game::from_seed_killer(seed, &template, difficulty)?
+            } else {
+                Game::from_seed_like(seed, &template, difficulty)?
+            }
+        }
         Some("-") => {
             bail!("currently, playing a Sudoku given through standard input is not supported")
         }
-        Some(input) => read_to_string(input)?.parse::<Sudoku>()?,
+        Some(_) => Game::from_sudoku(read_sudoku(m)?),
     };
 
-    tui::Game::run(s)
+    match resume_autosave(&game)? {
+        Some(saved) => tui::Game::run(saved, None, record),
+        None => tui::Game::run(game, None, record),
+    }
 }
 
 fn print(m: &ArgMatches) -> Result<()> {
-    // We can safely unwrap here since we set a default value
-    let input = m.value_of("INPUT").unwrap();
-    let s = read_to_string(input)?.parse::<Sudoku>()?;
+    let s = read_sudoku(m)?;
 
     if m.is_present("pretty") {
         println!("{:#}", s);
@@ -124,14 +350,54 @@ fn print(m: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+fn rate(m: &ArgMatches) -> Result<()> {
+    let s = read_sudoku(m)?;
+    let rating = s.rate();
+
+    println!("Difficulty: {}", rating.difficulty);
+    println!("Score: {}", rating.score);
+    match rating.hardest {
+        Some(t) => println!("Hardest technique: {}", t),
+        None => println!("Hardest technique: none"),
+    }
+    if !rating.solved {
+        println!("Cannot be solved without guessing");
+    }
+
+    Ok(())
+}
+
+fn replay(m: &ArgMatches) -> Result<()> {
+    let speed = m.value_of("speed").unwrap();
+    let speed = match speed.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => speed,
+        _ => bail!("invalid speed `{}`; must be a positive number", speed),
+    };
+    tui::Game::replay(Game::load_replay(m.value_of("INPUT").unwrap())?, speed)
+}
+
 fn solve(m: &ArgMatches) -> Result<()> {
-    // We can safely unwrap here since we set a default value
-    let input = m.value_of("INPUT").unwrap();
-    let s = read_to_string(input)?.parse::<Sudoku>()?;
+    let s = read_sudoku(m)?;
+    // We can also unwrap here for the same reason
+    let engine = m.value_of("engine").unwrap().parse::<Engine>()?;
 
-    if m.is_present("all") {
+    if m.is_present("explain") {
+        let mut deductions = s.deductions();
+        for (i, step) in deductions.by_ref().enumerate() {
+            println!("{}. {}", i + 1, step);
+        }
+        let board = deductions.board();
+        if !board.is_solved() {
+            println!("No further logical deductions can be made");
+        }
+        if m.is_present("pretty") {
+            println!("{:#}", board);
+        } else {
+            println!("{}", board);
+        }
+    } else if m.is_present("all") {
         let mut nsols = 0;
-        for sol in s.solutions() {
+        for sol in s.solutions_with(engine) {
             nsols += 1;
             if m.is_present("pretty") {
                 println!("{:#}", sol);
@@ -147,7 +413,7 @@ fn solve(m: &ArgMatches) -> Result<()> {
             if nsols == 1 { "" } else { "s" }
         );
     } else {
-        match s.solutions().next() {
+        match s.solutions_with(engine).next() {
             None => println!("No solution found"),
             Some(sol) => {
                 if m.is_present("pretty") {
@@ -162,15 +428,190 @@ fn solve(m: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+fn stats(m: &ArgMatches) -> Result<()> {
+    let top = m.value_of("top").unwrap();
+    let top = top.parse::<usize>().chain_err(
+        || format!("invalid number of games `{}`", top),
+    )?;
+    let path = match stats::stats_path() {
+        None => bail!("there is nowhere to keep statistics"),
+        Some(path) => path,
+    };
+    print!("{}", Stats::load(path)?.report(top, stats::now()));
+
+    Ok(())
+}
+
+/// Returns the `box` argument, which is shared by all subcommands that read or generate a sudoku.
+fn box_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("box")
+        .short("b")
+        .long("box")
+        .takes_value(true)
+        .help("Sets the dimensions of each box, as ROWSxCOLUMNS (e.g. 2x3)")
+}
+
+/// Returns the `jigsaw` argument, which is shared by all subcommands that generate a sudoku.
+fn jigsaw_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("jigsaw")
+        .short("j")
+        .long("jigsaw")
+        .help("Generates a jigsaw sudoku, whose boxes are replaced by irregular regions")
+}
+
+/// Returns the `killer` argument, which is shared by all subcommands that generate a sudoku.
+fn killer_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("killer")
+        .short("k")
+        .long("killer")
+        .help("Generates a killer sudoku, with cages whose entries must add up to a given sum")
+}
+
+/// Returns the `variant` argument, which is shared by all subcommands that read or generate a
+/// sudoku. It may be given more than once to combine variants.
+fn variant_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("variant")
+        .long("variant")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .possible_values(constraint::VARIANTS)
+        .help("Adds the rules of a sudoku variant")
+}
+
+/// Returns the `size` argument, which is shared by all subcommands that generate a sudoku.
+fn size_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("size")
+        .short("n")
+        .long("size")
+        .takes_value(true)
+        .help("Sets the size of the generated grid (e.g. 4, 6, 9, 12, 16 or 25)")
+}
+
+/// A helper function which parses the `box` or `size` argument into a shape, returning `None` if
+/// neither was given.
+fn parse_shape(m: &ArgMatches) -> Result<Option<Shape>> {
+    if let Some(b) = m.value_of("box") {
+        return Ok(Some(b.parse::<Shape>()?));
+    }
+    match m.value_of("size") {
+        None => Ok(None),
+        Some(size) => {
+            let size = size.parse::<usize>().chain_err(
+                || format!("invalid size `{}`", size),
+            )?;
+            Ok(Some(Shape::for_size(size)?))
+        }
+    }
+}
+
+/// A helper function which returns an empty sudoku to generate others like, with the shape given
+/// by the `box` or `size` argument and the variants given by the `variant` argument.
+fn parse_template(m: &ArgMatches) -> Result<Sudoku> {
+    let mut s = Sudoku::new(parse_shape(m)?.unwrap_or_default());
+    add_variants(m, &mut s)?;
+    Ok(s)
+}
+
+/// A helper function which adds the variants given by the `variant` argument to a sudoku.
+fn add_variants(m: &ArgMatches, s: &mut Sudoku) -> Result<()> {
+    for name in m.values_of("variant").into_iter().flatten() {
+        s.add_constraint(constraint::variant(name)?)
+            .chain_err(|| format!("the sudoku breaks the rules of the `{}` variant", name))?;
+    }
+    Ok(())
+}
+
+/// A helper function which reads and parses the sudoku given by the `INPUT` argument, using the
+/// shape given by the `box` argument if there is one and adding the variants given by the
+/// `variant` argument.
+fn read_sudoku(m: &ArgMatches) -> Result<Sudoku> {
+    // We can safely unwrap here since we set a default value (or checked for the input already)
+    let contents = read_to_string(m.value_of("INPUT").unwrap())?;
+    let mut s = match parse_shape(m)? {
+        None => contents.parse::<Sudoku>()?,
+        Some(shape) => Sudoku::parse_with_shape(&contents, shape)?,
+    };
+    add_variants(m, &mut s)?;
+    Ok(s)
+}
+
+/// Looks for an unfinished game of the same puzzle as the given one, which was saved automatically
+/// by the TUI (see `tui::autosave_path`), and asks whether to resume it instead, returning it if
+/// so. Since playing the given game would replace the saved one, the player is always asked, unless
+/// they are the same game.
+fn resume_autosave(game: &Game) -> Result<Option<Game>> {
+    match tui::autosave_path(game) {
+        Some(path) => ask_resume(&path, Some(game)),
+        None => Ok(None),
+    }
+}
+
+/// Looks for the unfinished game which was saved automatically by the TUI most recently and asks
+/// whether to resume it, returning the game if so.
+fn resume_latest_autosave() -> Result<Option<Game>> {
+    match tui::latest_autosave() {
+        Some(path) => ask_resume(&path, None),
+        None => Ok(None),
+    }
+}
+
+/// Asks whether to resume the unfinished game saved automatically at the given path, if there is
+/// one (and it isn't the same as `replacement`, the game which would replace it), returning the
+/// game if so.
+fn ask_resume(path: &Path, replacement: Option<&Game>) -> Result<Option<Game>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let game = match Game::load(path) {
+        Ok(ref game) if game.is_solved() || replacement == Some(game) => return Ok(None),
+        Ok(game) => game,
+        Err(e) => {
+            // A broken autosave shouldn't stop anyone from playing a new game
+            let causes = e.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+            eprintln!("Warning: ignoring autosaved game: {}", causes.join(": "));
+            return Ok(None);
+        }
+    };
+
+    print!(
+        "Found an unfinished game {}(played for {}). Resume it{}? [Y/n] ",
+        if replacement.is_some() { "of this puzzle " } else { "" },
+        util::format_duration(game.elapsed()),
+        if replacement.is_some() { " instead of replacing it" } else { "" }
+    );
+    io::stdout().flush().chain_err(|| "could not write to standard output")?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).chain_err(
+        || "could not read from standard input",
+    )?;
+    let answer = answer.trim().to_lowercase();
+    if answer.is_empty() || answer.starts_with('y') {
+        Ok(Some(game))
+    } else {
+        Ok(None)
+    }
+}
+
+/// A helper function which parses the `seed` argument, returning a random seed if none was given.
+fn parse_seed(m: &ArgMatches) -> Result<u64> {
+    match m.value_of("seed") {
+        None => Ok(util::random_seed()),
+        Some(seed) => seed.parse::<u64>().chain_err(
+            || format!("invalid seed `{}`", seed),
+        ),
+    }
+}
+
 /// A helper function which reads the given file to a string. The special filename `-` represents
 /// standard input.
 fn read_to_string(filename: &str) -> Result<String> {
     let mut br = if filename == "-" {
-        BufReader::new(Box::new(io::stdin()) as Box<Read>)
+        BufReader::new(Box::new(io::stdin()) as Box<dyn Read>)
     } else {
         BufReader::new(Box::new(File::open(filename).chain_err(|| {
             format!("could not open file `{}`", filename)
-        })?) as Box<Read>)
+        })?) as Box<dyn Read>)
     };
 
     let mut contents = String::new();
@@ -0,0 +1,184 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Hints which explain how the next number can be found, rather than just giving it away.
+//!
+//! A hint is found by solving the current board logically (see `sudoku::logic`), in the same way
+//! that a human player would, until a number can be placed. This means that a hint can be given a
+//! little at a time: first the technique to look for and where, then the cells involved, and only
+//! then the number itself.
+
+use rand::Rng;
+
+use errors::*;
+use sudoku::{digit_char, Annotations, Sudoku};
+use sudoku::logic::{Step, Technique, Unit};
+
+/// A hint for the current board of a game (see `Game::next_hint`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hint {
+    /// The deductions leading up to the placement, in the order they are made; the last one
+    /// places the number. If the placement can't be found logically, there are none.
+    steps: Vec<Step>,
+    /// Where to look for the first deduction, such as `row 4`, if it is in a single unit or cage.
+    region: Option<String>,
+    /// The number to place, as `(row, column, number)`.
+    placement: (usize, usize, u8),
+}
+
+impl Hint {
+    /// Finds a hint for the given board, with the given annotations. Annotations which the player
+    /// has made in a cell are taken to be its only candidates, unless they rule out the right
+    /// number there. If the board can't be solved logically, a number is chosen at random from
+    /// the empty cells using the given random number generator. Returns `None` if the board is
+    /// full, or an error if it has no solution.
+    pub(super) fn find<R: Rng>(
+        board: &Sudoku,
+        annotations: &[Vec<Annotations>],
+        rng: &mut R,
+    ) -> Result<Option<Self>> {
+        let solution = match board.solutions().next() {
+            None => return Err(ErrorKind::InvalidSudoku.into()),
+            Some(s) => s,
+        };
+        let size = board.size();
+        let empty = iproduct!(0..size, 0..size)
+            .filter(|&(i, j)| board[i][j] == 0)
+            .collect::<Vec<_>>();
+        if empty.is_empty() {
+            return Ok(None);
+        }
+
+        let mut deductions = board.deductions();
+        for &(i, j) in &empty {
+            let a = annotations[i][j];
+            if a.count() > 0 && a[solution[i][j]] {
+                deductions.restrict(i, j, a);
+            }
+        }
+        let mut steps = Vec::new();
+        for step in deductions {
+            let placement = step.placements.first().cloned();
+            steps.push(step);
+            if let Some(placement) = placement {
+                let region = region(board, &steps[0]);
+                return Ok(Some(Hint {
+                    steps,
+                    region,
+                    placement,
+                }));
+            }
+        }
+
+        // There's no way to avoid guessing (at least, none which we know of)
+        let &(row, col) = rng.choose(&empty).unwrap();
+        Ok(Some(Hint {
+            steps: Vec::new(),
+            region: None,
+            placement: (row, col, solution[row][col]),
+        }))
+    }
+
+    /// Returns the cells to look at: those which make up the pattern of the first deduction, or
+    /// just the cell of the placement if there are no deductions.
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        match self.steps.first() {
+            Some(step) => step.cells.clone(),
+            None => vec![(self.placement.0, self.placement.1)],
+        }
+    }
+
+    /// Returns a description of the first deduction which doesn't give away any cells, such as
+    /// `Look at row 4 for a hidden single`.
+    pub fn clue(&self) -> String {
+        let technique = match self.steps.first() {
+            None => return "No known technique helps here, so a number must be guessed".into(),
+            Some(step) => step.technique,
+        };
+        match self.region {
+            Some(ref region) => format!("Look at {} for {}", region, with_article(technique)),
+            None => format!("Look for {}", with_article(technique)),
+        }
+    }
+
+    /// Returns a description of the cells which make up the pattern of the first deduction (see
+    /// `cells`), which doesn't give away the number to place.
+    pub fn describe_cells(&self) -> String {
+        match self.steps.first() {
+            None => "The highlighted cell is a good place to guess".into(),
+            Some(step) => format!(
+                "The highlighted cells make up {}",
+                with_article(step.technique)
+            ),
+        }
+    }
+
+    /// Returns a full explanation of how the number is placed, such as `hidden single on 7 in row
+    /// 4: place 7 at r4c6`.
+    pub fn explain(&self) -> String {
+        let (row, col, n) = self.placement;
+        match self.steps.len() {
+            0 => format!(
+                "no known technique helps, so {} at r{}c{} was taken from the solution",
+                digit_char(n),
+                row + 1,
+                col + 1
+            ),
+            1 => self.steps[0].to_string(),
+            len => format!("{} (after {} other deductions)", self.steps[len - 1], len - 1),
+        }
+    }
+
+    /// Returns the number to place, as `(row, column, number)`.
+    pub fn placement(&self) -> (usize, usize, u8) {
+        self.placement
+    }
+
+    /// Returns the logical deductions leading up to the placement, in order; the last one places
+    /// the number. There are none if the number had to be guessed.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+/// Returns a description of the region of the board where the deduction is made, if it is
+/// within a single unit or cage.
+fn region(board: &Sudoku, step: &Step) -> Option<String> {
+    if let Some(unit) = step.unit {
+        return Some(unit.to_string());
+    }
+    if step.technique == Technique::CageCombination {
+        let &(row, col) = step.cells.iter().min()?;
+        return Some(format!("the cage at r{}c{}", row + 1, col + 1));
+    }
+    Unit::all(board)
+        .into_iter()
+        .find(|unit| {
+            let cells = unit.cells(board);
+            step.cells.iter().all(|c| cells.contains(c))
+        })
+        .map(|unit| unit.to_string())
+}
+
+/// Returns the name of the technique with the right indefinite article, such as `an X-Wing`.
+fn with_article(technique: Technique) -> String {
+    let name = technique.name();
+    if name.starts_with('X') {
+        format!("an {}", name)
+    } else {
+        format!("a {}", name)
+    }
+}
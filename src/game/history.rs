@@ -0,0 +1,296 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! The history of a game: the moves made by the player, kept in an undo tree.
+//!
+//! Like the undo tree in Vim, making a new move after undoing some others doesn't throw the undone
+//! moves away. Instead, the new move starts another branch of the tree, and the player can jump
+//! back to any move on any branch later on.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+use std::time::Duration;
+
+use errors::*;
+use sudoku::{digit_char, parse_digit, parse_position, Annotations};
+
+/// An action which changed the state of a game, as recorded in its undo history.
+///
+/// All positions are given as `(row, column)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// A number was put at a position.
+    Put(u8, (usize, usize)),
+    /// The number at a position was removed.
+    Remove((usize, usize)),
+    /// An annotation was toggled at a position.
+    Annotate(u8, (usize, usize)),
+    /// All the annotations at a position were cleared.
+    ClearAnnotations((usize, usize)),
+    /// A corner mark was toggled at a position.
+    CornerMark(u8, (usize, usize)),
+    /// All the corner marks at a position were cleared.
+    ClearCornerMarks((usize, usize)),
+    /// A number was put at a position as a hint.
+    Hint(u8, (usize, usize)),
+    /// The whole board was solved.
+    Solve,
+    /// The annotations of every empty position were replaced by its candidates (see
+    /// `Sudoku::candidates_at`).
+    FillAnnotations,
+}
+
+impl Action {
+    /// Returns the position which the action changed, if it changed a single position.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match *self {
+            Action::Put(_, p) |
+            Action::Remove(p) |
+            Action::Annotate(_, p) |
+            Action::ClearAnnotations(p) |
+            Action::CornerMark(_, p) |
+            Action::ClearCornerMarks(p) |
+            Action::Hint(_, p) => Some(p),
+            Action::Solve | Action::FillAnnotations => None,
+        }
+    }
+}
+
+impl Display for Action {
+    /// Formats the action as a short description, such as `put 5 at r2c3` (using 1-based
+    /// positions, as in the cages of a killer sudoku). This can be parsed again using `FromStr`.
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let pos = |(row, col): (usize, usize)| format!("r{}c{}", row + 1, col + 1);
+        match *self {
+            Action::Put(n, p) => write!(f, "put {} at {}", digit_char(n), pos(p)),
+            Action::Remove(p) => write!(f, "clear {}", pos(p)),
+            Action::Annotate(n, p) => write!(f, "annotate {} at {}", digit_char(n), pos(p)),
+            Action::ClearAnnotations(p) => write!(f, "clear annotations at {}", pos(p)),
+            Action::CornerMark(n, p) => write!(f, "corner mark {} at {}", digit_char(n), pos(p)),
+            Action::ClearCornerMarks(p) => write!(f, "clear corner marks at {}", pos(p)),
+            Action::Hint(n, p) => write!(f, "hint {} at {}", digit_char(n), pos(p)),
+            Action::Solve => write!(f, "solve"),
+            Action::FillAnnotations => write!(f, "fill annotations"),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = Error;
+
+    /// Parses an action in the format given by its `Display` implementation.
+    fn from_str(s: &str) -> Result<Self> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let number = |w: &str| -> Result<u8> {
+            match w.chars().next().and_then(parse_digit) {
+                Some(n) if w.len() == 1 => Ok(n),
+                _ => bail!(ErrorKind::Parse(format!("invalid number `{}` in action", w))),
+            }
+        };
+        Ok(match words[..] {
+            ["put", n, "at", p] => Action::Put(number(n)?, parse_position(p)?),
+            ["clear", "annotations", "at", p] => Action::ClearAnnotations(parse_position(p)?),
+            ["clear", "corner", "marks", "at", p] => Action::ClearCornerMarks(parse_position(p)?),
+            ["clear", p] => Action::Remove(parse_position(p)?),
+            ["annotate", n, "at", p] => Action::Annotate(number(n)?, parse_position(p)?),
+            ["corner", "mark", n, "at", p] => Action::CornerMark(number(n)?, parse_position(p)?),
+            ["hint", n, "at", p] => Action::Hint(number(n)?, parse_position(p)?),
+            ["solve"] => Action::Solve,
+            ["fill", "annotations"] => Action::FillAnnotations,
+            _ => bail!(ErrorKind::Parse(format!("invalid action `{}`", s))),
+        })
+    }
+}
+
+/// The change made by a move to a single cell, as its number (or 0 if it was empty), annotations
+/// and corner marks before and after the move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Change {
+    pub(super) position: (usize, usize),
+    pub(super) before: (u8, Annotations, Annotations),
+    pub(super) after: (u8, Annotations, Annotations),
+}
+
+/// A move in the undo tree of a game: an action made by the player, along with the changes it
+/// made to the board so that it can be undone and redone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    action: Action,
+    /// The time spent playing the game when the move was made.
+    time: Duration,
+    /// The index of the move which this one was made after, or `None` if it was made from the
+    /// start of the game.
+    parent: Option<usize>,
+    /// The index of the move which is made again when redoing from this one.
+    next: Option<usize>,
+    /// Whether the move was made together with its parent, as part of a single change to several
+    /// positions, so that they are undone and redone together.
+    joined: bool,
+    changes: Vec<Change>,
+}
+
+impl Move {
+    /// Returns the action which was made.
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// Returns whether the move was made together with its parent, as part of a single change to
+    /// several positions (such as putting a number in every selected position). Undoing or
+    /// redoing either of them undoes or redoes the other as well.
+    pub fn joined(&self) -> bool {
+        self.joined
+    }
+
+    /// Returns the index of the move which this one was made after, or `None` if it was the
+    /// first move of its branch from the start of the game.
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// Returns whether the move removed the number it put from the annotations or corner marks of
+    /// the positions which see it (see `Game::set_autoprune`).
+    pub fn pruned(&self) -> bool {
+        match self.action {
+            Action::Put(_, p) => self.changes.iter().any(|c| c.position != p),
+            _ => false,
+        }
+    }
+
+    /// Returns the time spent playing the game when the move was made.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the changes made by the move.
+    pub(super) fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+/// The undo tree of a game: every move which has been made, in the order they were made, along
+/// with the move which led to the current state.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct UndoTree {
+    moves: Vec<Move>,
+    /// The index of the last move leading to the current state, or `None` at the start.
+    current: Option<usize>,
+    /// The index of the move which is made again when redoing from the start.
+    first: Option<usize>,
+}
+
+impl UndoTree {
+    /// Returns the index of the last move leading to the current state, if any.
+    pub(super) fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Returns whether the move at the given index is the one which is made when redoing from its
+    /// parent.
+    pub(super) fn is_next(&self, index: usize) -> bool {
+        let next = match self.moves[index].parent {
+            Some(p) => self.moves[p].next,
+            None => self.first,
+        };
+        next == Some(index)
+    }
+
+    /// Returns the indices of the moves which haven't been followed by any others, which are the
+    /// tips of the branches of the tree, in the order they were made.
+    pub(super) fn leaves(&self) -> Vec<usize> {
+        let mut is_leaf = vec![true; self.moves.len()];
+        for parent in self.moves.iter().filter_map(|m| m.parent) {
+            is_leaf[parent] = false;
+        }
+        (0..self.moves.len()).filter(|&i| is_leaf[i]).collect()
+    }
+
+    /// Returns all the moves in the tree, in the order they were made.
+    pub(super) fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Returns the index of the move which `redo` would make, if any.
+    pub(super) fn next(&self) -> Option<usize> {
+        match self.current {
+            Some(i) => self.moves[i].next,
+            None => self.first,
+        }
+    }
+
+    /// Returns the indices of the moves leading from the start to the move at `index` (or to the
+    /// start itself, if `index` is `None`), oldest first.
+    pub(super) fn path(&self, index: Option<usize>) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut index = index;
+        while let Some(i) = index {
+            path.push(i);
+            index = self.moves[i].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Adds a new move, made at the given time, after the current one, which becomes the current
+    /// move. If `joined` is set, the move is joined to the current one (see `Move::joined`).
+    pub(super) fn push(
+        &mut self,
+        action: Action,
+        time: Duration,
+        joined: bool,
+        changes: Vec<Change>,
+    ) {
+        assert!(!joined || self.current.is_some(), "there is no move to join");
+        let index = self.moves.len();
+        self.moves.push(Move {
+            action,
+            time,
+            parent: self.current,
+            next: None,
+            joined,
+            changes,
+        });
+        self.set_next(self.current, index);
+        self.current = Some(index);
+    }
+
+    /// Moves back to the parent of the current move, returning the index of the move to be undone
+    /// (if there is one). Redoing afterwards will return to the same move.
+    pub(super) fn undo(&mut self) -> Option<usize> {
+        let index = self.current?;
+        self.current = self.moves[index].parent;
+        self.set_next(self.current, index);
+        Some(index)
+    }
+
+    /// Moves forward to the move which is next to be redone, returning its index (if there is
+    /// one).
+    pub(super) fn redo(&mut self) -> Option<usize> {
+        let index = self.next()?;
+        self.current = Some(index);
+        Some(index)
+    }
+
+    /// Sets the move which is made when redoing from the move at `parent` (or from the start, if
+    /// `parent` is `None`). The move must be a child of `parent`.
+    pub(super) fn set_next(&mut self, parent: Option<usize>, index: usize) {
+        assert_eq!(self.moves[index].parent, parent, "move {} is not a child", index);
+        match parent {
+            Some(p) => self.moves[p].next = Some(index),
+            None => self.first = Some(index),
+        }
+    }
+}
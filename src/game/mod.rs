@@ -0,0 +1,899 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Universal backend for the interactive Sudoku game.
+
+use std::default::Default;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::{self, Rng};
+
+use errors::*;
+use sudoku::{Annotations, Sudoku};
+use sudoku::logic::{Difficulty, Unit};
+use util;
+
+mod hint;
+mod history;
+mod save;
+
+pub use self::hint::Hint;
+pub use self::history::{Action, Move};
+
+use self::history::{Change, UndoTree};
+
+/// Represents the state of a game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    /// The board currently in play.
+    board: Sudoku,
+    /// The original (given) board.
+    given: Sudoku,
+    /// The unique solution of the given board, or `None` if it doesn't have exactly one.
+    solution: Option<Sudoku>,
+    /// User annotations (also known as centre marks): the numbers which could go in each cell.
+    annotations: Vec<Vec<Annotations>>,
+    /// User corner marks: the numbers which must go in one of the cells of a box (or some other
+    /// unit) which have them.
+    corner_marks: Vec<Vec<Annotations>>,
+    /// The current position as `(row, column)`.
+    position: (usize, usize),
+    /// The selected positions, in order (see `select`).
+    selection: Vec<(usize, usize)>,
+    /// Every move which has been made, including those which have been undone.
+    history: UndoTree,
+    /// Whether the current board has been solved.
+    is_solved: bool,
+    /// The seed used to generate the board, if it was generated.
+    seed: Option<u64>,
+    /// The time spent playing the game so far.
+    elapsed: Duration,
+    /// The number of hints given so far.
+    hints: u32,
+    /// The number of moves undone so far.
+    undos: u32,
+    /// The number of numbers put so far which were mistakes (see `mistakes`).
+    mistakes: u32,
+    /// How the game went up to the point it was first solved, if it has been.
+    completion: Option<Completion>,
+    /// Whether putting a number removes it from the annotations and corner marks of the positions
+    /// which see it.
+    autoprune: bool,
+}
+
+/// A record of how a game went up to the point it was first solved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Completion {
+    time: Duration,
+    hints: u32,
+    undos: u32,
+    mistakes: u32,
+    solver: bool,
+}
+
+/// A way of checking the numbers on the board for mistakes (see `Game::is_mistake_at`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckMode {
+    /// Nothing is checked.
+    Off,
+    /// Numbers which break the rules of the board, by clashing with another number or (in a
+    /// killer sudoku) their cage, are mistakes.
+    Conflicts,
+    /// Numbers which are different from the solution are mistakes. If the board doesn't have a
+    /// unique solution, this is the same as `Conflicts`.
+    Solution,
+}
+
+impl CheckMode {
+    /// All the ways of checking the board.
+    pub const ALL: &'static [CheckMode] = &[
+        CheckMode::Off,
+        CheckMode::Conflicts,
+        CheckMode::Solution,
+    ];
+
+    /// Returns the name of the mode, as used in the TUI.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            CheckMode::Off => "off",
+            CheckMode::Conflicts => "conflicts",
+            CheckMode::Solution => "solution",
+        }
+    }
+}
+
+impl Display for CheckMode {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for CheckMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match CheckMode::ALL.iter().find(|m| m.name().eq_ignore_ascii_case(s)) {
+            Some(&m) => Ok(m),
+            None => bail!(ErrorKind::Parse(format!("unknown check mode `{}`", s))),
+        }
+    }
+}
+
+impl Completion {
+    /// Returns a record of a game which was solved after the given time, with the given numbers
+    /// of hints, undos and mistakes. If `solver` is set, the game was solved using the `solve`
+    /// action rather than by the player.
+    pub fn new(time: Duration, hints: u32, undos: u32, mistakes: u32, solver: bool) -> Self {
+        Completion {
+            time,
+            hints,
+            undos,
+            mistakes,
+            solver,
+        }
+    }
+
+    /// Returns the number of hints given before the game was solved.
+    pub fn hints(&self) -> u32 {
+        self.hints
+    }
+
+    /// Returns the number of numbers put which were mistakes before the game was solved (see
+    /// `Game::mistakes`).
+    pub fn mistakes(&self) -> u32 {
+        self.mistakes
+    }
+
+    /// Returns whether the game was solved using the `solve` action, rather than by the player.
+    pub fn solver(&self) -> bool {
+        self.solver
+    }
+
+    /// Returns the time spent playing the game before it was solved.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the number of moves undone before the game was solved.
+    pub fn undos(&self) -> u32 {
+        self.undos
+    }
+}
+
+impl Game {
+    /// Returns a new game using a randomly generated (uniquely solvable) board.
+    pub fn new() -> Self {
+        Game::from_seed(util::random_seed())
+    }
+
+    /// Returns a new game using a randomly generated (uniquely solvable) board of the given
+    /// difficulty.
+    pub fn with_difficulty(difficulty: Difficulty) -> Result<Self> {
+        Game::from_seed_with_difficulty(util::random_seed(), difficulty)
+    }
+
+    /// Returns a new game using a board generated from the given seed. The same seed will always
+    /// give the same board.
+    pub fn from_seed(seed: u64) -> Self {
+        let mut game = Game::from_sudoku(Sudoku::generate_with_rng(&mut util::seeded_rng(seed)));
+        game.seed = Some(seed);
+        game
+    }
+
+    /// Returns a new game using a board of the given difficulty generated from the given seed. The
+    /// same seed and difficulty will always give the same board.
+    pub fn from_seed_with_difficulty(seed: u64, difficulty: Difficulty) -> Result<Self> {
+        let mut rng = util::seeded_rng(seed);
+        let mut game = Game::from_sudoku(
+            Sudoku::generate_with_difficulty_and_rng(difficulty, &mut rng)?,
+        );
+        game.seed = Some(seed);
+        Ok(game)
+    }
+
+    /// Returns a new game using a board generated from the given seed with the same shape as
+    /// `template`, optionally of the given difficulty (see `Sudoku::generate_like`).
+    pub fn from_seed_like(
+        seed: u64,
+        template: &Sudoku,
+        difficulty: Option<Difficulty>,
+    ) -> Result<Self> {
+        let mut rng = util::seeded_rng(seed);
+        let mut game = Game::from_sudoku(template.generate_like(difficulty, &mut rng)?);
+        game.seed = Some(seed);
+        Ok(game)
+    }
+
+    /// Returns a new game using a killer sudoku generated from the given seed with the same shape
+    /// as `template`, optionally of the given difficulty (see `Sudoku::generate_killer_like`).
+    pub fn from_seed_killer(
+        seed: u64,
+        template: &Sudoku,
+        difficulty: Option<Difficulty>,
+    ) -> Result<Self> {
+        let mut rng = util::seeded_rng(seed);
+        let mut game = Game::from_sudoku(template.generate_killer_like(difficulty, &mut rng)?);
+        game.seed = Some(seed);
+        Ok(game)
+    }
+
+    /// Returns a new game using a jigsaw sudoku (or killer jigsaw sudoku, if `killer` is set)
+    /// generated from the given seed with the same shape as `template`, optionally of the given
+    /// difficulty (see `Sudoku::generate_jigsaw_like`).
+    pub fn from_seed_jigsaw(
+        seed: u64,
+        template: &Sudoku,
+        killer: bool,
+        difficulty: Option<Difficulty>,
+    ) -> Result<Self> {
+        let mut rng = util::seeded_rng(seed);
+        let board = if killer {
+            template.generate_killer_jigsaw_like(difficulty, &mut rng)?
+        } else {
+            template.generate_jigsaw_like(difficulty, &mut rng)?
+        };
+        let mut game = Game::from_sudoku(board);
+        game.seed = Some(seed);
+        Ok(game)
+    }
+
+    /// Returns a new game using the given board.
+    pub fn from_sudoku(given: Sudoku) -> Self {
+        let size = given.size();
+        let solution = {
+            let mut solutions = given.solutions();
+            match (solutions.next(), solutions.next()) {
+                (Some(s), None) => Some(s),
+                _ => None,
+            }
+        };
+        Game {
+            board: given.clone(),
+            given,
+            solution,
+            annotations: vec![vec![Annotations::with_size(size); size]; size],
+            corner_marks: vec![vec![Annotations::with_size(size); size]; size],
+            position: (0, 0),
+            selection: Vec::new(),
+            history: UndoTree::default(),
+            is_solved: false,
+            seed: None,
+            elapsed: Duration::from_secs(0),
+            hints: 0,
+            undos: 0,
+            mistakes: 0,
+            completion: None,
+            autoprune: false,
+        }
+    }
+
+    /// Adds the given amount of time to the time spent playing the game. The game doesn't keep
+    /// time by itself, so this is up to the interface.
+    pub fn add_elapsed(&mut self, time: Duration) {
+        self.elapsed += time;
+    }
+
+    /// Toggles the given annotation at the current position. If any positions are selected (see
+    /// `select`), the annotation is added to all of those which are empty, unless they all have it
+    /// already, in which case it is removed from all of them.
+    pub fn annotate(&mut self, n: u8) {
+        assert!(n >= 1 && n as usize <= self.board.size());
+        self.toggle_marks(n, false);
+    }
+
+    /// Returns a reference to the user annotations array.
+    pub fn annotations(&self) -> &[Vec<Annotations>] {
+        &self.annotations
+    }
+
+    /// Returns whether putting a number removes it from the annotations and corner marks of the
+    /// positions which see it (see `set_autoprune`).
+    pub fn autoprune(&self) -> bool {
+        self.autoprune
+    }
+
+    /// Returns a reference to the current board.
+    pub fn board(&self) -> &Sudoku {
+        &self.board
+    }
+
+    /// Returns the indices of the moves at the tips of the branches of the undo tree, in the order
+    /// they were made. These are the moves which haven't been followed by any others.
+    pub fn branches(&self) -> Vec<usize> {
+        self.history.leaves()
+    }
+
+    /// Clears the annotations at the current position, or at every selected position (see
+    /// `select`).
+    pub fn clear_annotations(&mut self) {
+        let actions = self
+            .targets()
+            .into_iter()
+            .filter(|&(row, col)| self.annotations[row][col].count() > 0)
+            .map(Action::ClearAnnotations)
+            .collect();
+        self.perform_all(actions);
+    }
+
+    /// Clears the corner marks at the current position, or at every selected position (see
+    /// `select`).
+    pub fn clear_corner_marks(&mut self) {
+        let actions = self
+            .targets()
+            .into_iter()
+            .filter(|&(row, col)| self.corner_marks[row][col].count() > 0)
+            .map(Action::ClearCornerMarks)
+            .collect();
+        self.perform_all(actions);
+    }
+
+    /// Clears the selection, so that only the current position is changed (see `select`).
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    /// Returns a reference to the user corner marks array.
+    pub fn corner_marks(&self) -> &[Vec<Annotations>] {
+        &self.corner_marks
+    }
+
+    /// Returns the index of the last move leading to the current state, or `None` if no moves
+    /// lead to it (because they have all been undone, or none were made).
+    pub fn current_move(&self) -> Option<usize> {
+        self.history.current()
+    }
+
+    /// Replaces the annotations of every empty position with its candidates: the numbers which can
+    /// be put there without breaking the rules (see `Sudoku::candidates_at`). This is a single
+    /// move, so it can be undone all at once.
+    pub fn fill_annotations(&mut self) {
+        self.perform(Action::FillAnnotations);
+    }
+
+    /// Returns how the game went up to the point it was first solved, or `None` if it hasn't been
+    /// solved yet. Solving it again after undoing some moves doesn't change this.
+    pub fn completion(&self) -> Option<Completion> {
+        self.completion
+    }
+
+    /// Returns the time spent playing the game so far (see `add_elapsed`).
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns a reference to the originally given board before any user input.
+    pub fn given(&self) -> &Sudoku {
+        &self.given
+    }
+
+    /// Returns the number of hints given so far.
+    pub fn hints(&self) -> u32 {
+        self.hints
+    }
+
+    /// Gives a hint for the current board by placing the number found by `next_hint`, returning
+    /// the position of the hint given (or `None` if the sudoku was already solved). An error will
+    /// be returned if the current board is in an invalid state (has no solution).
+    pub fn hint(&mut self) -> Result<Option<(usize, usize)>> {
+        self.hint_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Gives a hint for the current board, using the given random number generator to choose
+    /// where to place it if it can't be found logically. See `hint` for details.
+    pub fn hint_with_rng<R: Rng>(&mut self, rng: &mut R) -> Result<Option<(usize, usize)>> {
+        match self.next_hint_with_rng(rng)? {
+            None => Ok(None),
+            Some(hint) => {
+                let (row, col, n) = hint.placement();
+                self.perform(Action::Hint(n, (row, col)));
+                Ok(Some((row, col)))
+            }
+        }
+    }
+
+    /// Returns whether the number at position `(row, col)` is a mistake, according to the given
+    /// way of checking the board. An empty position or a given number is never a mistake.
+    pub fn is_mistake_at(&self, row: usize, col: usize, mode: CheckMode) -> bool {
+        let n = self.board[row][col];
+        if n == 0 || self.given[row][col] != 0 {
+            return false;
+        }
+        match (mode, self.solution.as_ref()) {
+            (CheckMode::Off, _) => false,
+            (CheckMode::Solution, Some(s)) => s[row][col] != n,
+            (CheckMode::Conflicts, _) | (CheckMode::Solution, None) => {
+                self.board.is_conflict_at(row, col)
+            }
+        }
+    }
+
+    /// Returns whether position `(row, col)` is selected (see `select`).
+    pub fn is_selected(&self, row: usize, col: usize) -> bool {
+        self.selection.binary_search(&(row, col)).is_ok()
+    }
+
+    /// Returns whether the current board has been solved.
+    pub fn is_solved(&self) -> bool {
+        self.is_solved
+    }
+
+    /// Undoes and redoes moves until the state is the one right after the move at the given index
+    /// (or the start of the game, if `index` is `None`), which may be on another branch of the
+    /// undo tree.
+    ///
+    /// # Panics
+    /// Will panic if there is no move at `index`.
+    pub fn jump_to(&mut self, index: Option<usize>) {
+        if let Some(i) = index {
+            assert!(i < self.history.moves().len(), "there is no move {}", i);
+        }
+        let path = self.history.path(index);
+        // Undo back to the last move in common, and then redo along the path. Each group of
+        // joined moves undone counts as a single undo, as it does in `undo`.
+        let mut in_group = false;
+        while self.history.current().is_some_and(|i| !path.contains(&i)) {
+            let index = self.undo_one().unwrap();
+            if !in_group {
+                self.undos += 1;
+            }
+            in_group = self.history.moves()[index].joined();
+        }
+        let start = match self.history.current() {
+            Some(i) => path.iter().position(|&p| p == i).unwrap() + 1,
+            None => 0,
+        };
+        for &i in &path[start..] {
+            self.history.set_next(self.history.current(), i);
+            self.redo_one();
+            self.check_completion(self.history.moves()[i].action());
+        }
+    }
+
+    /// Toggles the given corner mark at the current position. Corner marks are a second layer of
+    /// annotations, which are usually used to show the cells of a box where a number must go,
+    /// rather than the numbers which could go in a cell. Several selected positions (see `select`)
+    /// are changed in the same way as by `annotate`.
+    pub fn mark_corner(&mut self, n: u8) {
+        assert!(n >= 1 && n as usize <= self.board.size());
+        self.toggle_marks(n, true);
+    }
+
+    /// Returns the number of numbers put so far which were mistakes: they broke the rules of the
+    /// board where they were put (see `Sudoku::is_conflict_at`), or they were different from the
+    /// solution (if the board has a unique one). Hints and the `solve` action are never mistakes.
+    pub fn mistakes(&self) -> u32 {
+        self.mistakes
+    }
+
+    /// Moves the current position in the grid by the given amount in each direction.
+    /// If the motion in either direction would take the position outside the grid, the position in
+    /// that direction will be unchanged.
+    pub fn move_by(&mut self, rows: isize, cols: isize) {
+        let (row, col) = self.position();
+        let (newrow, newcol) = (row as isize + rows, col as isize + cols);
+        let size = self.board.size() as isize;
+        self.set_position(
+            if (0..size).contains(&newrow) {
+                newrow as usize
+            } else {
+                row
+            },
+            if (0..size).contains(&newcol) {
+                newcol as usize
+            } else {
+                col
+            },
+        );
+    }
+
+    /// Returns the actions of the moves leading from the start of the game to the current state,
+    /// in the order they were made. Making them again on the given board gives the current state
+    /// (see `perform`), so this can be used to replay the game.
+    pub fn move_log(&self) -> Vec<Action> {
+        let path = self.history.path(self.history.current());
+        path.into_iter().map(|i| self.history.moves()[i].action()).collect()
+    }
+
+    /// Returns every move in the undo tree, including those which have been undone, in the order
+    /// they were made.
+    pub fn moves(&self) -> &[Move] {
+        self.history.moves()
+    }
+
+    /// Returns a hint explaining how the next number can be found on the current board, without
+    /// giving it (see `Hint`). The hint is found by solving the board logically, starting from the
+    /// player's annotations in the cells where they have made any (as long as they don't rule out
+    /// the right number). Returns `None` if the board is full, or an error if it has no solution.
+    pub fn next_hint(&self) -> Result<Option<Hint>> {
+        self.next_hint_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Returns a hint for the current board, using the given random number generator to choose
+    /// the number to give if it can't be found logically. See `next_hint` for details.
+    pub fn next_hint_with_rng<R: Rng>(&self, rng: &mut R) -> Result<Option<Hint>> {
+        Hint::find(&self.board, &self.annotations, rng)
+    }
+
+    /// Makes the given action as a new move at the current time (see `elapsed`), returning `true`
+    /// if it could be made. An action can't be made if it would change a given number, or if it
+    /// would solve a board which has no solution.
+    ///
+    /// # Panics
+    /// Will panic if the position or number of the action is not valid for the board.
+    pub fn perform(&mut self, action: Action) -> bool {
+        self.make(action, false)
+    }
+
+    /// Makes the given action as a new move, joined to the current one if `joined` is set (see
+    /// `Move::joined`). See `perform` for details.
+    fn make(&mut self, action: Action, joined: bool) -> bool {
+        let size = self.board.size();
+        let mut cells = match action.position() {
+            Some((row, col)) => {
+                assert!(row < size && col < size, "position ({}, {}) is invalid", row, col);
+                vec![(row, col)]
+            }
+            None => iproduct!(0..size, 0..size).collect(),
+        };
+        if let Action::Put(_, (row, col)) = action {
+            if self.autoprune {
+                cells.extend(self.board.peers(row, col));
+            }
+        }
+        let before = cells
+            .iter()
+            .map(|&(row, col)| self.cell_state(row, col))
+            .collect::<Vec<_>>();
+
+        match action {
+            Action::Put(n, (row, col)) | Action::Hint(n, (row, col)) => {
+                assert!(n >= 1 && n as usize <= size, "entry number `{}` is invalid", n);
+                if self.given[row][col] != 0 {
+                    return false;
+                }
+                self.board.put_at(n, row, col);
+                // Only a put has the positions which see it to prune
+                for &(i, j) in &cells[1..] {
+                    self.annotations[i][j].set(n, false);
+                    self.corner_marks[i][j].set(n, false);
+                }
+            }
+            Action::Remove((row, col)) => {
+                if self.given[row][col] != 0 {
+                    return false;
+                }
+                self.board.remove_at(row, col);
+            }
+            Action::Annotate(n, (row, col)) => {
+                assert!(n >= 1 && n as usize <= size, "annotation `{}` is invalid", n);
+                self.annotations[row][col].toggle(n);
+            }
+            Action::ClearAnnotations((row, col)) => self.annotations[row][col].clear(),
+            Action::CornerMark(n, (row, col)) => {
+                assert!(n >= 1 && n as usize <= size, "corner mark `{}` is invalid", n);
+                self.corner_marks[row][col].toggle(n);
+            }
+            Action::ClearCornerMarks((row, col)) => self.corner_marks[row][col].clear(),
+            Action::Solve => match self.board.solutions().next() {
+                None => return false,
+                Some(s) => self.board = s,
+            },
+            Action::FillAnnotations => for (row, col) in cells.iter().cloned() {
+                if self.board[row][col] == 0 {
+                    self.annotations[row][col] = self.board.candidates_at(row, col);
+                }
+            },
+        }
+
+        // Only the cells which actually changed need to be remembered
+        let changes = cells
+            .into_iter()
+            .zip(before)
+            .map(|((row, col), before)| Change {
+                position: (row, col),
+                before,
+                after: self.cell_state(row, col),
+            })
+            .filter(|c| c.before != c.after)
+            .collect();
+        self.history.push(action, self.elapsed, joined, changes);
+        match action {
+            Action::Put(_, (row, col)) => {
+                let wrong = self.is_mistake_at(row, col, CheckMode::Solution);
+                if wrong || self.board.is_conflict_at(row, col) {
+                    self.mistakes += 1;
+                }
+            }
+            Action::Hint(..) => self.hints += 1,
+            _ => {}
+        }
+        // Check to see if we've solved (or unsolved) the board with this move
+        self.is_solved = self.board.is_solved();
+        self.check_completion(action);
+        true
+    }
+
+    /// Returns the current position in the game grid.
+    pub fn position(&self) -> (usize, usize) {
+        self.position
+    }
+
+    /// Puts the given number at the current position in the game grid, or at every selected
+    /// position (see `select`).
+    ///
+    /// If a number is already in a position as a given, nothing will happen there.
+    ///
+    /// # Panics
+    /// Will panic if `n` is not between 1 and the size of the board, inclusive.
+    pub fn put(&mut self, n: u8) {
+        assert!(
+            n >= 1 && n as usize <= self.board.size(),
+            "entry number `{}` is invalid",
+            n
+        );
+
+        let actions = self.targets().into_iter().map(|p| Action::Put(n, p)).collect();
+        self.perform_all(actions);
+    }
+
+    /// Returns an identifier for the given board, which is the same for every game with the same
+    /// board (whether it was generated or not). This is a hash of the board, so it is very likely,
+    /// but not guaranteed, to be different for different boards.
+    pub fn puzzle_id(&self) -> String {
+        // This is the 64-bit FNV-1a hash, which (unlike the standard library's hasher) is
+        // guaranteed to stay the same in every version
+        let hash = self.given.to_string().bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+            (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    /// Removes the number at the current position in the game grid, or at every selected position
+    /// (see `select`).
+    ///
+    /// If a number is already in a position as a given, nothing will happen there.
+    pub fn remove(&mut self) {
+        let actions = self
+            .targets()
+            .into_iter()
+            .filter(|&(row, col)| self.board[row][col] != 0)
+            .map(Action::Remove)
+            .collect();
+        self.perform_all(actions);
+    }
+
+    /// Returns the seed used to generate the board, or `None` if the board was not generated.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Adds position `(row, col)` to the selection. While any positions are selected, `put`,
+    /// `remove`, `annotate` and `clear_annotations` change all of them at once, in a single move
+    /// which is undone all at once (see `Move::joined`), rather than the current position.
+    pub fn select(&mut self, row: usize, col: usize) {
+        let size = self.board.size();
+        assert!(row < size && col < size, "position ({}, {}) is invalid", row, col);
+        if let Err(i) = self.selection.binary_search(&(row, col)) {
+            self.selection.insert(i, (row, col));
+        }
+    }
+
+    /// Adds every position holding the given number to the selection (see `select`).
+    pub fn select_number(&mut self, n: u8) {
+        let size = self.board.size();
+        for (row, col) in iproduct!(0..size, 0..size) {
+            if self.board[row][col] == n {
+                self.select(row, col);
+            }
+        }
+    }
+
+    /// Adds every position in the given unit to the selection (see `select`).
+    pub fn select_unit(&mut self, unit: Unit) {
+        for (row, col) in unit.cells(&self.board) {
+            self.select(row, col);
+        }
+    }
+
+    /// Returns the selected positions, in order (see `select`).
+    pub fn selection(&self) -> &[(usize, usize)] {
+        &self.selection
+    }
+
+    /// Returns the unique solution of the given board, or `None` if it has no solution or more
+    /// than one.
+    pub fn solution(&self) -> Option<&Sudoku> {
+        self.solution.as_ref()
+    }
+
+    /// Sets whether putting a number removes it from the annotations and corner marks of the
+    /// positions which see it (in the same row, column or box, for example). The marks removed
+    /// are part of the same move, so undoing it brings them back. The setting is saved along with
+    /// the game.
+    pub fn set_autoprune(&mut self, autoprune: bool) {
+        self.autoprune = autoprune;
+    }
+
+    /// Sets the current position in the game grid. Note that both the row and the column are
+    /// 0-based.
+    pub fn set_position(&mut self, row: usize, col: usize) {
+        let size = self.board.size();
+        assert!(
+            row < size && col < size,
+            "position ({}, {}) is invalid",
+            row,
+            col
+        );
+
+        self.position = (row, col);
+    }
+
+    /// Attempts to solve the board completely, returning `true` if this was actually done.
+    pub fn solve(&mut self) -> bool {
+        self.perform(Action::Solve)
+    }
+
+    /// Reverts the last move leading to the current state, along with any moves it was made
+    /// together with (see `Move::joined`), returning `true` if there was such a move and `false`
+    /// otherwise. The move which was undone can be made again using `redo`.
+    pub fn undo(&mut self) -> bool {
+        let mut index = match self.undo_one() {
+            None => return false,
+            Some(index) => index,
+        };
+        while self.history.moves()[index].joined() {
+            index = self.undo_one().unwrap();
+        }
+        self.undos += 1;
+        true
+    }
+
+    /// Returns the number of moves undone so far, including those undone to go to another branch
+    /// of the undo tree (see `jump_to`). Moves made together (see `Move::joined`) count once.
+    pub fn undos(&self) -> u32 {
+        self.undos
+    }
+
+    /// Returns the action which `undo` would revert, if any.
+    pub fn undo_action(&self) -> Option<Action> {
+        self.history.current().map(|i| self.history.moves()[i].action())
+    }
+
+    /// Makes the most recently undone move again, returning `true` if there was such a move and
+    /// `false` otherwise. If the undone move has been followed by new moves on another branch, it
+    /// can still be redone, but only from the state it was made from.
+    pub fn redo(&mut self) -> bool {
+        let mut index = match self.redo_one() {
+            None => return false,
+            Some(index) => index,
+        };
+        while let Some(next) = self.history.next().filter(|&i| self.history.moves()[i].joined()) {
+            self.redo_one();
+            index = next;
+        }
+        let action = self.history.moves()[index].action();
+        self.check_completion(action);
+        true
+    }
+
+    /// Returns the action which `redo` would make again, if any.
+    pub fn redo_action(&self) -> Option<Action> {
+        self.history.next().map(|i| self.history.moves()[i].action())
+    }
+
+    /// Records how the game went if it has just been solved for the first time, by making the given
+    /// action.
+    fn check_completion(&mut self, action: Action) {
+        if self.is_solved && self.completion.is_none() {
+            self.completion = Some(Completion::new(
+                self.elapsed,
+                self.hints,
+                self.undos,
+                self.mistakes,
+                action == Action::Solve,
+            ));
+        }
+    }
+
+    /// Returns the state of position `(row, col)` which moves can change: its number (or 0 if it is
+    /// empty), annotations and corner marks.
+    fn cell_state(&self, row: usize, col: usize) -> (u8, Annotations, Annotations) {
+        (
+            self.board[row][col],
+            self.annotations[row][col],
+            self.corner_marks[row][col],
+        )
+    }
+
+    /// Makes the given actions as a single move, each joined to the one before (see
+    /// `Move::joined`), skipping those which can't be made. Returns whether any could be made.
+    fn perform_all(&mut self, actions: Vec<Action>) -> bool {
+        let mut made = false;
+        for action in actions {
+            made |= self.make(action, made);
+        }
+        made
+    }
+
+    /// Redoes the move which `redo` would make on its own, even if it was made together with
+    /// others, returning its index (if there is one).
+    fn redo_one(&mut self) -> Option<usize> {
+        let index = self.history.redo()?;
+        self.apply(index, false);
+        Some(index)
+    }
+
+    /// Returns the positions which are changed by `put` and the like: the selected positions, or
+    /// the current position if none are selected.
+    fn targets(&self) -> Vec<(usize, usize)> {
+        if self.selection.is_empty() {
+            vec![self.position]
+        } else {
+            self.selection.clone()
+        }
+    }
+
+    /// Toggles the given number in the annotations, or the corner marks if `corner` is set, at the
+    /// current position or the selected positions (see `annotate`).
+    fn toggle_marks(&mut self, n: u8, corner: bool) {
+        let mut targets = self.targets();
+        if !self.selection.is_empty() {
+            targets.retain(|&(row, col)| self.board[row][col] == 0);
+        }
+        let marks = if corner { &self.corner_marks } else { &self.annotations };
+        let add = !targets.iter().all(|&(row, col)| marks[row][col][n]);
+        let actions = targets
+            .into_iter()
+            .filter(|&(row, col)| marks[row][col][n] != add)
+            .map(|p| if corner { Action::CornerMark(n, p) } else { Action::Annotate(n, p) })
+            .collect();
+        self.perform_all(actions);
+    }
+
+    /// Undoes the last move leading to the current state on its own, even if it was made together
+    /// with others, returning its index (if there is one).
+    fn undo_one(&mut self) -> Option<usize> {
+        let index = self.history.undo()?;
+        self.apply(index, true);
+        Some(index)
+    }
+
+    /// Applies the changes of the move at the given index to the board and annotations, or
+    /// reverts them if `undo` is set.
+    fn apply(&mut self, index: usize, undo: bool) {
+        let changes = self.history.moves()[index].changes();
+        for change in changes {
+            let (row, col) = change.position;
+            let (n, annotations, corner_marks) = if undo { change.before } else { change.after };
+            if n == 0 {
+                self.board.remove_at(row, col);
+            } else {
+                self.board.put_at(n, row, col);
+            }
+            self.annotations[row][col] = annotations;
+            self.corner_marks[row][col] = corner_marks;
+        }
+        self.is_solved = self.board.is_solved();
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
@@ -0,0 +1,539 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Saving games to files and loading them again.
+//!
+//! A saved game is a text file. The first line gives the version of the format, and is followed
+//! by a few lines of the form `key: value` describing the game, and then by sections which each
+//! start with a name in square brackets:
+//!
+//! ```text
+//! rsudoku save 1
+//! box: 3x3
+//! seed: 42
+//! autoprune: on
+//! position: r2c7
+//! selection: r3c1 r3c2
+//! elapsed: 93.512
+//! hints: 1
+//! undos: 4
+//! mistakes: 2
+//! completed: 90.004 1 3 2
+//! current: 3
+//! redo: 1 3
+//!
+//! [given]
+//! (the given sudoku, in the usual format)
+//!
+//! [moves]
+//! 1 after 0 at 12.250: put 5 at r2c7
+//! 2 after 1 at 20.017: annotate 3 at r4c1
+//! 3 after 1 at 31.932: put 4 at r2c7
+//! 4 after 3 at 40.100 pruning: put 8 at r1c1
+//! 5 after 4 at 52.875: annotate 6 at r3c1
+//! 6 after 5 at 52.875 joined: annotate 6 at r3c2
+//! 7 after 6 at 60.250: corner mark 2 at r4c1
+//! ```
+//!
+//! The `autoprune` setting (see `Game::set_autoprune`) is `on` or `off`, and is off if it is
+//! missing; likewise, nothing is selected if `selection` is missing. The `hints`, `undos` and
+//! `mistakes` count what the player has done so far. If the game has been solved, `completed`
+//! gives the time and these three counts from when it was first solved, followed by `solve` if it
+//! was solved using the `solve` action.
+//!
+//! The `[moves]` section holds the undo tree of the game: every move which has been made, in
+//! order, as its number, the number of the move it was made after (0 for the start of the game),
+//! the time spent playing when it was made (in seconds) and its action. A move which removed the
+//! number it put from the annotations of other positions is marked with `pruning` before the
+//! action, since the setting may have changed since it was made, and a move which was made
+//! together with the one before it (such as when changing several selected positions at once)
+//! is marked with `joined`. The current state is the one right after the move numbered
+//! `current`, and `redo` lists the moves which are made again when redoing from the move before
+//! them. The board, annotations and corner marks aren't saved, since they can be worked out by
+//! making the moves again.
+//!
+//! A move log, which is exported for replaying a game, has a similar format. It starts with the
+//! line `rsudoku moves 1` and has no undo tree, only the moves leading to the current state, one
+//! per line as the time it was made and its action (such as `12.250: put 5 at r2c7`, or
+//! `40.100 pruning: put 8 at r1c1`).
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use errors::*;
+use sudoku::{self, Shape, Sudoku};
+use super::{Action, Completion, Game, Move};
+
+/// The version of the format written by `Game::to_save_string`. Saves in any other version are
+/// rejected, so once games have been saved in this version, changing the format means adding a
+/// new version and still reading this one.
+const SAVE_VERSION: u32 = 1;
+
+/// The start of the first line of a saved game, which is followed by the version.
+const HEADER: &str = "rsudoku save";
+
+/// The first line of a move log written by `Game::to_move_log_string`.
+const MOVE_LOG_HEADER: &str = "rsudoku moves 1";
+
+/// The marks written before the action of a move, which describe how it was made.
+#[derive(Clone, Copy, Debug, Default)]
+struct Marks {
+    /// Whether the move was made together with the one before (see `Move::joined`).
+    joined: bool,
+    /// Whether the move pruned annotations (see `Move::pruned`).
+    pruning: bool,
+}
+
+impl Marks {
+    /// Returns the marks of the given move.
+    fn of(m: &Move) -> Self {
+        Marks {
+            joined: m.joined(),
+            pruning: m.pruned(),
+        }
+    }
+
+    /// Parses the marks of the move on the given line, which must each be given at most once.
+    fn parse(words: &[&str], line: &str) -> Result<Self> {
+        let mut marks = Marks::default();
+        for &word in words {
+            let mark = match word {
+                "joined" => &mut marks.joined,
+                "pruning" => &mut marks.pruning,
+                _ => bail!(ErrorKind::InvalidSave(format!("invalid move `{}`", line))),
+            };
+            if *mark {
+                bail!(ErrorKind::InvalidSave(format!("invalid move `{}`", line)));
+            }
+            *mark = true;
+        }
+        Ok(marks)
+    }
+}
+
+impl Display for Marks {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if self.joined {
+            write!(f, " joined")?;
+        }
+        if self.pruning {
+            write!(f, " pruning")?;
+        }
+        Ok(())
+    }
+}
+
+impl Game {
+    /// Parses a game saved using `to_save_string`.
+    pub fn from_save_string(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        let version = match lines.next() {
+            Some(l) if l.starts_with(HEADER) => l[HEADER.len()..].trim().parse::<u32>().ok(),
+            _ => None,
+        };
+        match version {
+            Some(SAVE_VERSION) => {}
+            Some(v) => bail!(ErrorKind::InvalidSave(format!("unsupported version {}", v))),
+            None => bail!(ErrorKind::InvalidSave("not a saved game".into())),
+        }
+
+        // The key-value lines come first, then the sections
+        let (mut shape, mut seed, mut position) = (None, None, (0, 0));
+        let (mut autoprune, mut selection) = (false, Vec::new());
+        let mut elapsed = Duration::from_secs(0);
+        let (mut current, mut redo) = (0, Vec::new());
+        let (mut hints, mut undos, mut mistakes, mut completion) = (0, 0, 0, None);
+        let mut sections = Vec::<(&str, Vec<&str>)>::new();
+        for line in lines {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                sections.push((&trimmed[1..trimmed.len() - 1], Vec::new()));
+            } else if let Some(&mut (_, ref mut contents)) = sections.last_mut() {
+                contents.push(line);
+            } else if !trimmed.is_empty() {
+                let mut parts = trimmed.splitn(2, ':');
+                let (key, value) = (parts.next().unwrap(), parts.next().unwrap_or("").trim());
+                match key {
+                    "box" => shape = Some(value.parse::<Shape>()?),
+                    "seed" => seed = Some(parse_value(key, value)?),
+                    "autoprune" => autoprune = parse_setting(key, value)?,
+                    "position" => position = sudoku::parse_position(value)?,
+                    "selection" => {
+                        for p in value.split_whitespace() {
+                            selection.push(sudoku::parse_position(p)?);
+                        }
+                    }
+                    "elapsed" => elapsed = parse_time(key, value)?,
+                    "hints" => hints = parse_value(key, value)?,
+                    "undos" => undos = parse_value(key, value)?,
+                    "mistakes" => mistakes = parse_value(key, value)?,
+                    "completed" => completion = Some(parse_completion(value)?),
+                    "current" => current = parse_value::<usize>(key, value)?,
+                    "redo" => {
+                        for n in value.split_whitespace() {
+                            redo.push(parse_value::<usize>(key, n)?);
+                        }
+                    }
+                    _ => bail!(ErrorKind::InvalidSave(format!("unexpected line `{}`", line))),
+                }
+            }
+        }
+
+        let mut sections = sections.into_iter();
+        let given = match (sections.next(), shape) {
+            (Some(("given", contents)), Some(shape)) => {
+                Sudoku::parse_with_shape(&contents.join("\n"), shape)?
+            }
+            (Some(("given", _)), None) => {
+                bail!(ErrorKind::InvalidSave("missing box shape".into()))
+            }
+            _ => bail!(ErrorKind::InvalidSave("expected a `[given]` section".into())),
+        };
+        let size = given.size();
+        let mut game = Game::from_sudoku(given);
+        match sections.next() {
+            Some(("moves", contents)) => {
+                for line in contents.into_iter().filter(|l| !l.trim().is_empty()) {
+                    parse_move(&mut game, line)?;
+                }
+            }
+            Some((name, _)) => {
+                bail!(ErrorKind::InvalidSave(format!("unexpected section `[{}]`", name)))
+            }
+            None => {}
+        }
+        if let Some((name, _)) = sections.next() {
+            bail!(ErrorKind::InvalidSave(format!("unexpected section `[{}]`", name)));
+        }
+
+        let moves = game.moves().len();
+        if current > moves {
+            bail!(ErrorKind::InvalidSave(format!("there is no move {}", current)));
+        }
+        game.jump_to(current.checked_sub(1));
+        for n in redo {
+            if n == 0 || n > moves {
+                bail!(ErrorKind::InvalidSave(format!("there is no move {} to redo", n)));
+            }
+            let parent = game.moves()[n - 1].parent();
+            game.history.set_next(parent, n - 1);
+        }
+
+        if position.0 >= size || position.1 >= size {
+            bail!(ErrorKind::InvalidSave("position is not on the grid".into()));
+        }
+        for (row, col) in selection {
+            if row >= size || col >= size {
+                bail!(ErrorKind::InvalidSave("selection is not on the grid".into()));
+            }
+            game.select(row, col);
+        }
+        // Making the moves again counts them as if they were new, so the counts are set afterwards
+        game.position = position;
+        game.seed = seed;
+        game.autoprune = autoprune;
+        game.elapsed = elapsed;
+        game.hints = hints;
+        game.undos = undos;
+        game.mistakes = mistakes;
+        game.completion = completion;
+        Ok(game)
+    }
+
+    /// Parses a move log written by `to_move_log_string`, making each of its moves in turn. The
+    /// game is left right after the last move.
+    pub fn from_move_log_string(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some(MOVE_LOG_HEADER) {
+            bail!(ErrorKind::InvalidSave("not a move log".into()));
+        }
+
+        let (mut shape, mut seed) = (None, None);
+        let mut sections = Vec::<(&str, Vec<&str>)>::new();
+        for line in lines {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                sections.push((&trimmed[1..trimmed.len() - 1], Vec::new()));
+            } else if let Some(&mut (_, ref mut contents)) = sections.last_mut() {
+                contents.push(line);
+            } else if !trimmed.is_empty() {
+                let mut parts = trimmed.splitn(2, ':');
+                let (key, value) = (parts.next().unwrap(), parts.next().unwrap_or("").trim());
+                match key {
+                    "box" => shape = Some(value.parse::<Shape>()?),
+                    "seed" => seed = Some(parse_value(key, value)?),
+                    _ => bail!(ErrorKind::InvalidSave(format!("unexpected line `{}`", line))),
+                }
+            }
+        }
+
+        let (given, moves) = match (&sections[..], shape) {
+            (&[("given", ref given), ("moves", ref moves)], Some(shape)) => {
+                (Sudoku::parse_with_shape(&given.join("\n"), shape)?, moves)
+            }
+            (&[("given", _), ("moves", _)], None) => {
+                bail!(ErrorKind::InvalidSave("missing box shape".into()))
+            }
+            _ => bail!(ErrorKind::InvalidSave(
+                "expected a `[given]` section and a `[moves]` section".into()
+            )),
+        };
+        let mut game = Game::from_sudoku(given);
+        game.seed = seed;
+        for line in moves.iter().filter(|l| !l.trim().is_empty()) {
+            let mut parts = line.splitn(2, ':');
+            let (time, action) = (parts.next().unwrap(), parts.next());
+            let (time, marks) = match (&time.split_whitespace().collect::<Vec<_>>()[..], action) {
+                (&[time, ref marks @ ..], Some(_)) => (time, Marks::parse(marks, line)?),
+                _ => bail!(ErrorKind::InvalidSave(format!("invalid move `{}`", line))),
+            };
+            game.elapsed = parse_time("move time", time)?;
+            make_move(&mut game, action.unwrap().parse::<Action>()?, marks, line)?;
+        }
+        Ok(game)
+    }
+
+    /// Loads a game to be replayed from the file at the given path, which may contain either a
+    /// saved game (see `from_save_string`) or a move log (see `from_move_log_string`).
+    pub fn load_replay<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .chain_err(|| format!("could not read recorded game `{}`", path.display()))?;
+        let game = if contents.starts_with(MOVE_LOG_HEADER) {
+            Game::from_move_log_string(&contents)
+        } else {
+            Game::from_save_string(&contents)
+        };
+        game.chain_err(|| format!("could not load recorded game `{}`", path.display()))
+    }
+
+    /// Loads a game from the file at the given path (see `from_save_string`).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .chain_err(|| format!("could not read saved game `{}`", path.display()))?;
+        Game::from_save_string(&contents)
+            .chain_err(|| format!("could not load saved game `{}`", path.display()))
+    }
+
+    /// Saves the game to the file at the given path, replacing anything already there (see
+    /// `to_save_string`).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        File::create(path)
+            .and_then(|mut f| f.write_all(self.to_save_string().as_bytes()))
+            .chain_err(|| format!("could not save game to `{}`", path.display()))
+    }
+
+    /// Exports the move log of the game (see `move_log`) to the file at the given path, replacing
+    /// anything already there (see `to_move_log_string`).
+    pub fn export_moves<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        File::create(path)
+            .and_then(|mut f| f.write_all(self.to_move_log_string().as_bytes()))
+            .chain_err(|| format!("could not export moves to `{}`", path.display()))
+    }
+
+    /// Returns the given board and the move log of the game (see `move_log`) in the format
+    /// described in the module documentation.
+    pub fn to_move_log_string(&self) -> String {
+        let mut s = format!("{}\n", MOVE_LOG_HEADER);
+        s += &format!("box: {}\n", self.given.shape());
+        if let Some(seed) = self.seed {
+            s += &format!("seed: {}\n", seed);
+        }
+        s += &format!("\n[given]\n{}\n\n[moves]\n", self.given);
+        for i in self.history.path(self.history.current()) {
+            let m = &self.moves()[i];
+            s += &format!("{}{}: {}\n", fmt_time(m.time()), Marks::of(m), m.action());
+        }
+        s
+    }
+
+    /// Returns the full state of the game (including the undo tree) in the save format described
+    /// in the module documentation, which can be read by `from_save_string`.
+    pub fn to_save_string(&self) -> String {
+        let mut s = format!("{} {}\n", HEADER, SAVE_VERSION);
+        s += &format!("box: {}\n", self.given.shape());
+        if let Some(seed) = self.seed {
+            s += &format!("seed: {}\n", seed);
+        }
+        if self.autoprune {
+            s += "autoprune: on\n";
+        }
+        s += &format!("position: r{}c{}\n", self.position.0 + 1, self.position.1 + 1);
+        if !self.selection.is_empty() {
+            let positions = self
+                .selection
+                .iter()
+                .map(|&(row, col)| format!("r{}c{}", row + 1, col + 1));
+            s += &format!("selection: {}\n", positions.collect::<Vec<_>>().join(" "));
+        }
+        s += &format!("elapsed: {}\n", fmt_time(self.elapsed));
+        s += &format!("hints: {}\n", self.hints);
+        s += &format!("undos: {}\n", self.undos);
+        s += &format!("mistakes: {}\n", self.mistakes);
+        if let Some(c) = self.completion {
+            s += &format!(
+                "completed: {} {} {} {}{}\n",
+                fmt_time(c.time()),
+                c.hints(),
+                c.undos(),
+                c.mistakes(),
+                if c.solver() { " solve" } else { "" }
+            );
+        }
+        s += &format!("current: {}\n", self.current_move().map_or(0, |i| i + 1));
+        let moves = self.moves();
+        // The moves which are redone from their parents, which are different from the others in
+        // that the parent remembers them
+        let redo = (0..moves.len())
+            .filter(|&i| self.history.is_next(i))
+            .map(|i| (i + 1).to_string());
+        s += &format!("redo: {}\n", redo.collect::<Vec<_>>().join(" "));
+        s += &format!("\n[given]\n{}\n", self.given);
+        s += "\n[moves]\n";
+        for (i, m) in moves.iter().enumerate() {
+            let parent = m.parent().map_or(0, |p| p + 1);
+            s += &format!(
+                "{} after {} at {}{}: {}\n",
+                i + 1,
+                parent,
+                fmt_time(m.time()),
+                Marks::of(m),
+                m.action()
+            );
+        }
+        s
+    }
+}
+
+/// Parses a line of the `[moves]` section of a saved game, and makes the move in `game`.
+fn parse_move(game: &mut Game, line: &str) -> Result<()> {
+    let mut parts = line.splitn(2, ':');
+    let (numbers, action) = (parts.next().unwrap(), parts.next());
+    let numbers = numbers.split_whitespace().collect::<Vec<_>>();
+    let (number, parent, time, marks) = match (&numbers[..], action) {
+        (&[number, "after", parent, "at", time, ref marks @ ..], Some(_)) => {
+            (number, parent, time, Marks::parse(marks, line)?)
+        }
+        _ => bail!(ErrorKind::InvalidSave(format!("invalid move `{}`", line))),
+    };
+    let number = parse_value::<usize>("move number", number)?;
+    let parent = parse_value::<usize>("move number", parent)?;
+    let time = parse_time("move time", time)?;
+    let action = action.unwrap().parse::<Action>()?;
+    let moves = game.moves().len();
+    if number != moves + 1 || parent > moves {
+        bail!(ErrorKind::InvalidSave(format!("move `{}` is out of order", line)));
+    }
+    game.jump_to(parent.checked_sub(1));
+    game.elapsed = time;
+    make_move(game, action, marks, line)
+}
+
+/// Makes the given action, read from the given line of a saved game or move log, in `game`, in
+/// the way described by its marks. It is checked beforehand, so that an invalid action is an
+/// error rather than a panic.
+fn make_move(game: &mut Game, action: Action, marks: Marks, line: &str) -> Result<()> {
+    let size = game.board().size();
+    match action {
+        Action::Put(n, _) |
+        Action::Annotate(n, _) |
+        Action::CornerMark(n, _) |
+        Action::Hint(n, _) if n == 0 || n as usize > size => {
+            bail!(ErrorKind::InvalidSave(format!("invalid number in move `{}`", line)))
+        }
+        _ => {}
+    }
+    match action.position() {
+        Some((row, col)) if row >= size || col >= size => {
+            bail!(ErrorKind::InvalidSave(format!("move `{}` is not on the grid", line)))
+        }
+        _ => {}
+    }
+    if marks.joined && game.current_move().is_none() {
+        bail!(ErrorKind::InvalidSave(format!("move `{}` has nothing to join", line)));
+    }
+    let autoprune = game.autoprune;
+    game.autoprune = marks.pruning;
+    let made = game.make(action, marks.joined);
+    game.autoprune = autoprune;
+    if !made {
+        bail!(ErrorKind::InvalidSave(format!("move `{}` can't be made", line)));
+    }
+    Ok(())
+}
+
+/// Parses the value of the `completed` line of a saved game.
+fn parse_completion(value: &str) -> Result<Completion> {
+    let words = value.split_whitespace().collect::<Vec<_>>();
+    let solver = match words.get(4) {
+        None => false,
+        Some(&"solve") if words.len() == 5 => true,
+        _ => bail!(ErrorKind::InvalidSave(format!("invalid completion `{}`", value))),
+    };
+    match words[..] {
+        [time, hints, undos, mistakes, ..] => Ok(Completion::new(
+            parse_time("completion time", time)?,
+            parse_value("hint count", hints)?,
+            parse_value("undo count", undos)?,
+            parse_value("mistake count", mistakes)?,
+            solver,
+        )),
+        _ => bail!(ErrorKind::InvalidSave(format!("invalid completion `{}`", value))),
+    }
+}
+
+/// Formats a time as a number of seconds with three decimal places, as it is written in a saved
+/// game.
+fn fmt_time(time: Duration) -> String {
+    format!("{}.{:03}", time.as_secs(), time.subsec_millis())
+}
+
+/// Parses a time written by `fmt_time` (or any other non-negative number of seconds), as the value
+/// of the given key.
+fn parse_time(key: &str, value: &str) -> Result<Duration> {
+    let secs = parse_value::<f64>(key, value)?;
+    if !secs.is_finite() || secs < 0.0 {
+        bail!(ErrorKind::InvalidSave(format!("invalid {} `{}`", key, value)));
+    }
+    Ok(Duration::from_millis((secs * 1000.0).round() as u64))
+}
+
+/// Parses the value of a setting which is either `on` or `off`, on the line with the given key at
+/// the start of a saved game.
+fn parse_setting(key: &str, value: &str) -> Result<bool> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => bail!(ErrorKind::InvalidSave(format!("invalid {} `{}`", key, value))),
+    }
+}
+
+/// Parses the value of the line with the given key at the start of a saved game.
+fn parse_value<T: ::std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse::<T>()
+        .map_err(|_| ErrorKind::InvalidSave(format!("invalid {} `{}`", key, value)).into())
+}
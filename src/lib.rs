@@ -21,12 +21,43 @@ extern crate chan_signal;
 extern crate error_chain;
 #[macro_use]
 extern crate itertools;
+extern crate libc;
 extern crate rand;
 extern crate termion;
 
 pub mod errors {
     error_chain! {
         errors {
+            /// A sudoku of the requested difficulty could not be generated.
+            DifficultyNotMet(d: ::sudoku::logic::Difficulty) {
+                description("could not generate sudoku of requested difficulty")
+                display("could not generate a sudoku of difficulty `{}`", d)
+            }
+            /// A killer sudoku cage is invalid.
+            InvalidCage(s: String) {
+                description("invalid cage")
+                display("invalid cage: {}", s)
+            }
+            /// The regions of a jigsaw sudoku are invalid.
+            InvalidRegions(s: String) {
+                description("invalid regions")
+                display("invalid regions: {}", s)
+            }
+            /// A saved game could not be read.
+            InvalidSave(s: String) {
+                description("invalid saved game")
+                display("invalid saved game: {}", s)
+            }
+            /// The requested box dimensions cannot be used for a sudoku.
+            InvalidShape(rows: usize, cols: usize) {
+                description("invalid box shape")
+                display("invalid box shape `{}x{}`", rows, cols)
+            }
+            /// A statistics file could not be read.
+            InvalidStats(s: String) {
+                description("invalid statistics")
+                display("invalid statistics: {}", s)
+            }
             /// The sudoku is invalid (has no solution).
             InvalidSudoku {
                 description("sudoku is invalid")
@@ -41,6 +72,7 @@ pub mod errors {
 }
 
 pub mod game;
+pub mod stats;
 pub mod sudoku;
 pub mod tui;
 pub mod util;
@@ -50,10 +82,11 @@ pub use sudoku::Sudoku;
 #[cfg(test)]
 mod tests {
     use Sudoku;
-    use sudoku::Annotations;
+    use sudoku::{self, Annotations};
 
     /// Tests `Annotations`.
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn annotations() {
         let mut a = Annotations::new();
 
@@ -88,6 +121,58 @@ mod tests {
 
         a.clear();
         assert_eq!(a, Annotations::new());
+
+        a = Annotations::from_bits(0b110_0000_0110, 9);
+        assert_eq!(a.list(), vec![1, 2, 9]);
+        assert_eq!(a.bits(), 0b10_0000_0110);
+        assert_eq!(a.lowest(), Some(1));
+
+        a.set(2, false);
+        a.set(9, false);
+        assert_eq!(a.list(), vec![1]);
+
+        // Setting annotations through an index or `set` keeps the bitmask up to date
+        a[4] = true;
+        assert_eq!(a.bits(), 0b1_0010);
+        assert_eq!(a.lowest(), Some(1));
+        a.toggle(1);
+        assert_eq!(a.bits(), 0b1_0000);
+        assert_eq!(a, Annotations::from_bits(0b1_0000, 9));
+
+        // Iterating over the annotations covers the numbers on the board they belong to
+        assert_eq!(a.into_iter().count(), 9);
+        a = Annotations::from_slice(&[false; 16]);
+        a.set(16, true);
+        a.set(16, true);
+        assert_eq!(a.list(), vec![16]);
+        assert_eq!(a.into_iter().count(), 16);
+        assert_eq!(a.into_iter().position(|&b| b), Some(15));
+        assert_eq!(Annotations::with_size(sudoku::MAX_SIZE).into_iter().count(), sudoku::MAX_SIZE);
+    }
+
+    /// Tests putting and removing entries, including ones which break the rules.
+    #[test]
+    fn put_remove() {
+        let mut s = Sudoku::from_grid([[0; 9]; 9]).unwrap();
+        s.put_at(5, 0, 0);
+        s.put_at(5, 0, 8);
+        assert!(!s.has_solution());
+        assert!(!s.is_solved());
+        assert!(s.is_conflict_at(0, 0) && s.is_conflict_at(0, 8));
+        assert!(!s.is_conflict_at(0, 4));
+
+        // Replacing one of the duplicates should make the board valid again
+        s.put_at(3, 0, 8);
+        assert!(s.has_solution());
+        assert!(!s.is_conflict_at(0, 0));
+        s.put_at(5, 4, 0);
+        assert!(!s.has_solution());
+        assert_eq!(s.remove_at(4, 0), 5);
+        assert_eq!(s.remove_at(4, 0), 0);
+
+        let solution = s.solutions().next().unwrap();
+        assert!(solution.is_solved());
+        assert_eq!((solution[0][0], solution[0][8]), (5, 3));
     }
 
     /// Tests sudoku parsing.
@@ -174,6 +259,423 @@ trailing output is bad";
         assert!(input5.parse::<Sudoku>().is_err());
     }
 
+    /// Tests logical solving of an easy sudoku, which should need only singles.
+    #[test]
+    fn deductions() {
+        use sudoku::logic::Technique;
+
+        let s = "003020600
+900305001
+001806400
+008102900
+700000008
+006708200
+002609500
+800203009
+005010300".parse::<Sudoku>()
+            .unwrap();
+        let mut deductions = s.deductions();
+        let steps = deductions.by_ref().collect::<Vec<_>>();
+
+        assert!(deductions.board().is_solved());
+        assert_eq!(Some(deductions.board()), s.solutions().next().as_ref());
+        assert!(steps.iter().all(|step| step.technique <= Technique::HiddenSingle));
+        // One placement for each empty cell
+        assert_eq!(steps.len(), 49);
+    }
+
+    /// Tests difficulty ratings.
+    #[test]
+    fn rate() {
+        use sudoku::logic::{Difficulty, Technique};
+
+        let easy = "003020600900305001001806400008102900700000008006708200002609500800203009005010300"
+            .parse::<Sudoku>()
+            .unwrap()
+            .rate();
+        assert_eq!(easy.difficulty, Difficulty::Easy);
+        assert!(easy.solved);
+
+        let expert = "900040000000600031020000090000700020002935600070002000060000073510009000000080009"
+            .parse::<Sudoku>()
+            .unwrap()
+            .rate();
+        assert_eq!(expert.difficulty, Difficulty::Expert);
+        assert_eq!(expert.hardest, Some(Technique::XYWing));
+        assert!(expert.score > easy.score);
+
+        let diabolical = "800000000003600000070090000050007000000045700000100030001000068008500010090000400"
+            .parse::<Sudoku>()
+            .unwrap()
+            .rate();
+        assert_eq!(diabolical.difficulty, Difficulty::Diabolical);
+        assert!(!diabolical.solved);
+    }
+
+    /// Tests that both engines find the same solutions for a puzzle with many of them.
+    #[test]
+    fn engines() {
+        use sudoku::Engine;
+
+        // The Project Euler example with its first two rows removed
+        let s = "000000000
+000000000
+001806400
+008102900
+700000008
+006708200
+002609500
+800203009
+005010300".parse::<Sudoku>()
+            .unwrap();
+        let mut backtrack = s.solutions_with(Engine::Backtrack).collect::<Vec<_>>();
+        let mut dlx = s.solutions_with(Engine::Dlx).collect::<Vec<_>>();
+        assert!(backtrack.len() > 1);
+        assert!(dlx.iter().all(|sol| sol.is_solved()));
+
+        // The engines may find solutions in a different order
+        backtrack.sort_by_key(|sol| sol.to_string());
+        dlx.sort_by_key(|sol| sol.to_string());
+        assert_eq!(backtrack, dlx);
+
+        assert_eq!("dlx".parse::<Engine>().unwrap(), Engine::Dlx);
+        assert!("magic".parse::<Engine>().is_err());
+    }
+
+    /// Tests counting solutions.
+    #[test]
+    fn count_solutions() {
+        let empty = Sudoku::from_grid([[0; 9]; 9]).unwrap();
+        assert_eq!(empty.count_solutions(1000), 1000);
+        assert_eq!(empty.count_solutions(0), 0);
+
+        let unique = "003020600900305001001806400008102900700000008006708200002609500800203009005010300"
+            .parse::<Sudoku>()
+            .unwrap();
+        assert_eq!(unique.count_solutions(10), 1);
+        assert_eq!(unique.solutions().next().unwrap().count_solutions(10), 1);
+
+        let mut none = unique.clone();
+        none.put_at(9, 0, 0);
+        assert_eq!(none.count_solutions(10), 0);
+
+        let mut many = unique.clone();
+        many.remove_at(0, 2);
+        many.remove_at(0, 4);
+        many.remove_at(0, 6);
+        many.remove_at(1, 0);
+        assert_eq!(many.count_solutions(1000), many.solutions().count());
+    }
+
+    /// Tests box shapes for different sizes of sudoku.
+    #[test]
+    fn shapes() {
+        use sudoku::Shape;
+
+        assert_eq!(Shape::default(), Shape::new(3, 3).unwrap());
+        assert_eq!(Shape::for_size(6).unwrap(), Shape::new(2, 3).unwrap());
+        assert_eq!(Shape::for_size(12).unwrap(), Shape::new(3, 4).unwrap());
+        assert_eq!(Shape::for_size(25).unwrap(), Shape::new(5, 5).unwrap());
+        assert!(Shape::for_size(7).is_err());
+        assert!(Shape::new(0, 3).is_err());
+        assert!(Shape::new(4, 7).is_err());
+        assert_eq!("3x2".parse::<Shape>().unwrap(), Shape::new(3, 2).unwrap());
+        assert!("3by2".parse::<Shape>().is_err());
+
+        // A 6x6 grid has two boxes across and three down
+        let shape = Shape::new(2, 3).unwrap();
+        assert_eq!(shape.box_index(1, 4), 1);
+        assert_eq!(shape.box_index(4, 2), 4);
+        assert_eq!(shape.box_origin(5), (4, 3));
+    }
+
+    /// Tests parsing and printing sudokus of sizes other than 9x9.
+    #[test]
+    fn parse_sizes() {
+        use sudoku::Shape;
+
+        let small = "1.|..
+                     ..|.3
+                     --+--
+                     .4|..
+                     ..|.2".parse::<Sudoku>()
+            .unwrap();
+        assert_eq!(small.size(), 4);
+        assert_eq!(&small[1], &[0, 0, 0, 3]);
+        assert_eq!(small.to_string(), "1000\n0003\n0400\n0002");
+        assert_eq!(small.solutions().count(), small.count_solutions(100));
+
+        let large = "G...............
+                     ................
+                     ..a.............
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ................
+                     ...............1".parse::<Sudoku>()
+            .unwrap();
+        assert_eq!(large.shape(), Shape::new(4, 4).unwrap());
+        assert_eq!((large[0][0], large[2][2], large[15][15]), (16, 10, 1));
+        assert_eq!(format!("{:#}", large).parse::<Sudoku>().unwrap(), large);
+        assert!("H".repeat(256).parse::<Sudoku>().is_err());
+
+        // Tall boxes must be asked for explicitly, since they aren't the usual shape
+        let input = "100000 001000 000000 000000 000000 000000";
+        let tall = Sudoku::parse_with_shape(input, Shape::new(3, 2).unwrap()).unwrap();
+        assert_eq!(tall.shape().box_rows(), 3);
+        assert!(input.parse::<Sudoku>().is_err());
+    }
+
+    /// Tests generating and solving sudokus of sizes other than 9x9.
+    #[test]
+    fn generate_sizes() {
+        use sudoku::{Engine, Shape};
+        use util::seeded_rng;
+
+        for &(rows, cols) in &[(2, 2), (2, 3), (3, 2), (3, 4)] {
+            let template = Sudoku::new(Shape::new(rows, cols).unwrap());
+            let s = template.generate_like(None, &mut seeded_rng(1)).unwrap();
+            assert_eq!(s.shape(), template.shape());
+            assert!(s.has_unique_solution());
+
+            let backtrack = s.solutions_with(Engine::Backtrack).next().unwrap();
+            assert!(backtrack.is_solved());
+            assert_eq!(s.solutions_with(Engine::Dlx).next(), Some(backtrack));
+        }
+    }
+
+    /// Tests parsing and solving killer sudokus.
+    #[test]
+    fn killer() {
+        use sudoku::Engine;
+        use sudoku::logic::Technique;
+
+        let input = "1000
+                     0000
+                     0000
+                     0000
+                     3: r1c1 r1c2
+                     7: r1c3 r1c4
+                     5: r2c1 r3c1
+                     5: r2c2 r2c3
+                     5: r2c4 r3c4
+                     5: r3c2 r3c3
+                     7: r4c1 r4c2
+                     3: r4c3 r4c4";
+        let s = input.parse::<Sudoku>().unwrap();
+        assert_eq!(s.cages().len(), 8);
+        assert_eq!(s.cages()[2].sum(), 5);
+        assert_eq!(s.cages()[2].cells(), &[(1, 0), (2, 0)]);
+        assert_eq!(s.cage_index(2, 0), Some(2));
+        assert_eq!(s.to_string().parse::<Sudoku>().unwrap(), s);
+        assert_eq!(format!("{:#}", s).parse::<Sudoku>().unwrap(), s);
+
+        // Without the cages, there would be many solutions
+        let solution = "1234\n3412\n2143\n4321".parse::<Sudoku>().unwrap();
+        assert_eq!(s.count_solutions(10), 1);
+        for engine in &[Engine::Backtrack, Engine::Dlx] {
+            let found = s.solutions_with(*engine).next().unwrap();
+            assert!(found.is_solved());
+            let grid = found.to_string().lines().take(4).collect::<Vec<_>>().join("\n");
+            assert_eq!(grid, solution.to_string());
+        }
+        let rating = s.rate();
+        assert!(rating.solved);
+        assert_eq!(rating.hardest, Some(Technique::CageCombination));
+
+        // Cages whose sums don't match the entries
+        let wrong = "1234\n3412\n2143\n4321\n4: r1c1 r1c2".parse::<Sudoku>().unwrap();
+        assert!(!wrong.is_solved());
+        assert!(!wrong.has_solution());
+
+        for invalid in &[
+            "2: r1c1 r1c2",
+            "3: r1c1 r1c3",
+            "3: r1c1 r1c2\n4: r1c2 r1c3",
+            "3: r1c1 r1c5",
+            "3: r0c1 r1c2",
+            "three: r1c1 r1c2",
+        ] {
+            let input = format!("0000\n0000\n0000\n0000\n{}", invalid);
+            assert!(input.parse::<Sudoku>().is_err(), "parsed {:?}", invalid);
+        }
+        // Givens can't repeat a number within a cage
+        assert!("1000\n0100\n0000\n0000\n5: r1c1 r2c1 r2c2".parse::<Sudoku>().is_err());
+    }
+
+    /// Tests killer sudoku generation.
+    #[test]
+    fn generate_killer() {
+        use util::seeded_rng;
+
+        let s = Sudoku::new(Default::default())
+            .generate_killer_like(None, &mut seeded_rng(1))
+            .unwrap();
+        assert!(s.has_unique_solution());
+        let solution = s.solutions().next().unwrap();
+        for (i, j) in iproduct!(0..9, 0..9) {
+            assert!(s.cage_index(i, j).is_some());
+        }
+        for cage in s.cages() {
+            let sum = cage.cells().iter().map(|&(i, j)| solution[i][j] as u32).sum::<u32>();
+            assert_eq!(sum, cage.sum());
+        }
+
+        // New puzzles like a killer sudoku are killer sudokus too
+        let next = s.generate_like(None, &mut seeded_rng(2)).unwrap();
+        assert!(!next.cages().is_empty());
+        assert_ne!(next.cages(), s.cages());
+    }
+
+    /// Tests parsing and solving jigsaw sudokus.
+    #[test]
+    fn jigsaw() {
+        use sudoku::Engine;
+
+        let input = "0000
+                     0000
+                     0000
+                     0000
+                     regions: 1222 1132 4133 4443";
+        let s = input.parse::<Sudoku>().unwrap();
+        assert!(s.is_jigsaw());
+        assert_eq!(s.region_index(1, 2), 2);
+        assert_eq!(s.region_cells(1), &[(0, 1), (0, 2), (0, 3), (1, 3)]);
+        assert_eq!(s.to_string().parse::<Sudoku>().unwrap(), s);
+        assert_eq!(format!("{:#}", s).parse::<Sudoku>().unwrap(), s);
+        assert!(!"0000\n0000\n0000\n0000".parse::<Sudoku>().unwrap().is_jigsaw());
+
+        for engine in &[Engine::Backtrack, Engine::Dlx] {
+            let found = s.solutions_with(*engine).next().unwrap();
+            assert!(found.is_solved());
+            for r in 0..4 {
+                let mut numbers = s.region_cells(r)
+                    .iter()
+                    .map(|&(i, j)| found[i][j])
+                    .collect::<Vec<_>>();
+                numbers.sort();
+                assert_eq!(numbers, [1, 2, 3, 4]);
+            }
+        }
+
+        for invalid in &[
+            // Too few cells
+            "1222 1132 4133 444",
+            // A region with too many cells
+            "1222 1132 4133 4441",
+            // A region which isn't connected
+            "1121 1222 3344 3344",
+            // A region number which is too large
+            "1222 1132 4133 4445",
+            "122a 1132 4133 4443",
+        ] {
+            let input = format!("0000\n0000\n0000\n0000\nregions: {}", invalid);
+            assert!(input.parse::<Sudoku>().is_err(), "parsed {:?}", invalid);
+        }
+        // Givens can't repeat a number within a region
+        let repeated = "1000\n0000\n0100\n0000\nregions: 1222 1132 4133 4443";
+        assert!(repeated.parse::<Sudoku>().is_err());
+    }
+
+    /// Tests jigsaw sudoku generation.
+    #[test]
+    fn generate_jigsaw() {
+        use sudoku::Shape;
+        use util::seeded_rng;
+
+        let template = Sudoku::new(Shape::new(2, 3).unwrap());
+        let s = template.generate_jigsaw_like(None, &mut seeded_rng(1)).unwrap();
+        assert!(s.is_jigsaw());
+        assert!(s.has_unique_solution());
+        assert!(s.solutions().next().unwrap().is_solved());
+        let shape = s.shape();
+        assert!(iproduct!(0..6, 0..6).any(|(i, j)| s.region_index(i, j) != shape.box_index(i, j)));
+
+        // New puzzles like a jigsaw sudoku get new regions
+        let next = s.generate_like(None, &mut seeded_rng(2)).unwrap();
+        assert!(next.is_jigsaw());
+        assert_ne!(next.to_string().lines().last(), s.to_string().lines().last());
+        let killer = template.generate_killer_jigsaw_like(None, &mut seeded_rng(3)).unwrap();
+        assert!(killer.is_jigsaw() && !killer.cages().is_empty());
+        assert!(killer.has_unique_solution());
+    }
+
+    /// Tests the rules added by the built-in variants.
+    #[test]
+    fn variants() {
+        use sudoku::Engine;
+        use sudoku::constraint;
+
+        let empty = "0".repeat(36);
+        for &name in constraint::VARIANTS {
+            let input = format!("{}\nvariant: {}", empty, name);
+            let s = input.parse::<Sudoku>().unwrap();
+            assert_eq!(s.constraints().len(), 1);
+            assert_eq!(s.constraints()[0].name(), name);
+            assert_eq!(s.to_string().parse::<Sudoku>().unwrap(), s);
+            assert_eq!(format!("{:#}", s).parse::<Sudoku>().unwrap(), s);
+
+            let backtrack = s.solutions_with(Engine::Backtrack).next().unwrap();
+            let dlx = s.solutions_with(Engine::Dlx).next().unwrap();
+            assert!(backtrack.is_solved() && dlx.is_solved());
+        }
+        assert!(format!("{}\nvariant: diagonal", empty).parse::<Sudoku>().is_err());
+        assert!(format!("{}\nrules: x", empty).parse::<Sudoku>().is_err());
+
+        // Each pair of entries is fine in an ordinary sudoku, but breaks the rules of the variant
+        let broken = [
+            ("x", [(0, 0, 1), (8, 8, 1)]),
+            ("hyper", [(1, 1, 1), (3, 3, 1)]),
+            ("anti-knight", [(0, 2, 1), (1, 4, 1)]),
+            ("anti-king", [(2, 2, 1), (3, 3, 1)]),
+            ("non-consecutive", [(0, 0, 1), (0, 1, 2)]),
+        ];
+        for &(name, entries) in &broken {
+            let mut s = Sudoku::new(Default::default());
+            for &(i, j, n) in &entries {
+                s.put_at(n, i, j);
+            }
+            assert!(s.has_solution());
+            let input = format!("{}\nvariant: {}", s, name);
+            assert!(input.parse::<Sudoku>().is_err(), "parsed {:?}", input);
+            assert!(s.add_constraint(constraint::variant(name).unwrap()).is_err());
+            assert!(s.constraints().is_empty());
+
+            let (a, b) = ((entries[0].0, entries[0].1), (entries[1].0, entries[1].1));
+            let n = s.remove_at(b.0, b.1);
+            s.add_constraint(constraint::variant(name).unwrap()).unwrap();
+            // The non-consecutive cells are in the same row, so they were already peers
+            assert!(s.sees(a, b));
+            s.put_at(n, b.0, b.1);
+            assert!(!s.has_solution());
+        }
+    }
+
+    /// Tests generation of sudokus with the rules of a variant.
+    #[test]
+    fn generate_variant() {
+        use sudoku::{constraint, Shape};
+        use util::seeded_rng;
+
+        // A small grid keeps this quick without optimizations
+        for &name in constraint::VARIANTS {
+            let mut template = Sudoku::new(Shape::new(2, 3).unwrap());
+            template.add_constraint(constraint::variant(name).unwrap()).unwrap();
+            let s = template.generate_like(None, &mut seeded_rng(1)).unwrap();
+            assert_eq!(s.constraints()[0].name(), name);
+            assert!(s.has_unique_solution());
+            assert!(s.solutions().next().unwrap().is_solved());
+        }
+    }
+
     /// Tests sudoku generation.
     #[test]
     fn generate() {
@@ -183,4 +685,70 @@ trailing output is bad";
             assert!(s.has_unique_solution());
         }
     }
+
+    /// Tests generation of sudokus with a particular difficulty.
+    #[test]
+    fn generate_with_difficulty() {
+        use sudoku::logic::Difficulty;
+
+        for &name in &["easy", "Medium", "HARD"] {
+            let d = name.parse::<Difficulty>().unwrap();
+            let s = Sudoku::generate_with_difficulty(d).unwrap();
+            assert!(s.has_unique_solution());
+            assert_eq!(s.rate().difficulty, d);
+        }
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+
+    /// Tests the layout of the TUI grid with cells large enough to hold their candidates.
+    #[test]
+    fn large_grid() {
+        use sudoku::Shape;
+        use tui::Grid;
+
+        // The cells of a 9x9 board are 3x3 grids of candidates, with the number in the middle
+        let grid = Grid::large_for_board(&Sudoku::new(Shape::new(3, 3).unwrap()));
+        assert_eq!((grid.width(), grid.height()), (67, 31));
+        assert_eq!(grid.cell_position((0, 0)), (1, 1));
+        assert_eq!(grid.cell_position((4, 4)), (30, 14));
+        assert_eq!(grid.candidate_position(1), (1, 0));
+        assert_eq!(grid.candidate_position(6), (5, 1));
+        assert_eq!(grid.candidate_position(9), (5, 2));
+
+        // With 3x2 boxes, there are only two lines of candidates, so a blank one is added between
+        // them for the number
+        let grid = Grid::large_for_board(&Sudoku::new(Shape::new(3, 2).unwrap()));
+        assert_eq!((grid.width(), grid.height()), (46, 21));
+        assert_eq!(grid.cell_position((5, 5)), (38, 17));
+        assert_eq!(grid.candidate_position(3), (5, 0));
+        assert_eq!(grid.candidate_position(4), (1, 2));
+
+        // Outlined grids have a border between every pair of cells
+        let mut s = Sudoku::new(Shape::new(2, 2).unwrap());
+        s.add_cage(3, vec![(0, 0), (0, 1)]).unwrap();
+        let grid = Grid::large_for_board(&s);
+        assert_eq!((grid.width(), grid.height()), (25, 17));
+        assert_eq!(grid.cell_position((1, 2)), (13, 5));
+    }
+
+    /// Tests finding the data directory from the environment.
+    #[test]
+    fn data_dir() {
+        use std::path::PathBuf;
+        use util::data_dir_in;
+
+        let dir = |xdg: Option<&str>, home: Option<&str>| {
+            data_dir_in(xdg.map(Into::into), home.map(Into::into))
+        };
+        let xdg = PathBuf::from("/data/rsudoku");
+        let home = PathBuf::from("/home/me/.local/share/rsudoku");
+        assert_eq!(dir(Some("/data"), Some("/home/me")), Some(xdg.clone()));
+        assert_eq!(dir(Some("/data"), None), Some(xdg));
+        assert_eq!(dir(None, Some("/home/me")), Some(home.clone()));
+        // Relative paths (including an empty one) are ignored
+        assert_eq!(dir(Some("data"), Some("/home/me")), Some(home.clone()));
+        assert_eq!(dir(Some(""), Some("/home/me")), Some(home));
+        assert_eq!(dir(None, Some("")), None);
+        assert_eq!(dir(Some("data"), None), None);
+    }
 }
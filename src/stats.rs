@@ -0,0 +1,411 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Statistics about finished games, which are kept in a local file.
+//!
+//! The file starts with the line `rsudoku stats 1`, followed by one line for each finished game,
+//! oldest first:
+//!
+//! ```text
+//! rsudoku stats 1
+//! 1760700000 3x3 medium 8c3e1f0a4b2d9e67 312.250 1 4 2
+//! 1760786400 3x3 hard 2f9b6d1c0e8a7354 95.004 0 0 0 solve
+//! ```
+//!
+//! Each line gives the date the game was finished (in seconds since the Unix epoch), the box
+//! shape, the difficulty of the puzzle (see `Sudoku::rate`), its puzzle id (see
+//! `Game::puzzle_id`), the time spent playing in seconds, and the numbers of hints, undos and
+//! mistakes. Games which were finished using the `solve` action end with `solve`; these are kept,
+//! but they don't count towards the best times or streaks.
+
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File};
+use std::io::{ErrorKind as IoErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use errors::*;
+use game::{Completion, Game};
+use sudoku::Shape;
+use sudoku::logic::Difficulty;
+use util;
+
+/// The first line of a statistics file.
+const HEADER: &str = "rsudoku stats 1";
+
+/// The name of the file in the data directory (see `util::data_dir`) where statistics are kept.
+const STATS_FILE: &str = "stats.txt";
+
+/// The number of seconds in a day.
+const DAY: u64 = 24 * 60 * 60;
+
+/// A record of a finished game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The time the game was finished, in seconds since the Unix epoch.
+    date: u64,
+    shape: Shape,
+    difficulty: Difficulty,
+    puzzle: String,
+    completion: Completion,
+}
+
+/// Statistics for the games of a single size and difficulty which were solved by the player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Summary {
+    /// The size of the grid (such as 9 for a 9x9 grid).
+    pub size: usize,
+    /// The difficulty of the puzzles.
+    pub difficulty: Difficulty,
+    /// The number of games solved.
+    pub solved: usize,
+    /// The shortest time taken to solve a game.
+    pub best: Duration,
+    /// The average time taken to solve a game.
+    pub average: Duration,
+}
+
+/// The history of finished games.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    records: Vec<Record>,
+}
+
+impl Record {
+    /// Returns a record of the given game, finished at the given date (in seconds since the Unix
+    /// epoch), or `None` if the game hasn't been solved. The difficulty of the puzzle is worked
+    /// out by rating its given board.
+    pub fn new(game: &Game, date: u64) -> Option<Self> {
+        game.completion().map(|completion| Record {
+            date,
+            shape: game.given().shape(),
+            difficulty: game.given().rate().difficulty,
+            puzzle: game.puzzle_id(),
+            completion,
+        })
+    }
+
+    /// Returns how the game went until it was solved.
+    pub fn completion(&self) -> Completion {
+        self.completion
+    }
+
+    /// Returns the date the game was finished, in seconds since the Unix epoch.
+    pub fn date(&self) -> u64 {
+        self.date
+    }
+
+    /// Returns the difficulty of the puzzle.
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Returns the id of the puzzle (see `Game::puzzle_id`).
+    pub fn puzzle(&self) -> &str {
+        &self.puzzle
+    }
+
+    /// Returns the box shape of the puzzle.
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+}
+
+impl Stats {
+    /// Returns an empty history.
+    pub fn new() -> Self {
+        Stats::default()
+    }
+
+    /// Parses a history in the format described in the module documentation.
+    pub fn from_stats_string(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some(HEADER) {
+            bail!(ErrorKind::InvalidStats("not a statistics file".into()));
+        }
+        let mut stats = Stats::new();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            stats.add(parse_record(line)?);
+        }
+        Ok(stats)
+    }
+
+    /// Loads the history from the file at the given path. A file which doesn't exist is treated
+    /// as an empty history, since no games have been finished yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        match File::open(path) {
+            Ok(mut f) => f.read_to_string(&mut contents),
+            Err(ref e) if e.kind() == IoErrorKind::NotFound => return Ok(Stats::new()),
+            Err(e) => Err(e),
+        }.chain_err(|| format!("could not read statistics `{}`", path.display()))?;
+        Stats::from_stats_string(&contents)
+            .chain_err(|| format!("could not load statistics `{}`", path.display()))
+    }
+
+    /// Adds the record of a finished game.
+    pub fn add(&mut self, record: Record) {
+        self.records.push(record);
+    }
+
+    /// Returns the statistics for each size and difficulty of the games solved by the player,
+    /// ordered by size and then difficulty.
+    pub fn by_difficulty(&self) -> Vec<Summary> {
+        let mut groups = self.solved().map(|r| (r.shape.size(), r.difficulty)).collect::<Vec<_>>();
+        groups.sort();
+        groups.dedup();
+        groups
+            .into_iter()
+            .map(|(size, difficulty)| {
+                let times = self.solved()
+                    .filter(|r| r.shape.size() == size && r.difficulty == difficulty)
+                    .map(|r| r.completion.time())
+                    .collect::<Vec<_>>();
+                Summary {
+                    size,
+                    difficulty,
+                    solved: times.len(),
+                    best: *times.iter().min().unwrap(),
+                    average: times.iter().sum::<Duration>() / times.len() as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns up to `n` of the games of the given size and difficulty solved by the player,
+    /// fastest first. Games solved in the same time are ordered by the number of hints used, and
+    /// then by date. Games of other sizes and difficulties aren't ranked along with them, since a
+    /// small or easy puzzle would always beat a larger or harder one.
+    pub fn leaderboard(&self, size: usize, difficulty: Difficulty, n: usize) -> Vec<&Record> {
+        let mut records = self.solved()
+            .filter(|r| r.shape.size() == size && r.difficulty == difficulty)
+            .collect::<Vec<_>>();
+        records.sort_by_key(|r| (r.completion.time(), r.completion.hints(), r.date));
+        records.truncate(n);
+        records
+    }
+
+    /// Returns the records of all finished games, oldest first.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Returns a report of the statistics, with the `top` fastest games of each size and difficulty
+    /// in the leaderboards, as of the given date (see `streaks`).
+    pub fn report(&self, top: usize, today: u64) -> String {
+        let solved = self.solved().count();
+        if self.records.is_empty() {
+            return "No games have been finished yet.\n".into();
+        }
+        let mut s = format!("Games solved: {}", solved);
+        let used_solver = self.records.len() - solved;
+        if used_solver > 0 {
+            write!(s, " (and {} finished using :solve)", used_solver).unwrap();
+        }
+        let (current, longest) = self.streaks(today);
+        write!(
+            s,
+            "\nCurrent streak: {} (longest: {})\n",
+            days(current),
+            days(longest)
+        ).unwrap();
+        if solved == 0 {
+            return s;
+        }
+
+        let summaries = self.by_difficulty();
+        s += "\nSIZE   DIFFICULTY  SOLVED  BEST      AVERAGE\n";
+        for summary in &summaries {
+            writeln!(
+                s,
+                "{:<7}{:<12}{:<8}{:<10}{}",
+                format!("{0}x{0}", summary.size),
+                summary.difficulty.to_string(),
+                summary.solved,
+                util::format_duration(summary.best),
+                util::format_duration(summary.average)
+            ).unwrap();
+        }
+
+        for summary in &summaries {
+            write!(
+                s,
+                "\nFastest {0}x{0} {1} games:\n",
+                summary.size,
+                summary.difficulty
+            ).unwrap();
+            s += "RANK  DATE        TIME      HINTS  UNDOS  MISTAKES\n";
+            let leaderboard = self.leaderboard(summary.size, summary.difficulty, top);
+            for (i, r) in leaderboard.into_iter().enumerate() {
+                let c = r.completion;
+                writeln!(
+                    s,
+                    "{:<6}{:<12}{:<10}{:<7}{:<7}{}",
+                    i + 1,
+                    format_date(r.date),
+                    util::format_duration(c.time()),
+                    c.hints(),
+                    c.undos(),
+                    c.mistakes()
+                ).unwrap();
+            }
+        }
+        s
+    }
+
+    /// Saves the history to the file at the given path, replacing anything already there. The
+    /// directory containing the file is created if necessary.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).chain_err(|| {
+                format!("could not create directory `{}`", dir.display())
+            })?;
+        }
+        File::create(path)
+            .and_then(|mut f| f.write_all(self.to_stats_string().as_bytes()))
+            .chain_err(|| format!("could not save statistics to `{}`", path.display()))
+    }
+
+    /// Returns the current and longest streaks of days (in UTC) on which the player solved at
+    /// least one game, as of the given date (in seconds since the Unix epoch). The current streak
+    /// only counts if the last game was solved today or yesterday.
+    pub fn streaks(&self, today: u64) -> (u32, u32) {
+        let mut days = self.solved().map(|r| r.date / DAY).collect::<Vec<_>>();
+        days.sort();
+        days.dedup();
+        let (mut streak, mut longest) = (0, 0);
+        for (i, &day) in days.iter().enumerate() {
+            streak = if i > 0 && days[i - 1] + 1 == day {
+                streak + 1
+            } else {
+                1
+            };
+            longest = longest.max(streak);
+        }
+        match days.last() {
+            Some(&last) if last + 1 >= today / DAY => (streak, longest),
+            _ => (0, longest),
+        }
+    }
+
+    /// Returns the history in the format described in the module documentation, which can be
+    /// read by `from_stats_string`.
+    pub fn to_stats_string(&self) -> String {
+        let mut s = format!("{}\n", HEADER);
+        for r in &self.records {
+            let c = r.completion;
+            writeln!(
+                s,
+                "{} {} {} {} {}.{:03} {} {} {}{}",
+                r.date,
+                r.shape,
+                r.difficulty,
+                r.puzzle,
+                c.time().as_secs(),
+                c.time().subsec_millis(),
+                c.hints(),
+                c.undos(),
+                c.mistakes(),
+                if c.solver() { " solve" } else { "" }
+            ).unwrap();
+        }
+        s
+    }
+
+    /// Returns the records of the games solved by the player (rather than using the `solve`
+    /// action).
+    fn solved(&self) -> impl Iterator<Item = &Record> {
+        self.records.iter().filter(|r| !r.completion.solver())
+    }
+}
+
+/// Returns the path of the file where statistics about finished games are kept, or `None` if
+/// there is nowhere to put it (see `util::data_dir`).
+pub fn stats_path() -> Option<PathBuf> {
+    util::data_dir().map(|d| d.join(STATS_FILE))
+}
+
+/// Returns the current date, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Adds a record of the given game, finished now, to the statistics file (see `stats_path`). Does
+/// nothing if the game hasn't been solved, or if there's no statistics file.
+pub fn record(game: &Game) -> Result<()> {
+    let (path, record) = match (stats_path(), Record::new(game, now())) {
+        (Some(path), Some(record)) => (path, record),
+        _ => return Ok(()),
+    };
+    let mut stats = Stats::load(&path)?;
+    stats.add(record);
+    stats.save(&path)
+}
+
+/// Parses a line of a statistics file.
+fn parse_record(line: &str) -> Result<Record> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let invalid = || ErrorKind::InvalidStats(format!("invalid record `{}`", line));
+    let solver = match words.get(8) {
+        None => false,
+        Some(&"solve") if words.len() == 9 => true,
+        _ => return Err(invalid().into()),
+    };
+    let (date, shape, difficulty, puzzle, time, hints, undos, mistakes) = match words[..] {
+        [date, shape, difficulty, puzzle, time, hints, undos, mistakes, ..] => {
+            (date, shape, difficulty, puzzle, time, hints, undos, mistakes)
+        }
+        _ => return Err(invalid().into()),
+    };
+    let time = match time.parse::<f64>() {
+        Ok(t) if t.is_finite() && t >= 0.0 => Duration::from_millis((t * 1000.0).round() as u64),
+        _ => return Err(invalid().into()),
+    };
+    let count = |n: &str| n.parse::<u32>().map_err(|_| Error::from(invalid()));
+    Ok(Record {
+        date: date.parse::<u64>().map_err(|_| invalid())?,
+        shape: shape.parse::<Shape>()?,
+        difficulty: difficulty.parse::<Difficulty>()?,
+        puzzle: puzzle.to_string(),
+        completion: Completion::new(time, count(hints)?, count(undos)?, count(mistakes)?, solver),
+    })
+}
+
+/// Formats a date (in seconds since the Unix epoch) as `YYYY-MM-DD`, in UTC.
+fn format_date(date: u64) -> String {
+    // Converts days since the epoch to a date in the proleptic Gregorian calendar; see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = date / DAY + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Returns a number of days, such as `1 day` or `3 days`.
+fn days(n: u32) -> String {
+    format!("{} day{}", n, if n == 1 { "" } else { "s" })
+}
@@ -0,0 +1,205 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Extra rules for sudoku variants.
+//!
+//! Every sudoku has the same basic rules (each row, column and box contains every number once),
+//! but many variants add more. A variant is described by a `Constraint`, which can add extra
+//! units (like the diagonals of Sudoku X), require certain pairs of cells to hold different
+//! numbers (like the cells a knight's move apart in anti-knight sudoku), or rule out numbers in
+//! some other way. Constraints are added to a `Sudoku` using `Sudoku::add_constraint`, after which
+//! they are respected by the solvers and the generator.
+
+use std::fmt::Debug;
+
+use errors::*;
+use super::{Shape, Sudoku};
+
+/// The names of the built-in variants, as accepted by `variant`.
+pub const VARIANTS: &[&str] = &["x", "hyper", "anti-knight", "anti-king", "non-consecutive"];
+
+/// A rule which a sudoku must follow, besides the basic rules.
+///
+/// All positions are given as `(row, column)`.
+pub trait Constraint: Debug {
+    /// Returns the name of the constraint, which is used to identify it in the text format of a
+    /// `Sudoku`. Constraints with the same name are considered to be the same.
+    fn name(&self) -> &str;
+
+    /// Returns the units which this constraint adds to a grid of the given shape: groups of cells
+    /// which must all hold different numbers.
+    fn units(&self, _shape: Shape) -> Vec<Vec<(usize, usize)>> {
+        Vec::new()
+    }
+
+    /// Returns the positions which must hold a different number to the given position in a grid
+    /// of the given shape, apart from those which share a unit with it.
+    fn peers(&self, _shape: Shape, _row: usize, _col: usize) -> Vec<(usize, usize)> {
+        Vec::new()
+    }
+
+    /// Returns a bitmask of any other numbers ruled out at the given position by the entries of
+    /// the board (bit `n` is set if `n` can't be put there).
+    fn excluded(&self, _s: &Sudoku, _row: usize, _col: usize) -> u32 {
+        0
+    }
+
+    /// Returns whether `excluded` is used by this constraint. A constraint which implements
+    /// `excluded` must also override this to return `true`; otherwise, the Dancing Links solver
+    /// assumes that its units and peers are all there is to it.
+    fn uses_excluded(&self) -> bool {
+        false
+    }
+}
+
+/// Sudoku X: each of the two main diagonals must also contain every number once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Diagonals;
+
+/// Hyper sudoku (also known as Windoku): there are extra boxes, one cell in from each corner of
+/// the grid and separated by a single row or column, which must also contain every number once.
+/// A 9x9 grid has four of these.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Windows;
+
+/// Anti-knight sudoku: cells a chess knight's move apart must hold different numbers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AntiKnight;
+
+/// Anti-king sudoku: cells a chess king's move apart (including diagonally) must hold different
+/// numbers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AntiKing;
+
+/// Non-consecutive sudoku: cells which share a side must not hold consecutive numbers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NonConsecutive;
+
+impl Constraint for Diagonals {
+    fn name(&self) -> &str {
+        "x"
+    }
+
+    fn units(&self, shape: Shape) -> Vec<Vec<(usize, usize)>> {
+        let size = shape.size();
+        vec![
+            (0..size).map(|i| (i, i)).collect(),
+            (0..size).map(|i| (i, size - 1 - i)).collect(),
+        ]
+    }
+}
+
+impl Constraint for Windows {
+    fn name(&self) -> &str {
+        "hyper"
+    }
+
+    fn units(&self, shape: Shape) -> Vec<Vec<(usize, usize)>> {
+        let (size, box_rows, box_cols) = (shape.size(), shape.box_rows(), shape.box_cols());
+        let rows = (1..size)
+            .step_by(box_rows + 1)
+            .filter(|&r| r + box_rows < size);
+        let cols = (1..size)
+            .step_by(box_cols + 1)
+            .filter(|&c| c + box_cols < size)
+            .collect::<Vec<_>>();
+        iproduct!(rows, cols)
+            .map(|(r, c)| iproduct!(r..r + box_rows, c..c + box_cols).collect())
+            .collect()
+    }
+}
+
+impl Constraint for AntiKnight {
+    fn name(&self) -> &str {
+        "anti-knight"
+    }
+
+    fn peers(&self, shape: Shape, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let moves = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+        offsets(shape, row, col, &moves)
+    }
+}
+
+impl Constraint for AntiKing {
+    fn name(&self) -> &str {
+        "anti-king"
+    }
+
+    fn peers(&self, shape: Shape, row: usize, col: usize) -> Vec<(usize, usize)> {
+        offsets(shape, row, col, &[(1, 1), (1, -1), (-1, -1), (-1, 1)])
+    }
+}
+
+impl Constraint for NonConsecutive {
+    fn name(&self) -> &str {
+        "non-consecutive"
+    }
+
+    fn excluded(&self, s: &Sudoku, row: usize, col: usize) -> u32 {
+        // This is called very often while solving, so the neighbours are checked directly
+        let size = s.size();
+        let mut neighbours = [0; 4];
+        if row > 0 {
+            neighbours[0] = s[row - 1][col];
+        }
+        if row + 1 < size {
+            neighbours[1] = s[row + 1][col];
+        }
+        if col > 0 {
+            neighbours[2] = s[row][col - 1];
+        }
+        if col + 1 < size {
+            neighbours[3] = s[row][col + 1];
+        }
+        neighbours
+            .iter()
+            .filter(|&&n| n != 0)
+            .fold(0, |excluded, &n| excluded | 1 << (n - 1) | 1 << (n + 1))
+    }
+
+    fn uses_excluded(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the built-in constraint for the variant with the given name (see `VARIANTS`).
+pub fn variant(name: &str) -> Result<Box<dyn Constraint>> {
+    Ok(match name {
+        "x" => Box::new(Diagonals),
+        "hyper" => Box::new(Windows),
+        "anti-knight" => Box::new(AntiKnight),
+        "anti-king" => Box::new(AntiKing),
+        "non-consecutive" => Box::new(NonConsecutive),
+        _ => bail!(ErrorKind::Parse(format!("unknown variant `{}`", name))),
+    })
+}
+
+/// Returns the positions on a grid of the given shape which are the given offsets away from
+/// `(row, col)`.
+fn offsets(
+    shape: Shape,
+    row: usize,
+    col: usize,
+    offsets: &[(isize, isize)],
+) -> Vec<(usize, usize)> {
+    let size = shape.size() as isize;
+    offsets
+        .iter()
+        .map(|&(i, j)| (row as isize + i, col as isize + j))
+        .filter(|&(i, j)| 0 <= i && i < size && 0 <= j && j < size)
+        .map(|(i, j)| (i as usize, j as usize))
+        .collect()
+}
@@ -0,0 +1,436 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! A solver based on Knuth's Algorithm X, using Dancing Links.
+//!
+//! A sudoku can be seen as an exact cover problem: each possible entry (a number in a particular
+//! cell) is a row of a matrix which covers several constraints (columns): the cell is filled, and
+//! the number appears in each unit containing the entry (its row, column and box, and any units
+//! added by the sudoku's variant, like the diagonals of Sudoku X). A solution is a set of rows
+//! which covers every constraint exactly once. The matrix is stored as a sparse grid of doubly
+//! linked nodes, so that rows and columns can be removed and restored cheaply while searching.
+//!
+//! Pairs of cells which must hold different numbers without sharing a unit (like the cells a
+//! knight's move apart in anti-knight sudoku) get secondary columns, which may be covered at most
+//! once rather than exactly once. Rules which can't be expressed this way (like the sums of killer
+//! sudoku cages) are checked against a board holding the entries chosen so far, and rows which
+//! would break them are skipped.
+
+use rand::Rng;
+
+use super::Sudoku;
+
+/// The index of the root node, which links together all the uncovered column headers.
+const ROOT: usize = 0;
+
+/// The state of a Dancing Links search over the solutions of a sudoku.
+///
+/// Nodes are referred to by their index in the link arrays. Index 0 is the root, the next indices
+/// are the column headers (one for each cell, and one for each number in each unit, followed by
+/// the secondary columns: one for each number in each pair of peers), and the rest are the nodes
+/// of the matrix rows.
+pub struct Dlx {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    /// The column header of each node.
+    column: Vec<usize>,
+    /// The entry `(row, column, number)` corresponding to each node.
+    entry: Vec<(usize, usize, u8)>,
+    /// The number of nodes remaining in each column (indexed by header).
+    size: Vec<usize>,
+    /// The first column of each unit of the board, or `None` for a unit which is too small to be
+    /// part of the exact cover.
+    unit_columns: Vec<Option<usize>>,
+    /// The first secondary column of each pair of peers containing each cell, row by row.
+    peer_columns: Vec<Vec<usize>>,
+    /// Whether the board has rules which the matrix doesn't cover, which must be checked
+    /// separately.
+    extra_rules: bool,
+    /// The board containing the given entries and the entries of the rows currently chosen.
+    board: Sudoku,
+    /// The nodes of the rows currently chosen as part of the solution.
+    stack: Vec<usize>,
+    /// Whether the rows currently chosen make up a solution which has already been found.
+    found: bool,
+    /// Whether the search is finished.
+    done: bool,
+    /// The number of rows which may still be chosen before the search gives up, if it is limited.
+    budget: Option<usize>,
+}
+
+impl Dlx {
+    /// Sets up a search for the solutions of the given sudoku.
+    pub fn new(s: &Sudoku) -> Self {
+        Dlx::with_entries(s, entries(s))
+    }
+
+    /// Sets up a search for the solutions of the given sudoku, in which the possible entries are
+    /// tried in a random order (so the first solution found is a random one).
+    pub fn with_rng<R: Rng>(s: &Sudoku, rng: &mut R) -> Self {
+        let mut entries = entries(s);
+        rng.shuffle(&mut entries);
+        Dlx::with_entries(s, entries)
+    }
+
+    /// Limits the search to choosing the given number of rows in total, after which it gives up
+    /// (see `exhausted`).
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = Some(budget);
+    }
+
+    /// Returns whether the search gave up because it ran out of budget, in which case there may
+    /// have been more solutions left to find.
+    pub fn exhausted(&self) -> bool {
+        self.budget == Some(0)
+    }
+
+    /// Searches for the next solution, whose rows are left in `stack`. Returns `false` if there
+    /// are no solutions left (or the budget has run out).
+    pub fn find_next(&mut self) -> bool {
+        // Continue from the last choice if we've already found the current solution
+        if self.found {
+            self.found = false;
+            self.done = !self.advance();
+        }
+        while !self.done {
+            if self.right[ROOT] == ROOT {
+                // Every constraint is covered, so we have a solution
+                self.found = true;
+                return true;
+            }
+
+            let c = self.choose_column();
+            self.cover(c);
+            let node = self.next_allowed(self.down[c]);
+            if node == c {
+                // Nothing can satisfy this constraint
+                self.uncover(c);
+                self.done = !self.advance();
+            } else {
+                if let Some(ref mut budget) = self.budget {
+                    if *budget == 0 {
+                        self.done = true;
+                        break;
+                    }
+                    *budget -= 1;
+                }
+                self.choose(node);
+            }
+        }
+        false
+    }
+
+    /// Sets up a search for the solutions of the given sudoku, with one matrix row for each of the
+    /// given entries `(row, column, number)` (in that order).
+    fn with_entries(s: &Sudoku, entries: Vec<(usize, usize, u8)>) -> Self {
+        let size = s.size();
+        let mut columns = size * size;
+        let mut unit_columns = Vec::new();
+        for unit in s.units() {
+            if unit.len() == size {
+                unit_columns.push(Some(columns));
+                columns += size;
+            } else {
+                unit_columns.push(None);
+            }
+        }
+        let primary = columns;
+        let cell_peers = &s.layout.cell_peers;
+        let mut peer_columns = vec![Vec::new(); size * size];
+        for (a, peers) in cell_peers.iter().enumerate() {
+            for &b in peers {
+                // Each pair only needs its columns once, even if both cells list the other
+                if a < b || !cell_peers[b].contains(&a) {
+                    peer_columns[a].push(columns);
+                    peer_columns[b].push(columns);
+                    columns += size;
+                }
+            }
+        }
+        let extra_rules = !s.cages.is_empty() || unit_columns.contains(&None) ||
+            s.layout.constraints.iter().any(|c| c.uses_excluded());
+
+        let mut dlx = Dlx {
+            left: Vec::new(),
+            right: Vec::new(),
+            up: Vec::new(),
+            down: Vec::new(),
+            column: Vec::new(),
+            entry: Vec::new(),
+            size: vec![0; columns + 1],
+            unit_columns,
+            peer_columns,
+            extra_rules,
+            board: s.clone(),
+            stack: Vec::new(),
+            found: false,
+            budget: None,
+            // A board which already breaks the rules can't be fixed by filling in more entries
+            done: s.is_broken(),
+        };
+
+        // The root and primary column headers, linked together horizontally; the secondary column
+        // headers are left out, so that they are never chosen to be covered
+        for i in 0..columns + 1 {
+            if i > primary {
+                dlx.left.push(i);
+                dlx.right.push(i);
+            } else {
+                dlx.left.push(if i == 0 { primary } else { i - 1 });
+                dlx.right.push(if i == primary { 0 } else { i + 1 });
+            }
+            dlx.up.push(i);
+            dlx.down.push(i);
+            dlx.column.push(i);
+            dlx.entry.push((0, 0, 0));
+        }
+
+        // One matrix row for every entry; the rows for the given entries are then removed from the
+        // matrix by covering their columns.
+        let mut givens = Vec::new();
+        for (row, col, n) in entries {
+            let first = dlx.add_row(row, col, n);
+            if s[row][col] == n {
+                givens.push(first);
+            }
+        }
+        if !dlx.done {
+            for first in givens {
+                dlx.cover(dlx.column[first]);
+                dlx.cover_row(first);
+            }
+        }
+
+        dlx
+    }
+
+    /// Adds a row for the given entry to the matrix, returning the index of its first node.
+    fn add_row(&mut self, row: usize, col: usize, n: u8) -> usize {
+        let size = self.board.size();
+        let d = n as usize - 1;
+        let mut columns = vec![size * row + col];
+        columns.extend(
+            self.board.layout.cell_units[size * row + col]
+                .iter()
+                .filter_map(|&u| self.unit_columns[u])
+                .chain(self.peer_columns[size * row + col].iter().cloned())
+                .map(|c| c + d),
+        );
+        let first = self.left.len();
+        let end = first + columns.len() - 1;
+
+        for (k, &c) in columns.iter().enumerate() {
+            // Column headers are offset by one because of the root
+            let header = c + 1;
+            let node = first + k;
+            self.left.push(if node == first { end } else { node - 1 });
+            self.right.push(if node == end { first } else { node + 1 });
+            // Insert at the bottom of the column
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            let last = self.up[header];
+            self.down[last] = node;
+            self.up[header] = node;
+            self.column.push(header);
+            self.entry.push((row, col, n));
+            self.size[header] += 1;
+        }
+
+        first
+    }
+
+    /// Removes the given column from the header list, along with every row which intersects it.
+    fn cover(&mut self, c: usize) {
+        let (l, r) = (self.left[c], self.right[c]);
+        self.right[l] = r;
+        self.left[r] = l;
+
+        let mut i = self.down[c];
+        while i != c {
+            let mut j = self.right[i];
+            while j != i {
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = d;
+                self.up[d] = u;
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    /// Restores the given column, undoing `cover`.
+    fn uncover(&mut self, c: usize) {
+        let mut i = self.up[c];
+        while i != c {
+            let mut j = self.left[i];
+            while j != i {
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = j;
+                self.up[d] = j;
+                self.size[self.column[j]] += 1;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        let (l, r) = (self.left[c], self.right[c]);
+        self.right[l] = c;
+        self.left[r] = c;
+    }
+
+    /// Covers the columns of all the other nodes in the row of the given node.
+    fn cover_row(&mut self, node: usize) {
+        let mut j = self.right[node];
+        while j != node {
+            let c = self.column[j];
+            self.cover(c);
+            j = self.right[j];
+        }
+    }
+
+    /// Undoes `cover_row` for the given node.
+    fn uncover_row(&mut self, node: usize) {
+        let mut j = self.left[node];
+        while j != node {
+            let c = self.column[j];
+            self.uncover(c);
+            j = self.left[j];
+        }
+    }
+
+    /// Returns the uncovered column with the fewest nodes. If there are rules which the matrix
+    /// doesn't cover, only the nodes whose entries are allowed by them are counted, so that dead
+    /// ends are found as early as possible.
+    fn choose_column(&self) -> usize {
+        let size = self.board.size();
+        let hints = if self.extra_rules {
+            iproduct!(0..size, 0..size)
+                .map(|(row, col)| self.board.hints_at(row, col))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut best = self.right[ROOT];
+        let mut best_size = usize::MAX;
+        let mut c = best;
+        while c != ROOT && best_size > 0 {
+            let size = if self.extra_rules {
+                self.allowed_count(c, &hints, best_size)
+            } else {
+                self.size[c]
+            };
+            if size < best_size {
+                best = c;
+                best_size = size;
+            }
+            c = self.right[c];
+        }
+        best
+    }
+
+    /// Returns the number of nodes in the given column whose entries are allowed by the given
+    /// hints (for each cell, row by row), counting no higher than `max`.
+    fn allowed_count(&self, c: usize, hints: &[u32], max: usize) -> usize {
+        let size = self.board.size();
+        let mut count = 0;
+        let mut node = self.down[c];
+        while node != c && count < max {
+            let (row, col, n) = self.entry[node];
+            if hints[row * size + col] & 1 << n != 0 {
+                count += 1;
+            }
+            node = self.down[node];
+        }
+        count
+    }
+
+    /// Returns the first node, starting from `node` and moving down its column, whose entry can
+    /// be put on the board without breaking any rules that the matrix doesn't cover. The column
+    /// header is returned if there is no such node.
+    fn next_allowed(&self, mut node: usize) -> usize {
+        // Otherwise, the matrix itself makes sure every remaining entry is allowed
+        if !self.extra_rules {
+            return node;
+        }
+        while node != self.column[node] {
+            let (row, col, n) = self.entry[node];
+            if self.board.hints_at(row, col) & 1 << n != 0 {
+                break;
+            }
+            node = self.down[node];
+        }
+        node
+    }
+
+    /// Adds the row of the given node to the solution.
+    fn choose(&mut self, node: usize) {
+        let (row, col, n) = self.entry[node];
+        self.board.put_at(n, row, col);
+        self.stack.push(node);
+        self.cover_row(node);
+    }
+
+    /// Replaces the most recently chosen row with the next allowed row in its column, backtracking
+    /// as necessary. Returns `false` if there is nothing left to try.
+    fn advance(&mut self) -> bool {
+        while let Some(node) = self.stack.pop() {
+            self.uncover_row(node);
+            let (row, col, _) = self.entry[node];
+            self.board.remove_at(row, col);
+            let c = self.column[node];
+            let next = self.next_allowed(self.down[node]);
+            if next != c {
+                self.choose(next);
+                return true;
+            }
+            self.uncover(c);
+        }
+        false
+    }
+
+    /// Returns the solution corresponding to the rows currently chosen.
+    fn solution(&self) -> Sudoku {
+        self.board.clone()
+    }
+}
+
+impl Iterator for Dlx {
+    type Item = Sudoku;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.find_next() {
+            Some(self.solution())
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the entries `(row, column, number)` which need a row in the matrix for the given sudoku:
+/// the given entries, and every number which could still be put in each empty cell. Other entries
+/// would be removed as soon as the givens were covered, so there is no point adding them.
+fn entries(s: &Sudoku) -> Vec<(usize, usize, u8)> {
+    let size = s.size();
+    iproduct!(0..size, 0..size, 1..=size as u8)
+        .filter(|&(row, col, n)| {
+            s[row][col] == n || s.hints_at(row, col) & 1 << n != 0
+        })
+        .collect()
+}
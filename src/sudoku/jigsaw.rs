@@ -0,0 +1,274 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Jigsaw sudoku regions.
+//!
+//! In a jigsaw sudoku, the boxes are replaced by irregular regions: any connected groups of cells
+//! of the same size as a box, which must each contain every number once, just like a box.
+
+use std::fmt::{Formatter, Result as FmtResult};
+use std::rc::Rc;
+
+use rand::Rng;
+
+use errors::*;
+use super::{digit_char, parse_digit, Layout, Sudoku};
+use super::dlx::Dlx;
+use super::killer::{is_connected, neighbours};
+use super::logic::Difficulty;
+
+/// The number of guesses the solver may make when checking that random regions can be filled in
+/// before trying different ones.
+const REGIONS_BUDGET: usize = 1000;
+
+impl Sudoku {
+    /// Makes the sudoku a jigsaw sudoku, with the given regions in place of the boxes. The regions
+    /// are given by the index of the region containing each cell, row by row.
+    ///
+    /// An error is returned if there isn't a region index (between 0 and the size of the sudoku)
+    /// for every cell, if any region has the wrong number of cells or is not connected, or if the
+    /// entries already on the board repeat a number within a region.
+    pub fn set_regions(&mut self, regions: Vec<usize>) -> Result<()> {
+        let size = self.size();
+        if regions.len() != size * size {
+            bail!(ErrorKind::InvalidRegions(format!(
+                "expected a region for each of the {} cells, but found {}",
+                size * size,
+                regions.len()
+            )));
+        }
+        if let Some(&r) = regions.iter().find(|&&r| r >= size) {
+            bail!(ErrorKind::InvalidRegions(format!(
+                "there are only {} regions, but found region {}",
+                size,
+                r + 1
+            )));
+        }
+        for r in 0..size {
+            let cells = iproduct!(0..size, 0..size)
+                .filter(|&(i, j)| regions[i * size + j] == r)
+                .collect::<Vec<_>>();
+            if cells.len() != size {
+                bail!(ErrorKind::InvalidRegions(format!(
+                    "region {} has {} cells instead of {}",
+                    r + 1,
+                    cells.len(),
+                    size
+                )));
+            }
+            if !is_connected(&cells) {
+                bail!(ErrorKind::InvalidRegions(
+                    format!("the cells of region {} are not connected", r + 1),
+                ));
+            }
+        }
+
+        let constraints = self.layout.constraints.clone();
+        let mut s = self.clone();
+        s.set_layout(Rc::new(Layout::new(self.shape, Some(regions), constraints)));
+        if s.is_broken() {
+            bail!(ErrorKind::InvalidSudoku);
+        }
+        *self = s;
+        Ok(())
+    }
+
+    /// Returns whether this is a jigsaw sudoku, with irregular regions in place of the boxes.
+    pub fn is_jigsaw(&self) -> bool {
+        self.layout.regions.is_some()
+    }
+
+    /// Returns the index of the region containing the given position, which is the index of its
+    /// box unless this is a jigsaw sudoku.
+    pub fn region_index(&self, row: usize, col: usize) -> usize {
+        match self.layout.regions {
+            Some(ref regions) => regions[row * self.size() + col],
+            None => self.shape.box_index(row, col),
+        }
+    }
+
+    /// Returns the positions of the cells in the given region, row by row.
+    pub fn region_cells(&self, r: usize) -> &[(usize, usize)] {
+        // The regions come straight after the rows and columns
+        &self.layout.units[2 * self.size() + r]
+    }
+
+    /// Generates a random, uniquely solvable jigsaw sudoku with the same shape as this one (whose
+    /// entries and regions are ignored), optionally of the given difficulty. The regions are
+    /// random shapes, made by swapping cells between neighbouring boxes; see
+    /// `generate_with_difficulty` for details on how the difficulty is met.
+    ///
+    /// If this sudoku has cages, the generated sudoku is also a killer sudoku with new cages.
+    pub fn generate_jigsaw_like<R: Rng>(
+        &self,
+        difficulty: Option<Difficulty>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        self.generate_jigsaw(!self.cages.is_empty(), difficulty, rng)
+    }
+
+    /// Generates a random, uniquely solvable jigsaw sudoku which is also a killer sudoku, with
+    /// the same shape as this one; see `generate_jigsaw_like` and `generate_killer_like`.
+    pub fn generate_killer_jigsaw_like<R: Rng>(
+        &self,
+        difficulty: Option<Difficulty>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        self.generate_jigsaw(true, difficulty, rng)
+    }
+
+    /// Generates a random jigsaw sudoku (or killer jigsaw sudoku, if `killer` is set) with the
+    /// same shape as this one.
+    fn generate_jigsaw<R: Rng>(
+        &self,
+        killer: bool,
+        difficulty: Option<Difficulty>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        let mut template = self.blank();
+        // Not every set of regions can be filled in, so we keep trying until one can
+        loop {
+            template
+                .set_regions(random_regions(self, rng))
+                .expect("generated regions are invalid");
+            let mut search = Dlx::with_rng(&template.blank(), rng);
+            search.set_budget(REGIONS_BUDGET);
+            if search.next().is_some() {
+                break;
+            }
+        }
+        template.generate_puzzle(killer, difficulty, rng)
+    }
+
+    /// Writes the regions of a jigsaw sudoku as part of the `Display` implementation: one line
+    /// starting with `regions:`, followed by the region number of each cell (counting from 1 and
+    /// written like an entry), with a space between each row.
+    pub(super) fn fmt_regions(&self, f: &mut Formatter) -> FmtResult {
+        let size = self.size();
+        let rows = (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| digit_char(self.region_index(i, j) as u8 + 1))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        write!(f, "\nregions: {}", rows.join(" "))
+    }
+
+    /// Writes the grid of a jigsaw sudoku for the alternate `Display` format, with lines between
+    /// the cells of different regions.
+    pub(super) fn fmt_jigsaw_grid(&self, f: &mut Formatter) -> FmtResult {
+        let size = self.size();
+        let empty = if size > 9 { '.' } else { '0' };
+        // Whether there is a border between the cells at the given positions, which are off the
+        // grid at the edges
+        let border = |a: (usize, usize), b: (usize, usize)| {
+            a.0 >= size || a.1 >= size || b.0 >= size || b.1 >= size ||
+                self.region_index(a.0, a.1) != self.region_index(b.0, b.1)
+        };
+        // Positions above and to the left of the grid wrap around to a large number
+        let prev = |n: usize| n.wrapping_sub(1);
+
+        for i in 0..size + 1 {
+            // The border above the row
+            let mut line = String::new();
+            for j in 0..size + 1 {
+                let corner = border((prev(i), prev(j)), (prev(i), j)) ||
+                    border((i, prev(j)), (i, j)) ||
+                    border((prev(i), prev(j)), (i, prev(j))) ||
+                    border((prev(i), j), (i, j));
+                line.push(if corner { '+' } else { ' ' });
+                if j < size {
+                    line.push(if border((prev(i), j), (i, j)) { '-' } else { ' ' });
+                }
+            }
+            write!(f, "{}", line.trim_end())?;
+            if i == size {
+                break;
+            }
+
+            write!(f, "\n|")?;
+            for j in 0..size {
+                let n = self[i][j];
+                write!(f, "{}", if n == 0 { empty } else { digit_char(n) })?;
+                write!(f, "{}", if border((i, j), (i, j + 1)) { '|' } else { ' ' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the region map of a jigsaw sudoku, in the format written by `fmt_regions` (ignoring the
+/// `regions:` prefix), into the index of the region of each cell.
+pub(super) fn parse_regions(s: &str, size: usize) -> Result<Vec<usize>> {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match parse_digit(c) {
+            Some(n) if n as usize <= size => Ok(n as usize - 1),
+            _ => bail!(ErrorKind::Parse(format!("invalid region `{}`", c))),
+        })
+        .collect()
+}
+
+/// Returns random regions for a jigsaw sudoku with the same shape as the given one, starting from
+/// its boxes and repeatedly swapping cells between neighbouring regions.
+fn random_regions<R: Rng>(s: &Sudoku, rng: &mut R) -> Vec<usize> {
+    let (shape, size) = (s.shape(), s.size());
+    let mut regions = iproduct!(0..size, 0..size)
+        .map(|(i, j)| shape.box_index(i, j))
+        .collect::<Vec<_>>();
+    let region = |regions: &[usize], (i, j): (usize, usize)| regions[i * size + j];
+    let cells_of = |regions: &[usize], r: usize| {
+        iproduct!(0..size, 0..size)
+            .filter(|&p| region(regions, p) == r)
+            .collect::<Vec<_>>()
+    };
+
+    let mut swaps = 0;
+    while swaps < 2 * size * size {
+        // A cell on the edge of its region moves into a neighbouring region, in exchange for a
+        // cell of that region which touches the first
+        let a = (rng.gen_range(0, size), rng.gen_range(0, size));
+        let options = neighbours(size, a)
+            .into_iter()
+            .filter(|&p| region(&regions, p) != region(&regions, a))
+            .collect::<Vec<_>>();
+        let from = region(&regions, a);
+        let to = match rng.choose(&options) {
+            Some(&b) => region(&regions, b),
+            None => continue,
+        };
+        let options = cells_of(&regions, to)
+            .into_iter()
+            .filter(|&p| neighbours(size, p).iter().any(|&q| q != a && region(&regions, q) == from))
+            .collect::<Vec<_>>();
+        let c = match rng.choose(&options) {
+            Some(&c) => c,
+            None => continue,
+        };
+
+        regions[a.0 * size + a.1] = to;
+        regions[c.0 * size + c.1] = from;
+        if is_connected(&cells_of(&regions, from)) && is_connected(&cells_of(&regions, to)) {
+            swaps += 1;
+        } else {
+            regions[a.0 * size + a.1] = from;
+            regions[c.0 * size + c.1] = to;
+        }
+    }
+    regions
+}
@@ -0,0 +1,300 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Killer sudoku cages.
+//!
+//! In a killer sudoku, groups of cells called cages are each given a target sum: the numbers in a
+//! cage must add up to its sum, and no number may be repeated within a cage. Since only certain
+//! combinations of distinct numbers add up to any particular sum, a cage restricts which numbers
+//! can go in its cells even when it is completely empty.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use itertools::Itertools;
+use rand::Rng;
+
+use errors::*;
+use super::{parse_position, Cage, Occupancy, Sudoku};
+use super::logic::Difficulty;
+
+/// The sizes of cage to aim for when generating a killer sudoku, chosen from at random. A cage
+/// may end up smaller if it runs out of room to grow.
+const CAGE_SIZES: &[usize] = &[2, 2, 2, 3, 3, 3, 4, 4, 5];
+
+impl Cage {
+    /// Returns the target sum of the cage.
+    pub fn sum(&self) -> u32 {
+        self.sum
+    }
+
+    /// Returns the positions of the cells in the cage, row by row.
+    pub fn cells(&self) -> &[(usize, usize)] {
+        &self.cells
+    }
+
+    /// Returns a bitmask of the numbers which could still go in the empty cells of the cage,
+    /// given a bitmask of the numbers already in it (`used`) and of the numbers which the empty
+    /// cells could possibly hold (`possible`). A number is allowed if it is part of some
+    /// combination which adds up to the cage's sum and which can be made from these.
+    pub(super) fn allowed(&self, used: u32, possible: u32) -> u32 {
+        self.combinations
+            .iter()
+            .filter(|&&c| c & used == used && c & !used & !possible == 0)
+            .fold(0, |allowed, &c| allowed | c)
+            & !used
+    }
+
+    /// Returns whether the numbers in `used` could be part of a valid combination for the cage.
+    pub(super) fn can_contain(&self, used: u32) -> bool {
+        self.combinations.iter().any(|&c| c & used == used)
+    }
+}
+
+impl Display for Cage {
+    /// Formats the cage as its sum, followed by a colon and the positions of its cells (1-based,
+    /// like `r1c2` for the second cell of the first row), separated by spaces.
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "{}: {}",
+            self.sum,
+            self.cells
+                .iter()
+                .map(|&(row, col)| format!("r{}c{}", row + 1, col + 1))
+                .join(" ")
+        )
+    }
+}
+
+impl Sudoku {
+    /// Adds a killer sudoku cage with the given sum, made up of the given cells.
+    ///
+    /// An error is returned if the cells are not all on the grid, if any of them is already part
+    /// of another cage, if they are not connected (each cell must share a side with another cell
+    /// of the cage), or if no combination of different numbers in the cage adds up to `sum`.
+    pub fn add_cage(&mut self, sum: u32, mut cells: Vec<(usize, usize)>) -> Result<()> {
+        let size = self.size();
+        cells.sort();
+        if cells.is_empty() || cells.len() > size {
+            bail!(ErrorKind::InvalidCage(format!(
+                "a cage must have between 1 and {} cells",
+                size
+            )));
+        }
+        for (i, &(row, col)) in cells.iter().enumerate() {
+            if row >= size || col >= size {
+                bail!(ErrorKind::InvalidCage(format!(
+                    "cell r{}c{} is not on the grid",
+                    row + 1,
+                    col + 1
+                )));
+            }
+            if self.cage_index(row, col).is_some() || cells[..i].contains(&(row, col)) {
+                bail!(ErrorKind::InvalidCage(format!(
+                    "cell r{}c{} is in more than one cage",
+                    row + 1,
+                    col + 1
+                )));
+            }
+        }
+        if !is_connected(&cells) {
+            bail!(ErrorKind::InvalidCage(format!(
+                "the cells of the cage with sum {} are not connected",
+                sum
+            )));
+        }
+        let combinations = combinations(size, cells.len(), sum);
+        if combinations.is_empty() {
+            bail!(ErrorKind::InvalidCage(format!(
+                "no {} different numbers add up to {}",
+                cells.len(),
+                sum
+            )));
+        }
+
+        if self.cell_cages.is_empty() {
+            self.cell_cages = vec![None; size * size];
+        }
+        let mut occupancy = Occupancy::default();
+        for &(row, col) in &cells {
+            self.cell_cages[row * size + col] = Some(self.cages.len());
+            let n = self[row][col];
+            if n != 0 && occupancy.add(n) {
+                self.duplicates += 1;
+            }
+        }
+        self.cages.push(Cage {
+            sum,
+            cells,
+            combinations,
+        });
+        self.cage_occupancy.push(occupancy);
+        Ok(())
+    }
+
+    /// Returns the killer sudoku cages of this sudoku, which is empty for an ordinary sudoku.
+    pub fn cages(&self) -> &[Cage] {
+        &self.cages
+    }
+
+    /// Returns the index (in `cages`) of the cage containing the given position, if there is one.
+    pub fn cage_index(&self, row: usize, col: usize) -> Option<usize> {
+        self.cell_cages.get(row * self.size() + col).and_then(|&c| c)
+    }
+
+    /// Generates a random, uniquely solvable killer sudoku with the same shape as this one (whose
+    /// entries and cages are ignored), optionally of the given difficulty. The cages are chosen
+    /// at random, and as many entries are removed as possible while keeping the solution unique;
+    /// see `generate_with_difficulty` for details on how the difficulty is met.
+    pub fn generate_killer_like<R: Rng>(
+        &self,
+        difficulty: Option<Difficulty>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        self.generate_puzzle(true, difficulty, rng)
+    }
+
+    /// Divides this (completely filled) sudoku into random cages, whose sums are taken from its
+    /// entries.
+    pub(super) fn add_random_cages<R: Rng>(&mut self, rng: &mut R) {
+        let size = self.size();
+        let mut starts = iproduct!(0..size, 0..size).collect::<Vec<_>>();
+        rng.shuffle(&mut starts);
+        let mut caged = vec![false; size * size];
+        let mut cages = Vec::new();
+
+        for start in starts {
+            if caged[start.0 * size + start.1] {
+                continue;
+            }
+            // Grow the cage one neighbouring cell at a time, never repeating a number
+            let target = *rng.choose(CAGE_SIZES).unwrap();
+            let mut cells = vec![start];
+            let mut used = 1 << self[start.0][start.1];
+            while cells.len() < target {
+                let options = cells
+                    .iter()
+                    .flat_map(|&p| neighbours(size, p))
+                    .filter(|&(row, col)| {
+                        !caged[row * size + col] && !cells.contains(&(row, col)) &&
+                            used & 1 << self[row][col] == 0
+                    })
+                    .collect::<Vec<_>>();
+                match rng.choose(&options) {
+                    Some(&(row, col)) => {
+                        cells.push((row, col));
+                        used |= 1 << self[row][col];
+                    }
+                    None => break,
+                }
+            }
+
+            for &(row, col) in &cells {
+                caged[row * size + col] = true;
+            }
+            cells.sort();
+            cages.push(cells);
+        }
+
+        // The cages are added from top to bottom, which makes them easier to read when listed
+        cages.sort();
+        for cells in cages {
+            let sum = cells.iter().map(|&(row, col)| self[row][col] as u32).sum();
+            self.add_cage(sum, cells).expect("generated cage is invalid");
+        }
+    }
+}
+
+/// Parses a cage in the format given by its `Display` implementation, returning its sum and
+/// cells.
+pub(super) fn parse_cage(s: &str) -> Result<(u32, Vec<(usize, usize)>)> {
+    let mut parts = s.splitn(2, ':');
+    let sum = parts.next().unwrap().trim();
+    let sum = sum.parse::<u32>()
+        .chain_err(|| ErrorKind::Parse(format!("invalid cage sum `{}`", sum)))?;
+    let cells = parts
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+        .map(parse_position)
+        .collect::<Result<Vec<_>>>()?;
+    Ok((sum, cells))
+}
+
+/// Returns every set of `len` different numbers between 1 and `size` which add up to `sum`, as
+/// bitmasks.
+fn combinations(size: usize, len: usize, sum: u32) -> Vec<u32> {
+    fn extend(from: u32, size: u32, len: u32, sum: u32, mask: u32, found: &mut Vec<u32>) {
+        if len == 0 {
+            if sum == 0 {
+                found.push(mask);
+            }
+            return;
+        }
+        for n in from..size + 1 {
+            // The smallest and largest totals which could be made using `n` as the next number
+            let min = n * len + len * (len - 1) / 2;
+            let max = n + (len - 1) * size - (len - 1) * len.saturating_sub(2) / 2;
+            if min > sum {
+                break;
+            }
+            if max >= sum {
+                extend(n + 1, size, len - 1, sum - n, mask | 1 << n, found);
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    extend(1, size as u32, len as u32, sum, 0, &mut found);
+    found
+}
+
+/// Returns whether the given cells are connected, with each cell reachable from any other by
+/// moving between cells which share a side.
+pub(super) fn is_connected(cells: &[(usize, usize)]) -> bool {
+    let mut reached = vec![cells[0]];
+    let mut i = 0;
+    while i < reached.len() {
+        let (row, col) = reached[i];
+        for &p in cells {
+            let adjacent = (p.0 == row && (p.1 + 1 == col || col + 1 == p.1)) ||
+                (p.1 == col && (p.0 + 1 == row || row + 1 == p.0));
+            if adjacent && !reached.contains(&p) {
+                reached.push(p);
+            }
+        }
+        i += 1;
+    }
+    reached.len() == cells.len()
+}
+
+/// Returns the positions which share a side with the given position in a grid of the given size.
+pub(super) fn neighbours(size: usize, (row, col): (usize, usize)) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    if row > 0 {
+        result.push((row - 1, col));
+    }
+    if row + 1 < size {
+        result.push((row + 1, col));
+    }
+    if col > 0 {
+        result.push((row, col - 1));
+    }
+    if col + 1 < size {
+        result.push((row, col + 1));
+    }
+    result
+}
@@ -0,0 +1,861 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Human-style logical solving.
+//!
+//! Unlike the backtracking search performed by `Solutions`, the deductions made here are the same
+//! kind that a human player would make, using a handful of well-known named techniques. Each
+//! deduction is reported as a `Step`, so that a solution can be explained rather than just
+//! printed.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use errors::*;
+use super::{digit_char, Annotations, Sudoku};
+
+/// The score added to a rating when the puzzle cannot be solved using the known techniques.
+const GUESS_SCORE: u32 = 1000;
+/// The number of times the hardest technique must be used before a puzzle is considered to be one
+/// difficulty band harder.
+const BUMP_USES: usize = 3;
+
+/// A named solving technique.
+///
+/// Techniques are ordered from easiest to hardest; when looking for the next deduction, easier
+/// techniques are always tried first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    /// A cell which has only one candidate left.
+    NakedSingle,
+    /// A digit which has only one possible cell left in some unit.
+    HiddenSingle,
+    /// All candidates for a digit in a box lie in a single row or column, so the digit can be
+    /// eliminated from the rest of that row or column.
+    PointingPair,
+    /// All candidates for a digit in a row or column lie in a single box, so the digit can be
+    /// eliminated from the rest of that box.
+    BoxLineReduction,
+    /// A candidate in a killer sudoku cage which is not part of any combination of different
+    /// numbers adding up to the cage's sum (given the candidates of the rest of the cage).
+    CageCombination,
+    /// Two cells in a unit which have the same two candidates.
+    NakedPair,
+    /// Two digits which are confined to the same two cells in a unit.
+    HiddenPair,
+    /// Three cells in a unit whose candidates are drawn from the same three digits.
+    NakedTriple,
+    /// Three digits which are confined to the same three cells in a unit.
+    HiddenTriple,
+    /// A digit confined to the same two columns in two rows (or vice versa).
+    XWing,
+    /// A cell with candidates `xy` which sees two cells with candidates `xz` and `yz`.
+    XYWing,
+    /// A digit confined to the same three columns in three rows (or vice versa).
+    Swordfish,
+}
+
+/// The difficulty band of a puzzle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    /// Can be solved using only singles.
+    Easy,
+    /// Needs pairs, intersections (pointing pairs and box/line reduction) or cage combinations.
+    Medium,
+    /// Needs triples or X-Wings.
+    Hard,
+    /// Needs XY-Wings or swordfish.
+    Expert,
+    /// Cannot be solved using any of the known techniques (guessing is required).
+    Diabolical,
+}
+
+/// A difficulty rating for a puzzle, determined by solving it logically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rating {
+    /// The difficulty band of the puzzle.
+    pub difficulty: Difficulty,
+    /// A numeric score, where higher is harder. Each deduction adds the score of the technique
+    /// used, and puzzles which cannot be solved logically get a large fixed penalty.
+    pub score: u32,
+    /// The hardest technique needed to solve the puzzle (or `None` if no deductions were made).
+    pub hardest: Option<Technique>,
+    /// Whether the puzzle could be solved completely using the known techniques.
+    pub solved: bool,
+}
+
+/// A unit (row, column or box) of a `Sudoku`, in which each digit must appear exactly once.
+///
+/// All indices are 0-based, and boxes are numbered from left to right and top to bottom. In a
+/// jigsaw sudoku, the boxes are the regions (see `Sudoku::region_index`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Row(usize),
+    Column(usize),
+    Box(usize),
+    /// A unit added by the sudoku's variant, such as a diagonal of Sudoku X, numbered in the order
+    /// given by `Sudoku::units` (after the rows, columns and boxes).
+    Extra(usize),
+}
+
+/// A single deduction made by the logical solver.
+///
+/// All positions are given as `(row, column)` and entries as `(row, column, number)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// The technique used to make the deduction.
+    pub technique: Technique,
+    /// The unit in which the deduction was made, if the technique works within a single unit.
+    pub unit: Option<Unit>,
+    /// The digits which make up the pattern.
+    pub digits: Vec<u8>,
+    /// The cells which make up the pattern.
+    pub cells: Vec<(usize, usize)>,
+    /// The numbers which can be placed as a result of this step.
+    pub placements: Vec<(usize, usize, u8)>,
+    /// The candidates which can be eliminated as a result of this step.
+    pub eliminations: Vec<(usize, usize, u8)>,
+}
+
+/// An iterator over the logical deductions which can be made for a particular sudoku.
+///
+/// Each call to `next` finds the easiest available deduction and applies it, so the iterator
+/// ends either when the sudoku is solved or when none of the known techniques make any progress.
+/// The state reached at that point can be examined using `board` and `candidates`.
+#[derive(Clone, Debug)]
+pub struct Deductions {
+    /// The board, with all placements made so far.
+    board: Sudoku,
+    /// The remaining candidates for each cell, as bitmasks in which bit `n` is set if `n` is a
+    /// candidate.
+    candidates: Vec<Vec<u32>>,
+}
+
+impl Technique {
+    /// All techniques, from easiest to hardest.
+    pub const ALL: &'static [Technique] = &[
+        Technique::NakedSingle,
+        Technique::HiddenSingle,
+        Technique::PointingPair,
+        Technique::BoxLineReduction,
+        Technique::CageCombination,
+        Technique::NakedPair,
+        Technique::HiddenPair,
+        Technique::NakedTriple,
+        Technique::HiddenTriple,
+        Technique::XWing,
+        Technique::XYWing,
+        Technique::Swordfish,
+    ];
+
+    /// Returns the human-readable name of the technique.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Technique::NakedSingle => "naked single",
+            Technique::HiddenSingle => "hidden single",
+            Technique::PointingPair => "pointing pair",
+            Technique::BoxLineReduction => "box/line reduction",
+            Technique::CageCombination => "cage combination",
+            Technique::NakedPair => "naked pair",
+            Technique::HiddenPair => "hidden pair",
+            Technique::NakedTriple => "naked triple",
+            Technique::HiddenTriple => "hidden triple",
+            Technique::XWing => "X-Wing",
+            Technique::XYWing => "XY-Wing",
+            Technique::Swordfish => "swordfish",
+        }
+    }
+
+    /// Returns the difficulty band to which this technique belongs.
+    pub fn difficulty(&self) -> Difficulty {
+        match *self {
+            Technique::NakedSingle | Technique::HiddenSingle => Difficulty::Easy,
+            Technique::PointingPair |
+            Technique::BoxLineReduction |
+            Technique::CageCombination |
+            Technique::NakedPair |
+            Technique::HiddenPair => Difficulty::Medium,
+            Technique::NakedTriple | Technique::HiddenTriple | Technique::XWing => Difficulty::Hard,
+            Technique::XYWing | Technique::Swordfish => Difficulty::Expert,
+        }
+    }
+
+    /// Returns the score added to a rating each time this technique is used.
+    pub fn score(&self) -> u32 {
+        match *self {
+            Technique::NakedSingle => 1,
+            Technique::HiddenSingle => 2,
+            Technique::PointingPair => 5,
+            Technique::BoxLineReduction => 5,
+            Technique::CageCombination => 5,
+            Technique::NakedPair => 10,
+            Technique::HiddenPair => 15,
+            Technique::NakedTriple => 20,
+            Technique::HiddenTriple => 25,
+            Technique::XWing => 30,
+            Technique::XYWing => 40,
+            Technique::Swordfish => 50,
+        }
+    }
+}
+
+impl Display for Technique {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Difficulty {
+    /// All difficulties, from easiest to hardest.
+    pub const ALL: &'static [Difficulty] = &[
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+        Difficulty::Diabolical,
+    ];
+
+    /// Returns the human-readable name of the difficulty.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+            Difficulty::Diabolical => "diabolical",
+        }
+    }
+
+    /// Returns the next harder difficulty, if there is one.
+    fn harder(&self) -> Option<Difficulty> {
+        Difficulty::ALL.get(*self as usize + 1).cloned()
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Difficulty::ALL.iter().find(|d| d.name().eq_ignore_ascii_case(s)) {
+            Some(&d) => Ok(d),
+            None => bail!(ErrorKind::Parse(format!("unknown difficulty `{}`", s))),
+        }
+    }
+}
+
+impl Rating {
+    /// Rates the given sudoku by solving it logically.
+    ///
+    /// The difficulty band is that of the hardest technique needed. If that technique is needed
+    /// several times, the puzzle is bumped up one band (but only a puzzle which needs guessing is
+    /// ever considered diabolical).
+    pub fn new(s: &Sudoku) -> Self {
+        let mut deductions = s.deductions();
+        let mut score = 0;
+        let mut hardest = None;
+        let mut uses = 0;
+
+        for step in deductions.by_ref() {
+            score += step.technique.score();
+            if Some(step.technique) > hardest {
+                hardest = Some(step.technique);
+                uses = 0;
+            }
+            if Some(step.technique) == hardest {
+                uses += 1;
+            }
+        }
+        let solved = deductions.board().is_solved();
+
+        let difficulty = if !solved {
+            score += GUESS_SCORE;
+            Difficulty::Diabolical
+        } else {
+            let base = hardest.map_or(Difficulty::Easy, |t| t.difficulty());
+            if base == Difficulty::Easy || uses < BUMP_USES {
+                base
+            } else {
+                base.harder()
+                    .filter(|&d| d != Difficulty::Diabolical)
+                    .unwrap_or(base)
+            }
+        };
+
+        Rating {
+            difficulty,
+            score,
+            hardest,
+            solved,
+        }
+    }
+}
+
+impl Display for Rating {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} (score {})", self.difficulty, self.score)
+    }
+}
+
+impl Unit {
+    /// Returns a list of all the units of the given sudoku which contain every digit (27 for an
+    /// ordinary 9x9 sudoku).
+    pub fn all(s: &Sudoku) -> Vec<Unit> {
+        let size = s.size();
+        let extra = s.units()[3 * size..]
+            .iter()
+            .enumerate()
+            .filter(|(_, cells)| cells.len() == size)
+            .map(|(i, _)| Unit::Extra(i));
+        (0..size)
+            .map(Unit::Row)
+            .chain((0..size).map(Unit::Column))
+            .chain((0..size).map(Unit::Box))
+            .chain(extra)
+            .collect()
+    }
+
+    /// Returns the positions of the cells in this unit of the given sudoku.
+    pub fn cells(&self, s: &Sudoku) -> Vec<(usize, usize)> {
+        let size = s.size();
+        match *self {
+            Unit::Row(row) => (0..size).map(|col| (row, col)).collect(),
+            Unit::Column(col) => (0..size).map(|row| (row, col)).collect(),
+            Unit::Box(b) => s.region_cells(b).to_vec(),
+            Unit::Extra(i) => s.units()[3 * size + i].clone(),
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        // Units are displayed 1-based, since this is what a player would expect
+        match *self {
+            Unit::Row(row) => write!(f, "row {}", row + 1),
+            Unit::Column(col) => write!(f, "column {}", col + 1),
+            Unit::Box(b) => write!(f, "box {}", b + 1),
+            Unit::Extra(i) => write!(f, "extra unit {}", i + 1),
+        }
+    }
+}
+
+impl Step {
+    /// Returns a new step with no placements or eliminations.
+    fn new(technique: Technique, unit: Option<Unit>) -> Self {
+        Step {
+            technique,
+            unit,
+            digits: Vec::new(),
+            cells: Vec::new(),
+            placements: Vec::new(),
+            eliminations: Vec::new(),
+        }
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.technique)?;
+        if !self.digits.is_empty() {
+            write!(
+                f,
+                " on {}",
+                self.digits.iter().map(|&n| digit_char(n)).join("/")
+            )?;
+        }
+        if let Some(unit) = self.unit {
+            write!(f, " in {}", unit)?;
+        }
+        if !self.placements.is_empty() {
+            write!(
+                f,
+                ": place {}",
+                self.placements
+                    .iter()
+                    .map(|&(row, col, n)| {
+                        format!("{} at r{}c{}", digit_char(n), row + 1, col + 1)
+                    })
+                    .join(", ")
+            )?;
+        }
+        if !self.eliminations.is_empty() {
+            write!(
+                f,
+                ": eliminate {}",
+                self.eliminations
+                    .iter()
+                    .map(|&(row, col, n)| {
+                        format!("{} from r{}c{}", digit_char(n), row + 1, col + 1)
+                    })
+                    .join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Deductions {
+    /// Returns the deductions for the given sudoku, starting from its full candidate grid.
+    pub fn new(s: &Sudoku) -> Self {
+        let size = s.size();
+        let candidates = (0..size)
+            .map(|i| (0..size).map(|j| s.hints_at(i, j)).collect())
+            .collect();
+        Deductions {
+            board: s.clone(),
+            candidates,
+        }
+    }
+
+    /// Applies the given step, placing and eliminating candidates as appropriate.
+    pub fn apply(&mut self, step: &Step) {
+        for &(row, col, n) in &step.placements {
+            self.board.put_at(n, row, col);
+            self.candidates[row][col] = 0;
+            for (i, j) in self.board.peers(row, col) {
+                self.candidates[i][j] &= !(1 << n);
+            }
+        }
+        // Some constraints rule out numbers in ways that depend on the entries (like the neighbours
+        // of a placement in non-consecutive sudoku), so the candidates are brought up to date
+        if !step.placements.is_empty() && !self.board.constraints().is_empty() {
+            let size = self.size();
+            for (i, j) in iproduct!(0..size, 0..size) {
+                self.candidates[i][j] &= self.board.hints_at(i, j);
+            }
+        }
+        for &(row, col, n) in &step.eliminations {
+            self.candidates[row][col] &= !(1 << n);
+        }
+    }
+
+    /// Returns the board with all deductions made so far.
+    pub fn board(&self) -> &Sudoku {
+        &self.board
+    }
+
+    /// Returns the remaining candidates for each cell.
+    pub fn candidates(&self) -> Vec<Vec<Annotations>> {
+        let size = self.board.size();
+        self.candidates
+            .iter()
+            .map(|row| row.iter().map(|&c| Annotations::from_bits(c, size)).collect())
+            .collect()
+    }
+
+    /// Finds the easiest deduction which can be made from the current state, without applying it.
+    pub fn next_step(&self) -> Option<Step> {
+        for &t in Technique::ALL {
+            let step = match t {
+                Technique::NakedSingle => self.naked_single(),
+                Technique::HiddenSingle => self.hidden_single(),
+                Technique::PointingPair => self.pointing_pair(),
+                Technique::BoxLineReduction => self.box_line_reduction(),
+                Technique::CageCombination => self.cage_combination(),
+                Technique::NakedPair => self.naked_subset(t, 2),
+                Technique::HiddenPair => self.hidden_subset(t, 2),
+                Technique::NakedTriple => self.naked_subset(t, 3),
+                Technique::HiddenTriple => self.hidden_subset(t, 3),
+                Technique::XWing => self.fish(t, 2),
+                Technique::XYWing => self.xy_wing(),
+                Technique::Swordfish => self.fish(t, 3),
+            };
+            if step.is_some() {
+                return step;
+            }
+        }
+        None
+    }
+
+    /// Rules out every candidate at position `(row, col)` which isn't in `allowed`, as if it had
+    /// been eliminated by an earlier step. This is how deductions can start from a player's own
+    /// annotations; of course, later deductions are only sound if no right number is ruled out.
+    pub fn restrict(&mut self, row: usize, col: usize, allowed: Annotations) {
+        self.candidates[row][col] &= allowed.bits();
+    }
+
+    /// Returns the empty cells in the given unit which still have `n` as a candidate.
+    fn positions(&self, unit: Unit, n: u8) -> Vec<(usize, usize)> {
+        unit.cells(&self.board)
+            .into_iter()
+            .filter(|&(i, j)| self.board[i][j] == 0 && self.has(i, j, n))
+            .collect()
+    }
+
+    /// Returns the size of the sudoku.
+    fn size(&self) -> usize {
+        self.board.size()
+    }
+
+    /// Returns the numbers which can be entered in the sudoku.
+    fn digits(&self) -> ::std::ops::RangeInclusive<u8> {
+        1..=self.size() as u8
+    }
+
+    /// Returns whether the two positions must hold different numbers.
+    fn sees(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        self.board.sees(a, b)
+    }
+
+    /// Returns whether `n` is still a candidate at the given position.
+    fn has(&self, row: usize, col: usize, n: u8) -> bool {
+        self.candidates[row][col] & 1 << n != 0
+    }
+
+    fn naked_single(&self) -> Option<Step> {
+        for (i, j) in iproduct!(0..self.size(), 0..self.size()) {
+            if self.board[i][j] == 0 && self.candidates[i][j].count_ones() == 1 {
+                let n = self.candidates[i][j].trailing_zeros() as u8;
+                let mut step = Step::new(Technique::NakedSingle, None);
+                step.digits.push(n);
+                step.cells.push((i, j));
+                step.placements.push((i, j, n));
+                return Some(step);
+            }
+        }
+        None
+    }
+
+    fn hidden_single(&self) -> Option<Step> {
+        for unit in Unit::all(&self.board) {
+            for n in self.digits() {
+                let pos = self.positions(unit, n);
+                if pos.len() == 1 {
+                    let (i, j) = pos[0];
+                    let mut step = Step::new(Technique::HiddenSingle, Some(unit));
+                    step.digits.push(n);
+                    step.cells.push((i, j));
+                    step.placements.push((i, j, n));
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    fn pointing_pair(&self) -> Option<Step> {
+        for b in 0..self.size() {
+            let unit = Unit::Box(b);
+            for n in self.digits() {
+                let pos = self.positions(unit, n);
+                if pos.len() < 2 {
+                    continue;
+                }
+                // The line which contains all the candidates, if there is one
+                let line = if pos.iter().all(|&(i, _)| i == pos[0].0) {
+                    Unit::Row(pos[0].0)
+                } else if pos.iter().all(|&(_, j)| j == pos[0].1) {
+                    Unit::Column(pos[0].1)
+                } else {
+                    continue;
+                };
+
+                let elims = self.positions(line, n)
+                    .into_iter()
+                    .filter(|&(i, j)| self.board.region_index(i, j) != b)
+                    .map(|(i, j)| (i, j, n))
+                    .collect::<Vec<_>>();
+                if !elims.is_empty() {
+                    let mut step = Step::new(Technique::PointingPair, Some(unit));
+                    step.digits.push(n);
+                    step.cells = pos;
+                    step.eliminations = elims;
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    fn box_line_reduction(&self) -> Option<Step> {
+        let lines = (0..self.size())
+            .map(Unit::Row)
+            .chain((0..self.size()).map(Unit::Column));
+        for unit in lines {
+            for n in self.digits() {
+                let pos = self.positions(unit, n);
+                if pos.len() < 2 {
+                    continue;
+                }
+                let b = self.board.region_index(pos[0].0, pos[0].1);
+                if pos.iter().any(|&(i, j)| self.board.region_index(i, j) != b) {
+                    continue;
+                }
+
+                let elims = self.positions(Unit::Box(b), n)
+                    .into_iter()
+                    .filter(|p| !pos.contains(p))
+                    .map(|(i, j)| (i, j, n))
+                    .collect::<Vec<_>>();
+                if !elims.is_empty() {
+                    let mut step = Step::new(Technique::BoxLineReduction, Some(unit));
+                    step.digits.push(n);
+                    step.cells = pos;
+                    step.eliminations = elims;
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    fn cage_combination(&self) -> Option<Step> {
+        for cage in self.board.cages() {
+            let (full, empty): (Vec<_>, Vec<_>) = cage.cells()
+                .iter()
+                .cloned()
+                .partition(|&(i, j)| self.board[i][j] != 0);
+            let used = full.iter().fold(0, |used, &(i, j)| used | 1 << self.board[i][j]);
+            let possible = empty
+                .iter()
+                .fold(0, |possible, &(i, j)| possible | self.candidates[i][j]);
+            let allowed = cage.allowed(used, possible);
+
+            let elims = empty
+                .iter()
+                .flat_map(|&(i, j)| {
+                    numbers(self.candidates[i][j] & !allowed)
+                        .into_iter()
+                        .map(move |n| (i, j, n))
+                })
+                .collect::<Vec<_>>();
+            if !elims.is_empty() {
+                let mut step = Step::new(Technique::CageCombination, None);
+                step.digits = numbers(allowed);
+                step.cells = cage.cells().to_vec();
+                step.eliminations = elims;
+                return Some(step);
+            }
+        }
+        None
+    }
+
+    /// Looks for `size` cells in a unit whose candidates are drawn from only `size` digits.
+    fn naked_subset(&self, technique: Technique, size: usize) -> Option<Step> {
+        for unit in Unit::all(&self.board) {
+            let empty = unit.cells(&self.board)
+                .into_iter()
+                .filter(|&(i, j)| self.board[i][j] == 0)
+                .collect::<Vec<_>>();
+            // Cells with too many candidates can never be part of the subset
+            let cells = empty
+                .iter()
+                .cloned()
+                .filter(|&(i, j)| {
+                    let cnt = self.candidates[i][j].count_ones() as usize;
+                    2 <= cnt && cnt <= size
+                })
+                .collect::<Vec<_>>();
+
+            for subset in cells.into_iter().combinations(size) {
+                let digits = subset
+                    .iter()
+                    .flat_map(|&(i, j)| numbers(self.candidates[i][j]))
+                    .sorted()
+                    .into_iter()
+                    .dedup()
+                    .collect::<Vec<_>>();
+                if digits.len() != size {
+                    continue;
+                }
+
+                let elims = empty
+                    .iter()
+                    .filter(|p| !subset.contains(p))
+                    .flat_map(|&(i, j)| {
+                        digits
+                            .iter()
+                            .filter(move |&&n| self.has(i, j, n))
+                            .map(move |&n| (i, j, n))
+                    })
+                    .collect::<Vec<_>>();
+                if !elims.is_empty() {
+                    let mut step = Step::new(technique, Some(unit));
+                    step.digits = digits;
+                    step.cells = subset;
+                    step.eliminations = elims;
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    /// Looks for `size` digits in a unit which are confined to only `size` cells.
+    fn hidden_subset(&self, technique: Technique, size: usize) -> Option<Step> {
+        for unit in Unit::all(&self.board) {
+            // Digits with too many positions can never be part of the subset
+            let digits = self.digits()
+                .map(|n| (n, self.positions(unit, n)))
+                .filter(|(_, pos)| 2 <= pos.len() && pos.len() <= size)
+                .collect::<Vec<_>>();
+
+            for subset in digits.into_iter().combinations(size) {
+                let cells = subset
+                    .iter()
+                    .flat_map(|(_, pos)| pos.iter().cloned())
+                    .sorted()
+                    .into_iter()
+                    .dedup()
+                    .collect::<Vec<_>>();
+                if cells.len() != size {
+                    continue;
+                }
+                let digits = subset.into_iter().map(|(n, _)| n).collect::<Vec<_>>();
+
+                let elims = cells
+                    .iter()
+                    .flat_map(|&(i, j)| {
+                        numbers(self.candidates[i][j])
+                            .into_iter()
+                            .filter(|n| !digits.contains(n))
+                            .map(move |n| (i, j, n))
+                    })
+                    .collect::<Vec<_>>();
+                if !elims.is_empty() {
+                    let mut step = Step::new(technique, Some(unit));
+                    step.digits = digits;
+                    step.cells = cells;
+                    step.eliminations = elims;
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    /// Looks for a basic fish of the given size (X-Wing or swordfish).
+    fn fish(&self, technique: Technique, size: usize) -> Option<Step> {
+        for n in self.digits() {
+            // We try rows as the base set first and then columns; `transpose` converts between the
+            // two so that the rest of the logic can be shared.
+            for &transpose in &[false, true] {
+                let pos = |base: usize, cover: usize| if transpose {
+                    (cover, base)
+                } else {
+                    (base, cover)
+                };
+                let line = |base: usize| if transpose {
+                    Unit::Column(base)
+                } else {
+                    Unit::Row(base)
+                };
+                let cover_of = |(i, j): (usize, usize)| if transpose { i } else { j };
+
+                let bases = (0..self.size())
+                    .map(|base| (base, self.positions(line(base), n)))
+                    .filter(|(_, p)| 2 <= p.len() && p.len() <= size)
+                    .collect::<Vec<_>>();
+
+                for subset in bases.into_iter().combinations(size) {
+                    let covers = subset
+                        .iter()
+                        .flat_map(|(_, p)| p.iter().map(|&c| cover_of(c)))
+                        .sorted()
+                        .into_iter()
+                        .dedup()
+                        .collect::<Vec<_>>();
+                    if covers.len() != size {
+                        continue;
+                    }
+                    let baselines = subset.iter().map(|&(base, _)| base).collect::<Vec<_>>();
+
+                    let elims = iproduct!(0..self.size(), covers.iter())
+                        .filter(|&(base, _)| !baselines.contains(&base))
+                        .map(|(base, &cover)| pos(base, cover))
+                        .filter(|&(i, j)| self.board[i][j] == 0 && self.has(i, j, n))
+                        .map(|(i, j)| (i, j, n))
+                        .collect::<Vec<_>>();
+                    if !elims.is_empty() {
+                        let mut step = Step::new(technique, None);
+                        step.digits.push(n);
+                        step.cells = subset.into_iter().flat_map(|(_, p)| p).collect();
+                        step.eliminations = elims;
+                        return Some(step);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn xy_wing(&self) -> Option<Step> {
+        let bivalue = iproduct!(0..self.size(), 0..self.size())
+            .filter(|&(i, j)| self.board[i][j] == 0 && self.candidates[i][j].count_ones() == 2)
+            .collect::<Vec<_>>();
+
+        for &pivot in &bivalue {
+            let pc = numbers(self.candidates[pivot.0][pivot.1]);
+            let (x, y) = (pc[0], pc[1]);
+            let wings = bivalue
+                .iter()
+                .cloned()
+                .filter(|&p| self.sees(pivot, p))
+                .collect::<Vec<_>>();
+
+            for (&a, &b) in iproduct!(wings.iter(), wings.iter()) {
+                let (ac, bc) = (self.candidates[a.0][a.1], self.candidates[b.0][b.1]);
+                // `a` must contain `x` but not `y`, and `b` must contain `y` but not `x`; they
+                // then share the remaining candidate `z`.
+                let (xbit, ybit) = (1 << x, 1 << y);
+                if ac & xbit == 0 || ac & ybit != 0 || bc & ybit == 0 || bc & xbit != 0 {
+                    continue;
+                }
+                let zbit = ac & !xbit;
+                if bc & zbit == 0 {
+                    continue;
+                }
+                let z = zbit.trailing_zeros() as u8;
+
+                let elims = iproduct!(0..self.size(), 0..self.size())
+                    .filter(|&p| p != a && p != b && self.sees(p, a) && self.sees(p, b))
+                    .filter(|&(i, j)| self.board[i][j] == 0 && self.has(i, j, z))
+                    .map(|(i, j)| (i, j, z))
+                    .collect::<Vec<_>>();
+                if !elims.is_empty() {
+                    let mut step = Step::new(Technique::XYWing, None);
+                    step.digits = vec![x, y, z];
+                    step.cells = vec![pivot, a, b];
+                    step.eliminations = elims;
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for Deductions {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let step = self.next_step()?;
+        self.apply(&step);
+        Some(step)
+    }
+}
+
+/// Returns the numbers whose bits are set in the given bitmask, in increasing order.
+fn numbers(mask: u32) -> Vec<u8> {
+    (1..32).filter(|&n| mask & 1 << n != 0).collect()
+}
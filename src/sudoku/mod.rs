@@ -0,0 +1,1356 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+pub mod constraint;
+mod dlx;
+mod jigsaw;
+mod killer;
+pub mod logic;
+
+use std::default::Default;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::iter::{IntoIterator, Iterator};
+use std::ops::{Index, IndexMut};
+use std::rc::Rc;
+use std::slice;
+use std::str::FromStr;
+
+use rand::{self, Rng};
+
+use errors::*;
+use self::constraint::Constraint;
+use self::dlx::Dlx;
+use self::jigsaw::parse_regions;
+use self::killer::parse_cage;
+use self::logic::{Deductions, Difficulty, Rating};
+
+/// The largest supported size of a sudoku (the number of cells in each row, column and box).
+pub const MAX_SIZE: usize = 25;
+
+/// The number of puzzles to try when generating a sudoku of a particular difficulty before giving
+/// up.
+const GENERATE_ATTEMPTS: usize = 100;
+/// The number of guesses the solver may make when checking whether a large puzzle being generated
+/// still has a unique solution. Proving uniqueness can take a very long time for some sparse
+/// grids above 9x9, so the generator just keeps any entry whose removal can't be checked quickly.
+const UNIQUENESS_BUDGET: usize = 2000;
+/// The number of guesses per cell which the search for a random filled grid may make before it
+/// starts again with a different order.
+const FILL_BUDGET: usize = 2;
+
+/// The annotations for a single cell in a `Sudoku`, stored as a bitmask of candidates in the same
+/// way as the solver's (see `from_bits`), so converting between the two is cheap.
+///
+/// For convenience, the `Index` and `IndexMut` traits are implemented for this type so that the
+/// annotation for a particular number can be accessed directly without recomputing the index. For
+/// example, `annotations[1]` will return whether the annotation `1` is set. Iterating over a
+/// reference to the annotations gives whether each number from 1 to the size of the board they
+/// belong to is set (so 9 numbers for the annotations made by `new` or `from_array`).
+#[derive(Clone, Copy)]
+pub struct Annotations {
+    /// The bitmask of annotations, in which bit `n` is set if the annotation `n` is set. This is
+    /// out of date while `dirty` is set.
+    bits: u32,
+    /// Whether each annotation is set, indexed by number (so the first element is unused). This is
+    /// always up to date, since it is what `IndexMut` hands out.
+    view: [bool; MAX_SIZE + 1],
+    /// Whether `view` may have been changed through `IndexMut` since `bits` was last updated.
+    dirty: bool,
+    /// The size of the board the annotations belong to, which is the largest annotation.
+    size: u8,
+}
+
+/// The dimensions of the boxes in a `Sudoku`, which also determine the size of the whole grid.
+///
+/// A box with `r` rows and `c` columns contains `r * c` cells, which is also the number of cells
+/// in each row and column of the grid and the largest number which can be entered. The standard
+/// 9x9 sudoku has 3x3 boxes (the default), and a 6x6 sudoku usually has boxes with 2 rows and 3
+/// columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Shape {
+    box_rows: usize,
+    box_cols: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sudoku {
+    shape: Shape,
+    /// The rules of the sudoku, which are shared between copies since they never change.
+    layout: Rc<Layout>,
+    /// The entries of the grid, row by row.
+    grid: Vec<u8>,
+    /// The numbers used in each unit (in the same order as `layout.units`).
+    units: Vec<Occupancy>,
+    /// The cages of a killer sudoku (empty for an ordinary sudoku).
+    cages: Vec<Cage>,
+    /// The index of the cage containing each cell, row by row (or empty if there are no cages).
+    cell_cages: Vec<Option<usize>>,
+    /// The numbers used in each cage.
+    cage_occupancy: Vec<Occupancy>,
+    /// The total number of duplicate entries in all units and cages.
+    duplicates: u32,
+}
+
+/// The rules of a `Sudoku` which don't depend on its entries.
+#[derive(Debug)]
+struct Layout {
+    /// The index of the region containing each cell, row by row, if the sudoku is a jigsaw sudoku
+    /// (otherwise, the regions are the boxes).
+    regions: Option<Vec<usize>>,
+    /// The constraints of the sudoku's variant, besides the basic rules.
+    constraints: Vec<Rc<dyn Constraint>>,
+    /// The groups of cells which must all hold different numbers: the rows, columns and boxes (or
+    /// regions), followed by any units added by the constraints.
+    units: Vec<Vec<(usize, usize)>>,
+    /// The indices of the units containing each cell, row by row.
+    cell_units: Vec<Vec<usize>>,
+    /// The positions (as indices into the grid) which must hold different numbers to each cell,
+    /// apart from those sharing a unit with it.
+    cell_peers: Vec<Vec<usize>>,
+}
+
+/// A cage in a killer sudoku: a group of cells whose entries must add up to a target sum, with no
+/// number repeated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cage {
+    sum: u32,
+    cells: Vec<(usize, usize)>,
+    /// Every set of different numbers which fills the cage and adds up to its sum, as bitmasks.
+    combinations: Vec<u32>,
+}
+
+/// A solver backend, used to find the solutions of a sudoku.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// A backtracking search which always fills in the cell with the fewest possibilities.
+    #[default]
+    Backtrack,
+    /// Knuth's Algorithm X using Dancing Links, treating the sudoku as an exact cover problem.
+    Dlx,
+}
+
+/// An iterator over all solutions of a particular sudoku.
+pub struct Solutions(Search);
+
+/// The search behind a `Solutions` iterator, which depends on the engine in use.
+enum Search {
+    Backtrack(Backtrack),
+    Dlx(Box<Dlx>),
+}
+
+/// The state of a backtracking search.
+struct Backtrack {
+    /// The board being searched, which is modified in place as guesses are made and undone.
+    board: Sudoku,
+    /// The stack of guesses currently in use for backtracking.
+    stack: Vec<Guess>,
+    /// Whether `board` currently holds a solution which has already been found.
+    found: bool,
+    /// Whether the search is finished.
+    done: bool,
+}
+
+/// The numbers used in a single row, column or box of a `Sudoku`.
+///
+/// The counts are needed so that an invalid board (with duplicate entries) can still be updated
+/// correctly; the mask is what makes checking whether a number can go in a cell fast.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Occupancy {
+    /// A bitmask in which bit `n` is set if `n` is used at least once.
+    mask: u32,
+    /// The number of times each number is used (index 0 is unused).
+    counts: [u8; MAX_SIZE + 1],
+}
+
+/// A guess made while searching for solutions.
+struct Guess {
+    row: usize,
+    col: usize,
+    /// A bitmask of the candidates which have not yet been tried.
+    remaining: u32,
+}
+
+impl Annotations {
+    /// Creates a set of empty annotations, for the numbers 1-9.
+    pub fn new() -> Annotations {
+        Annotations::with_size(9)
+    }
+
+    /// Creates a set of empty annotations for a board of the given size.
+    ///
+    /// # Panics
+    /// Will panic if `size` is larger than `MAX_SIZE`.
+    pub fn with_size(size: usize) -> Annotations {
+        assert!(size <= MAX_SIZE, "too many annotations ({})", size);
+        Annotations {
+            bits: 0,
+            view: [false; MAX_SIZE + 1],
+            dirty: false,
+            size: size as u8,
+        }
+    }
+
+    /// Creates the annotations corresponding to the given `bool` array, for the numbers 1-9.
+    pub fn from_array(arr: [bool; 9]) -> Annotations {
+        Annotations::from_slice(&arr)
+    }
+
+    /// Creates the annotations corresponding to the given `bool` slice, whose first element is
+    /// for the number 1, for a board whose size is the length of the slice.
+    ///
+    /// # Panics
+    /// Will panic if the slice is longer than `MAX_SIZE`.
+    pub fn from_slice(arr: &[bool]) -> Annotations {
+        let mut a = Annotations::with_size(arr.len());
+        for (i, &b) in arr.iter().enumerate() {
+            a.set(i as u8 + 1, b);
+        }
+        a
+    }
+
+    /// Creates the annotations for a board of the given size corresponding to the given bitmask,
+    /// in which bit `n` is set if the annotation `n` is set. Bits which don't stand for a number
+    /// from 1 to `size` are ignored.
+    ///
+    /// # Panics
+    /// Will panic if `size` is larger than `MAX_SIZE`.
+    pub fn from_bits(bits: u32, size: usize) -> Annotations {
+        let mut a = Annotations::with_size(size);
+        a.bits = bits & ((1 << (size + 1)) - 2);
+        for n in 1..=size {
+            a.view[n] = a.bits & (1 << n) != 0;
+        }
+        a
+    }
+
+    /// Returns the bitmask corresponding to these annotations (see `from_bits`).
+    pub fn bits(&self) -> u32 {
+        if !self.dirty {
+            return self.bits;
+        }
+        (1..=self.size as usize)
+            .filter(|&n| self.view[n])
+            .fold(0, |bits, n| bits | 1 << n)
+    }
+
+    /// Returns the size of the board these annotations belong to.
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Clears all annotations.
+    pub fn clear(&mut self) {
+        *self = Annotations::with_size(self.size());
+    }
+
+    /// Returns the number of annotations set.
+    pub fn count(&self) -> i32 {
+        self.bits().count_ones() as i32
+    }
+
+    /// Returns a `Vec` containing all the annotation numbers which are set.
+    pub fn list(&self) -> Vec<u8> {
+        (1..=self.size).filter(|&n| self[n]).collect()
+    }
+
+    /// Returns the lowest annotation which is set.
+    pub fn lowest(&self) -> Option<u8> {
+        match self.bits() {
+            0 => None,
+            bits => Some(bits.trailing_zeros() as u8),
+        }
+    }
+
+    /// Sets or unsets the given annotation.
+    pub fn set(&mut self, n: u8, value: bool) {
+        let bit = self.bit(n);
+        self.bits = self.bits();
+        self.dirty = false;
+        if value {
+            self.bits |= bit;
+        } else {
+            self.bits &= !bit;
+        }
+        self.view[n as usize] = value;
+    }
+
+    /// Toggles the given annotation.
+    pub fn toggle(&mut self, n: u8) {
+        let value = !self[n];
+        self.set(n, value);
+    }
+
+    /// Returns the bit for the given annotation.
+    ///
+    /// # Panics
+    /// Will panic if `n` is not between 1 and the size of the board, inclusive.
+    fn bit(&self, n: u8) -> u32 {
+        assert!(
+            n >= 1 && n <= self.size,
+            "annotation number `{}` is invalid",
+            n
+        );
+        1 << n
+    }
+}
+
+impl Debug for Annotations {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for n in self.list() {
+            write!(f, "{}", digit_char(n))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Annotations::new()
+    }
+}
+
+impl PartialEq for Annotations {
+    fn eq(&self, other: &Annotations) -> bool {
+        self.size == other.size && self.bits() == other.bits()
+    }
+}
+
+impl Eq for Annotations {}
+
+impl Index<u8> for Annotations {
+    type Output = bool;
+
+    fn index(&self, index: u8) -> &Self::Output {
+        self.bit(index);
+        &self.view[index as usize]
+    }
+}
+
+impl IndexMut<u8> for Annotations {
+    fn index_mut(&mut self, index: u8) -> &mut Self::Output {
+        self.bit(index);
+        self.dirty = true;
+        &mut self.view[index as usize]
+    }
+}
+
+impl<'a> IntoIterator for &'a Annotations {
+    type Item = &'a bool;
+    type IntoIter = slice::Iter<'a, bool>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.view[1..=self.size()].iter()
+    }
+}
+
+impl Shape {
+    /// Returns the shape with boxes of the given dimensions. An error is returned if either
+    /// dimension is zero or the resulting grid would be larger than `MAX_SIZE`.
+    pub fn new(box_rows: usize, box_cols: usize) -> Result<Self> {
+        if box_rows == 0 || box_cols == 0 || box_rows * box_cols > MAX_SIZE {
+            bail!(ErrorKind::InvalidShape(box_rows, box_cols));
+        }
+        Ok(Shape { box_rows, box_cols })
+    }
+
+    /// Returns the usual shape for a grid of the given size, with boxes as close to square as
+    /// possible (and never taller than they are wide). Sizes which would only allow boxes with a
+    /// single row (such as prime numbers) are rejected.
+    pub fn for_size(size: usize) -> Result<Self> {
+        let box_rows = (2..size)
+            .take_while(|&r| r * r <= size)
+            .filter(|&r| size.is_multiple_of(r))
+            .last();
+        match box_rows {
+            Some(r) => Shape::new(r, size / r),
+            None => bail!(ErrorKind::InvalidShape(1, size)),
+        }
+    }
+
+    /// Returns the number of rows in each box.
+    pub fn box_rows(&self) -> usize {
+        self.box_rows
+    }
+
+    /// Returns the number of columns in each box.
+    pub fn box_cols(&self) -> usize {
+        self.box_cols
+    }
+
+    /// Returns the size of the grid, which is the number of cells in each row, column and box.
+    pub fn size(&self) -> usize {
+        self.box_rows * self.box_cols
+    }
+
+    /// Returns the index of the box containing the given position. Boxes are numbered from left
+    /// to right and top to bottom.
+    pub fn box_index(&self, row: usize, col: usize) -> usize {
+        // There are `box_rows` boxes across each band of the grid
+        row / self.box_rows * self.box_rows + col / self.box_cols
+    }
+
+    /// Returns the position of the top left cell of the given box.
+    pub fn box_origin(&self, b: usize) -> (usize, usize) {
+        (
+            b / self.box_rows * self.box_rows,
+            b % self.box_rows * self.box_cols,
+        )
+    }
+
+    /// Returns the positions of the cells in the given box, row by row.
+    pub fn box_cells(&self, b: usize) -> Vec<(usize, usize)> {
+        let (row, col) = self.box_origin(b);
+        iproduct!(row..row + self.box_rows, col..col + self.box_cols).collect()
+    }
+
+    /// Returns the shape with the dimensions of the boxes swapped.
+    pub fn transpose(&self) -> Shape {
+        Shape {
+            box_rows: self.box_cols,
+            box_cols: self.box_rows,
+        }
+    }
+
+    /// Returns a bitmask with the bits for each of the numbers which can be entered set.
+    fn all_numbers(&self) -> u32 {
+        (1 << (self.size() + 1)) - 2
+    }
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Shape {
+            box_rows: 3,
+            box_cols: 3,
+        }
+    }
+}
+
+impl Display for Shape {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}x{}", self.box_rows, self.box_cols)
+    }
+}
+
+impl FromStr for Shape {
+    type Err = Error;
+
+    /// Parses box dimensions of the form `RxC` (for example, `2x3`).
+    fn from_str(s: &str) -> Result<Self> {
+        let dims = s.split('x')
+            .map(|d| d.trim().parse::<usize>())
+            .collect::<::std::result::Result<Vec<_>, _>>();
+        match dims {
+            Ok(ref dims) if dims.len() == 2 => Shape::new(dims[0], dims[1]),
+            _ => bail!(ErrorKind::Parse(format!("invalid box shape `{}`", s))),
+        }
+    }
+}
+
+impl Layout {
+    /// Works out the layout of a sudoku with the given shape, regions (if it is a jigsaw sudoku)
+    /// and constraints.
+    fn new(
+        shape: Shape,
+        regions: Option<Vec<usize>>,
+        constraints: Vec<Rc<dyn Constraint>>,
+    ) -> Self {
+        let size = shape.size();
+        let mut units = Vec::new();
+        units.extend((0..size).map(|r| (0..size).map(|c| (r, c)).collect()));
+        units.extend((0..size).map(|c| (0..size).map(|r| (r, c)).collect()));
+        match regions {
+            Some(ref regions) => units.extend((0..size).map(|r| {
+                iproduct!(0..size, 0..size)
+                    .filter(|&(i, j)| regions[i * size + j] == r)
+                    .collect()
+            })),
+            None => units.extend((0..size).map(|b| shape.box_cells(b))),
+        }
+        for constraint in &constraints {
+            units.extend(constraint.units(shape));
+        }
+
+        let mut cell_units = vec![Vec::new(); size * size];
+        for (u, unit) in units.iter().enumerate() {
+            for &(row, col) in unit {
+                cell_units[row * size + col].push(u);
+            }
+        }
+        let mut cell_peers = vec![Vec::<usize>::new(); size * size];
+        for (row, col) in iproduct!(0..size, 0..size) {
+            let cell = row * size + col;
+            for constraint in &constraints {
+                for (i, j) in constraint.peers(shape, row, col) {
+                    let peer = i * size + j;
+                    let shares_unit = cell_units[cell].iter().any(|u| cell_units[peer].contains(u));
+                    if !shares_unit && !cell_peers[cell].contains(&peer) {
+                        cell_peers[cell].push(peer);
+                    }
+                }
+            }
+        }
+
+        Layout {
+            regions,
+            constraints,
+            units,
+            cell_units,
+            cell_peers,
+        }
+    }
+}
+
+impl PartialEq for Layout {
+    /// Layouts are the same if they have the same regions and constraints (the units and peers
+    /// follow from these).
+    fn eq(&self, other: &Layout) -> bool {
+        self.regions == other.regions && self.constraints.len() == other.constraints.len() &&
+            self.constraints
+                .iter()
+                .zip(&other.constraints)
+                .all(|(a, b)| a.name() == b.name())
+    }
+}
+
+impl Eq for Layout {}
+
+impl Sudoku {
+    /// Creates an empty `Sudoku` with the given shape.
+    pub fn new(shape: Shape) -> Self {
+        let size = shape.size();
+        let layout = Layout::new(shape, None, Vec::new());
+        Sudoku {
+            shape,
+            grid: vec![0; size * size],
+            units: vec![Occupancy::default(); layout.units.len()],
+            layout: Rc::new(layout),
+            cages: Vec::new(),
+            cell_cages: Vec::new(),
+            cage_occupancy: Vec::new(),
+            duplicates: 0,
+        }
+    }
+
+    /// Creates a new `Sudoku` from the given 9x9 grid. Any entries besides 0-9 are invalid, with 0
+    /// representing an empty cell.
+    pub fn from_grid(grid: [[u8; 9]; 9]) -> Result<Self> {
+        Sudoku::from_cells(Shape::default(), &grid.concat())
+    }
+
+    /// Creates a new `Sudoku` with the given shape from its entries, listed row by row. There must
+    /// be exactly one entry for each cell, with 0 representing an empty cell.
+    pub fn from_cells(shape: Shape, cells: &[u8]) -> Result<Self> {
+        let mut s = Sudoku::new(shape);
+        if cells.len() != s.grid.len() {
+            bail!(ErrorKind::InvalidSudoku);
+        }
+
+        let size = s.size();
+        for (i, &n) in cells.iter().enumerate() {
+            match n as usize {
+                0 => {}
+                n if n <= size => s.put_at(n as u8, i / size, i % size),
+                _ => bail!(ErrorKind::InvalidSudoku),
+            }
+        }
+        if s.duplicates != 0 {
+            bail!(ErrorKind::InvalidSudoku);
+        }
+
+        Ok(s)
+    }
+
+    /// Parses a `Sudoku` with the given shape; see the `FromStr` implementation for the format.
+    /// This is only needed for grids whose boxes are not the usual shape for their size (see
+    /// `Shape::for_size`), such as a 6x6 grid with boxes of 3 rows and 2 columns.
+    pub fn parse_with_shape(s: &str, shape: Shape) -> Result<Self> {
+        let (rules, grid): (Vec<&str>, Vec<&str>) = s.lines().partition(|l| l.contains(':'));
+        let chars = grid_chars(&grid);
+        let size = shape.size();
+        if chars.len() != size * size {
+            bail!(ErrorKind::Parse(format!(
+                "expected {} cells for a {}x{} sudoku, but found {}",
+                size * size,
+                size,
+                size,
+                chars.len()
+            )));
+        }
+
+        let mut cells = Vec::with_capacity(chars.len());
+        for (i, &c) in chars.iter().enumerate() {
+            let n = match c {
+                '0' | '.' | '_' => 0,
+                c => match parse_digit(c) {
+                    Some(n) if n as usize <= size => n,
+                    _ => bail!(ErrorKind::Parse(format!(
+                        "unexpected character `{}` at position ({}, {}) in sudoku",
+                        c,
+                        i / size,
+                        i % size
+                    ))),
+                },
+            };
+            cells.push(n);
+        }
+
+        let mut sudoku = Sudoku::from_cells(shape, &cells)?;
+        for rule in rules {
+            let mut parts = rule.splitn(2, ':');
+            let key = parts.next().unwrap().trim();
+            if key == "regions" {
+                sudoku.set_regions(parse_regions(parts.next().unwrap(), size)?)?;
+            } else if key == "variant" {
+                sudoku.add_constraint(constraint::variant(parts.next().unwrap().trim())?)?;
+            } else if key.parse::<u32>().is_ok() {
+                let (sum, cells) = parse_cage(rule)?;
+                sudoku.add_cage(sum, cells)?;
+            } else {
+                bail!(ErrorKind::Parse(format!("unexpected line `{}` in sudoku", rule)));
+            }
+        }
+        // The givens might repeat a number within a cage
+        if sudoku.duplicates != 0 {
+            bail!(ErrorKind::InvalidSudoku);
+        }
+
+        Ok(sudoku)
+    }
+
+    /// Returns the shape of the sudoku.
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    /// Returns the size of the sudoku (the number of cells in each row, column and box).
+    pub fn size(&self) -> usize {
+        self.shape.size()
+    }
+
+    /// Returns an iterator over the logical deductions (using human-style solving techniques)
+    /// which can be made for this sudoku, in the order in which they are made.
+    pub fn deductions(&self) -> Deductions {
+        Deductions::new(self)
+    }
+
+    /// Generates a `Sudoku` with a random grid. The generated `Sudoku` is guaranteed to have
+    /// exactly one solution.
+    pub fn generate() -> Self {
+        Sudoku::generate_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Generates a `Sudoku` with a random grid, using the given random number generator. The
+    /// generated `Sudoku` is guaranteed to have exactly one solution.
+    ///
+    /// Using a seeded generator (see `util::seeded_rng`) makes the result reproducible.
+    pub fn generate_with_rng<R: Rng>(rng: &mut R) -> Self {
+        Sudoku::new(Shape::default()).generate_up_to(false, None, rng)
+    }
+
+    /// Generates a `Sudoku` with a random grid of the given difficulty. The generated `Sudoku` is
+    /// guaranteed to have exactly one solution.
+    ///
+    /// Since the difficulty of a random puzzle cannot be controlled directly, several puzzles may
+    /// be generated before one of the right difficulty is found. If none is found within a fixed
+    /// number of attempts, an error is returned.
+    pub fn generate_with_difficulty(difficulty: Difficulty) -> Result<Self> {
+        Sudoku::generate_with_difficulty_and_rng(difficulty, &mut rand::thread_rng())
+    }
+
+    /// Generates a `Sudoku` with a random grid of the given difficulty, using the given random
+    /// number generator. See `generate_with_difficulty` for details.
+    pub fn generate_with_difficulty_and_rng<R: Rng>(
+        difficulty: Difficulty,
+        rng: &mut R,
+    ) -> Result<Self> {
+        Sudoku::new(Shape::default()).generate_like(Some(difficulty), rng)
+    }
+
+    /// Generates a random, uniquely solvable `Sudoku` with the same shape as this one (whose
+    /// entries are ignored), optionally of the given difficulty. See `generate_with_difficulty`
+    /// for details on how the difficulty is met.
+    ///
+    /// If this sudoku has cages, the generated sudoku is a killer sudoku with new cages (see
+    /// `generate_killer_like`), and if it is a jigsaw sudoku, the generated sudoku is a jigsaw
+    /// sudoku with new regions (see `generate_jigsaw_like`).
+    pub fn generate_like<R: Rng>(
+        &self,
+        difficulty: Option<Difficulty>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        if self.is_jigsaw() {
+            return self.generate_jigsaw_like(difficulty, rng);
+        }
+        self.generate_puzzle(!self.cages.is_empty(), difficulty, rng)
+    }
+
+    /// Counts the solutions of the `Sudoku`, stopping once `limit` solutions have been found (so
+    /// the result is never greater than `limit`). This is much faster than counting the results of
+    /// `solutions`, since the solutions themselves are never copied.
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        // Backtracking is quickest for small grids, but it has no way of noticing that a number
+        // has only one place left in a unit, which makes it far too slow for large ones. The same
+        // goes for variants which only add units, like Sudoku X.
+        let layout = &self.layout;
+        let only_units = !layout.constraints.is_empty() &&
+            layout.cell_peers.iter().all(|peers| peers.is_empty()) &&
+            layout.constraints.iter().all(|c| !c.uses_excluded());
+        if self.size() > 9 || only_units {
+            let mut search = Dlx::new(self);
+            let mut count = 0;
+            while count < limit && search.find_next() {
+                count += 1;
+            }
+            return count;
+        }
+        let mut search = Backtrack::new(self);
+        let mut count = 0;
+        while count < limit && search.find_next() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Returns whether the `Sudoku` has a solution.
+    pub fn has_solution(&self) -> bool {
+        self.count_solutions(1) == 1
+    }
+
+    /// Returns whether the `Sudoku` has a unique solution.
+    pub fn has_unique_solution(&self) -> bool {
+        self.count_solutions(2) == 1
+    }
+
+    /// Rates the difficulty of this sudoku, based on the techniques needed to solve it logically.
+    pub fn rate(&self) -> Rating {
+        Rating::new(self)
+    }
+
+    /// Returns the numbers which can be put at position `(row, col)` without breaking any rules,
+    /// which are the annotations a player would make there. A filled position has no candidates.
+    pub fn candidates_at(&self, row: usize, col: usize) -> Annotations {
+        Annotations::from_bits(self.hints_at(row, col), self.size())
+    }
+
+    /// Returns the candidates of every position (see `candidates_at`), row by row.
+    pub fn candidates(&self) -> Vec<Vec<Annotations>> {
+        let size = self.size();
+        (0..size)
+            .map(|i| (0..size).map(|j| self.candidates_at(i, j)).collect())
+            .collect()
+    }
+
+    /// Returns whether the entry at position `(row, col)` breaks the rules of the sudoku: another
+    /// position which sees it (see `sees`) holds the same number, a constraint rules it out, or
+    /// its cage can no longer add up to the right sum. An empty position never breaks the rules.
+    pub fn is_conflict_at(&self, row: usize, col: usize) -> bool {
+        let n = self[row][col];
+        n != 0 &&
+            (self.peers(row, col).into_iter().any(|(i, j)| self[i][j] == n) ||
+                 self.layout
+                     .constraints
+                     .iter()
+                     .any(|c| c.excluded(self, row, col) & 1 << n != 0) ||
+                 self.cage_index(row, col).is_some_and(|c| {
+                     !self.cages[c].can_contain(self.cage_occupancy[c].mask)
+                 }))
+    }
+
+    /// Returns whether the sudoku is solved.
+    pub fn is_solved(&self) -> bool {
+        // A full grid which doesn't break any rules must be solved (in particular, a full cage
+        // which could contain its numbers must have the right sum)
+        self.grid.iter().all(|&n| n != 0) && !self.is_broken()
+    }
+
+    /// Puts `n` at position `(row, col)`.
+    ///
+    /// # Panics
+    /// Will panic if `n` is not between 1 and the size of the sudoku, inclusive.
+    pub fn put_at(&mut self, n: u8, row: usize, col: usize) {
+        assert!(
+            n >= 1 && n as usize <= self.size(),
+            "entry number `{}` is invalid",
+            n
+        );
+
+        // We need to remove the number in this space first, otherwise replacing a number
+        // won't produce the correct results (the occupancy counts won't be updated correctly).
+        self.remove_at(row, col);
+        let size = self.size();
+        self.grid[row * size + col] = n;
+
+        for &u in &self.layout.cell_units[row * size + col] {
+            if self.units[u].add(n) {
+                self.duplicates += 1;
+            }
+        }
+        if let Some(c) = self.cage_index(row, col) {
+            if self.cage_occupancy[c].add(n) {
+                self.duplicates += 1;
+            }
+        }
+    }
+
+    /// Removes the entry at position `(row, col)`, returning the entry that was removed.
+    pub fn remove_at(&mut self, row: usize, col: usize) -> u8 {
+        let size = self.size();
+        let last = self.grid[row * size + col];
+        self.grid[row * size + col] = 0;
+        if last == 0 {
+            return 0;
+        }
+
+        for &u in &self.layout.cell_units[row * size + col] {
+            if self.units[u].remove(last) {
+                self.duplicates -= 1;
+            }
+        }
+        if let Some(c) = self.cage_index(row, col) {
+            if self.cage_occupancy[c].remove(last) {
+                self.duplicates -= 1;
+            }
+        }
+
+        last
+    }
+
+    /// Adds a constraint to the sudoku, so that it follows the rules of a variant (see the
+    /// `constraint` module). Adding a constraint with the same name as one already added has no
+    /// effect.
+    ///
+    /// An error is returned if the entries already on the board break the new constraint.
+    pub fn add_constraint(&mut self, constraint: Box<dyn Constraint>) -> Result<()> {
+        if self.layout.constraints.iter().any(|c| c.name() == constraint.name()) {
+            return Ok(());
+        }
+        let mut constraints = self.layout.constraints.clone();
+        constraints.push(Rc::from(constraint));
+        let mut s = self.clone();
+        let regions = self.layout.regions.clone();
+        s.set_layout(Rc::new(Layout::new(self.shape, regions, constraints)));
+        if s.is_broken() {
+            bail!(ErrorKind::InvalidSudoku);
+        }
+        *self = s;
+        Ok(())
+    }
+
+    /// Returns the constraints which have been added to the sudoku, in the order they were added.
+    pub fn constraints(&self) -> Vec<&dyn Constraint> {
+        self.layout.constraints.iter().map(|c| &**c).collect()
+    }
+
+    /// Returns the units of the sudoku: the groups of cells which must all hold different numbers.
+    /// These are the rows, columns and boxes, followed by any units added by its constraints.
+    pub fn units(&self) -> &[Vec<(usize, usize)>] {
+        &self.layout.units
+    }
+
+    /// Returns whether the two (distinct) positions must hold different numbers, because they
+    /// share a unit (such as a row, column or box) or a cage, or because of some other constraint.
+    pub fn sees(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        let size = self.size();
+        let (i, j) = (a.0 * size + a.1, b.0 * size + b.1);
+        let layout = &self.layout;
+        i != j &&
+            (layout.cell_units[i].iter().any(|u| layout.cell_units[j].contains(u)) ||
+                 layout.cell_peers[i].contains(&j) ||
+                 self.cage_index(a.0, a.1).is_some_and(|c| self.cage_index(b.0, b.1) == Some(c)))
+    }
+
+    /// Returns all the positions which see the given position (see `sees`).
+    pub fn peers(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let size = self.size();
+        iproduct!(0..size, 0..size)
+            .filter(|&p| self.sees(p, (row, col)))
+            .collect()
+    }
+
+    /// Returns an iterator over all solutions of this sudoku.
+    pub fn solutions(&self) -> Solutions {
+        self.solutions_with(Engine::default())
+    }
+
+    /// Returns an iterator over all solutions of this sudoku, found using the given engine. The
+    /// solutions may be given in a different order depending on the engine.
+    pub fn solutions_with(&self, engine: Engine) -> Solutions {
+        Solutions(match engine {
+            Engine::Backtrack => Search::Backtrack(Backtrack::new(self)),
+            Engine::Dlx => Search::Dlx(Box::new(Dlx::new(self))),
+        })
+    }
+
+    /// Generates a random, completely filled `Sudoku` with the same shape as this one.
+    fn generate_filled<R: Rng>(&self, rng: &mut R) -> Self {
+        // Starting from an empty grid, we take the first solution of an exact cover search which
+        // tries the possible entries in a random order. Some orders lead the search into a long
+        // dead end (especially for variants), so it is started again if it takes too long.
+        let blank = self.blank();
+        let size = self.size();
+        loop {
+            let mut search = Dlx::with_rng(&blank, rng);
+            search.set_budget(FILL_BUDGET * size * size);
+            if let Some(s) = search.next() {
+                return s;
+            }
+            assert!(search.exhausted(), "empty sudoku has no solution");
+        }
+    }
+
+    /// Returns an empty `Sudoku` with the same shape and constraints as this one (but no cages).
+    fn blank(&self) -> Self {
+        let mut s = Sudoku::new(self.shape);
+        s.set_layout(self.layout.clone());
+        s
+    }
+
+    /// Replaces the layout of the sudoku, updating the occupancy of its units to match.
+    fn set_layout(&mut self, layout: Rc<Layout>) {
+        self.units = vec![Occupancy::default(); layout.units.len()];
+        self.layout = layout;
+        self.cage_occupancy = vec![Occupancy::default(); self.cages.len()];
+        self.duplicates = 0;
+        let size = self.size();
+        for (row, col) in iproduct!(0..size, 0..size) {
+            let n = self.grid[row * size + col];
+            if n != 0 {
+                self.grid[row * size + col] = 0;
+                self.put_at(n, row, col);
+            }
+        }
+    }
+
+    /// Generates a random, uniquely solvable `Sudoku` (or killer sudoku, if `killer` is set) with
+    /// the same shape as this one, optionally of the given difficulty.
+    fn generate_puzzle<R: Rng>(
+        &self,
+        killer: bool,
+        difficulty: Option<Difficulty>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        // Some combinations of constraints (or regions) can't be satisfied at all
+        let has_rules = !self.layout.constraints.is_empty() || self.is_jigsaw();
+        if has_rules && !self.blank().has_solution() {
+            bail!(ErrorKind::InvalidSudoku);
+        }
+        let difficulty = match difficulty {
+            None => return Ok(self.generate_up_to(killer, None, rng)),
+            Some(d) => d,
+        };
+        for _ in 0..GENERATE_ATTEMPTS {
+            let s = self.generate_up_to(killer, Some(difficulty), rng);
+            if s.rate().difficulty == difficulty {
+                return Ok(s);
+            }
+        }
+        bail!(ErrorKind::DifficultyNotMet(difficulty))
+    }
+
+    /// Generates a random, uniquely solvable `Sudoku` (or killer sudoku, if `killer` is set) with
+    /// the same shape as this one, which is no harder than the given difficulty (if any).
+    fn generate_up_to<R: Rng>(&self, killer: bool, max: Option<Difficulty>, rng: &mut R) -> Self {
+        // After generating a random, filled sudoku (and dividing it into cages, if needed), we
+        // shuffle the positions of entries and try removing them one by one. If, after removing an
+        // entry, we no longer have a unique solution (or the puzzle has become too hard), then we
+        // put that entry back.
+        let mut s = self.generate_filled(rng);
+        if killer {
+            s.add_random_cages(rng);
+        }
+        let size = s.size();
+        let mut positions = iproduct!(0..size, 0..size).collect::<Vec<_>>();
+        rng.shuffle(positions.as_mut_slice());
+
+        for (i, j) in positions {
+            let removed = s.remove_at(i, j);
+            if !s.has_unique_solution_quickly() || max.is_some_and(|d| s.rate().difficulty > d) {
+                s.put_at(removed, i, j);
+            }
+        }
+
+        assert!(s.has_unique_solution());
+        s
+    }
+
+    /// Returns whether the sudoku has a unique solution, like `has_unique_solution`. For sudokus
+    /// larger than 9x9 (or with constraints), the search is limited to `UNIQUENESS_BUDGET`
+    /// guesses, and `false` is returned if that isn't enough to decide.
+    fn has_unique_solution_quickly(&self) -> bool {
+        if self.size() <= 9 && self.layout.constraints.is_empty() {
+            return self.has_unique_solution();
+        }
+        let mut search = Dlx::new(self);
+        search.set_budget(UNIQUENESS_BUDGET);
+        let mut count = 0;
+        while count < 2 && search.find_next() {
+            count += 1;
+        }
+        count == 1 && !search.exhausted()
+    }
+
+    /// Writes the grid for the `Display` implementation, with lines around each box in the
+    /// alternate format.
+    fn fmt_grid(&self, f: &mut Formatter) -> FmtResult {
+        let size = self.size();
+        let (box_rows, box_cols) = (self.shape.box_rows, self.shape.box_cols);
+        let empty = if size > 9 { '.' } else { '0' };
+        let border = format!(
+            "+{}",
+            format!("{}+", "-".repeat(box_cols)).repeat(size / box_cols)
+        );
+
+        if f.alternate() {
+            writeln!(f, "{}", border)?;
+        }
+        for i in 0..size {
+            if f.alternate() {
+                write!(f, "|")?;
+            }
+            for (j, &n) in self[i].iter().enumerate() {
+                write!(f, "{}", if n == 0 { empty } else { digit_char(n) })?;
+                if f.alternate() && j % box_cols == box_cols - 1 {
+                    write!(f, "|")?;
+                }
+            }
+            if f.alternate() && i % box_rows == box_rows - 1 {
+                write!(f, "\n{}", border)?;
+            }
+            if i != size - 1 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the sudoku already breaks the rules, in a way that filling in more entries
+    /// can't fix.
+    fn is_broken(&self) -> bool {
+        let size = self.size();
+        self.duplicates != 0 ||
+            self.cages
+                .iter()
+                .zip(&self.cage_occupancy)
+                .any(|(cage, occ)| !cage.can_contain(occ.mask)) ||
+            iproduct!(0..size, 0..size).any(|(i, j)| {
+                let n = self[i][j];
+                n != 0 &&
+                    (self.layout.cell_peers[i * size + j]
+                        .iter()
+                        .any(|&p| self.grid[p] == n) ||
+                         self.layout
+                             .constraints
+                             .iter()
+                             .any(|c| c.excluded(self, i, j) & 1 << n != 0))
+            })
+    }
+
+    /// Returns the empty space which has the fewest hints (possibilities), or `None` if there are
+    /// no empty spaces.
+    fn find_min_poss(&self) -> Option<(usize, usize)> {
+        let size = self.size();
+        let mut min = size as u32 + 1;
+        let mut pos = None;
+
+        for (i, j) in iproduct!(0..size, 0..size) {
+            if self.grid[i * size + j] != 0 {
+                continue;
+            }
+            let cnt = self.hints_at(i, j).count_ones();
+            if cnt < min {
+                min = cnt;
+                pos = Some((i, j));
+                // We can't do any better than this
+                if cnt <= 1 {
+                    break;
+                }
+            }
+        }
+
+        pos
+    }
+
+    /// Returns a bitmask of the numbers which can be put at the given position without breaking
+    /// any rules (bit `n` is set if `n` can be put there). Filled positions have no hints.
+    fn hints_at(&self, row: usize, col: usize) -> u32 {
+        if self[row][col] != 0 {
+            return 0;
+        }
+        let cell = row * self.size() + col;
+        let mut used = self.layout.cell_units[cell]
+            .iter()
+            .fold(0, |used, &u| used | self.units[u].mask);
+        for &p in &self.layout.cell_peers[cell] {
+            used |= 1 << self.grid[p];
+        }
+        for constraint in &self.layout.constraints {
+            used |= constraint.excluded(self, row, col);
+        }
+        let hints = self.shape.all_numbers() & !used;
+        match self.cage_index(row, col) {
+            Some(c) => hints & self.cages[c].allowed(self.cage_occupancy[c].mask, !0),
+            None => hints,
+        }
+    }
+}
+
+impl Display for Sudoku {
+    /// Formats the sudoku as one line of digits for each row, with `0` for empty cells (or `.`
+    /// for sizes above 9, where the numbers 10 and up are given as the letters `A`, `B`, etc.).
+    ///
+    /// The alternate format (`{:#}`) draws lines around each box (or each region, for a jigsaw
+    /// sudoku). The regions of a jigsaw sudoku are given after the grid, on a line starting with
+    /// `regions:`, and the variant of the sudoku is given by a line like `variant: x` for each of
+    /// its constraints, followed by the cages of a killer sudoku, one per line (see the `Display`
+    /// implementation of `Cage`).
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if f.alternate() && self.is_jigsaw() {
+            self.fmt_jigsaw_grid(f)?;
+        } else {
+            self.fmt_grid(f)?;
+        }
+        if self.is_jigsaw() {
+            self.fmt_regions(f)?;
+        }
+        for constraint in &self.layout.constraints {
+            write!(f, "\nvariant: {}", constraint.name())?;
+        }
+        for cage in &self.cages {
+            write!(f, "\n{}", cage)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Sudoku {
+    type Err = Error;
+
+    /// Parses a `Sudoku`, whose size is determined by the number of cells in the input. The usual
+    /// shape of box is assumed for that size (see `Shape::for_size`).
+    ///
+    /// We parse `.`, `0`, and `_` as empty squares, and ignore whitespace and the `|`, `+` and `-`
+    /// characters used in the alternate format. The numbers 10 and up are given as letters
+    /// (either case), starting from `A` for 10. A line starting with `regions:` makes a jigsaw
+    /// sudoku, giving the region number of each cell row by row (counting from 1, and written
+    /// like an entry, with any whitespace ignored), such as `regions: 1222 1132 4133 4443` for a
+    /// 4x4 grid. A line like `variant: x` adds the constraint of a built-in variant (see
+    /// `constraint::variant`), and any other line containing a colon is a killer sudoku cage,
+    /// like `10: r1c1 r1c2 r2c1` (a cage with sum 10 made up of the cells at row 1, column 1; row
+    /// 1, column 2; and row 2, column 1).
+    fn from_str(s: &str) -> Result<Self> {
+        let grid = s.lines().filter(|l| !l.contains(':')).collect::<Vec<_>>();
+        let cells = grid_chars(&grid).len();
+        let size = (1..MAX_SIZE + 1).find(|&n| n * n >= cells).unwrap_or(0);
+        if size * size != cells {
+            bail!(ErrorKind::Parse(format!(
+                "found {} cells in sudoku, which is not the size of any supported grid",
+                cells
+            )));
+        }
+        let shape = Shape::for_size(size).chain_err(|| {
+            ErrorKind::Parse(format!("no supported box shape for a {}x{} sudoku", size, size))
+        })?;
+
+        Sudoku::parse_with_shape(s, shape)
+    }
+}
+
+impl Index<usize> for Sudoku {
+    type Output = [u8];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        let size = self.size();
+        &self.grid[index * size..(index + 1) * size]
+    }
+}
+
+impl Occupancy {
+    /// Records a use of `n`, returning whether it was already used.
+    fn add(&mut self, n: u8) -> bool {
+        self.counts[n as usize] += 1;
+        self.mask |= 1 << n;
+        self.counts[n as usize] > 1
+    }
+
+    /// Removes a use of `n`, returning whether it is still used.
+    fn remove(&mut self, n: u8) -> bool {
+        self.counts[n as usize] -= 1;
+        if self.counts[n as usize] == 0 {
+            self.mask &= !(1 << n);
+            false
+        } else {
+            true
+        }
+    }
+}
+
+impl Engine {
+    /// Returns the name of the engine, as accepted by `from_str`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Engine::Backtrack => "backtrack",
+            Engine::Dlx => "dlx",
+        }
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Engine {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "backtrack" => Ok(Engine::Backtrack),
+            "dlx" => Ok(Engine::Dlx),
+            _ => bail!(ErrorKind::Parse(format!("unknown engine `{}`", s))),
+        }
+    }
+}
+
+impl Backtrack {
+    /// Sets up a search for the solutions of the given sudoku.
+    fn new(s: &Sudoku) -> Self {
+        Backtrack {
+            board: s.clone(),
+            stack: Vec::new(),
+            found: false,
+            // A board which already breaks the rules can't be fixed by filling in more entries
+            done: s.is_broken(),
+        }
+    }
+
+    /// Searches for the next solution, which is left in `board`. Returns `false` if there are no
+    /// solutions left.
+    fn find_next(&mut self) -> bool {
+        // Continue from the last guess if we've already found the current solution
+        if self.found {
+            self.found = false;
+            self.done = !self.advance();
+        }
+        while !self.done {
+            // Find the position with the fewest possibilities
+            match self.board.find_min_poss() {
+                None => {
+                    // The board is full, and since we only ever put valid entries, it must be
+                    // solved.
+                    self.found = true;
+                    return true;
+                }
+                Some((row, col)) => {
+                    let remaining = self.board.hints_at(row, col);
+                    self.stack.push(Guess { row, col, remaining });
+                }
+            }
+            self.done = !self.advance();
+        }
+        false
+    }
+
+    /// Tries the next untried candidate of the most recent guess, backtracking as necessary.
+    /// Returns `false` if there is nothing left to try.
+    fn advance(&mut self) -> bool {
+        while let Some(guess) = self.stack.last_mut() {
+            if guess.remaining == 0 {
+                self.board.remove_at(guess.row, guess.col);
+                self.stack.pop();
+            } else {
+                let n = guess.remaining.trailing_zeros() as u8;
+                guess.remaining &= guess.remaining - 1;
+                self.board.put_at(n, guess.row, guess.col);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for Solutions {
+    type Item = Sudoku;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            Search::Backtrack(ref mut search) => search.next(),
+            Search::Dlx(ref mut search) => search.next(),
+        }
+    }
+}
+
+impl Iterator for Backtrack {
+    type Item = Sudoku;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.find_next() {
+            Some(self.board.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the characters making up the cells of a sudoku grid given as text, leaving out
+/// whitespace and the lines drawn around boxes in the alternate format.
+fn grid_chars(lines: &[&str]) -> Vec<char> {
+    lines
+        .iter()
+        .flat_map(|l| l.chars())
+        .filter(|&c| !c.is_whitespace() && !"|+-".contains(c))
+        .collect()
+}
+
+/// Returns the character used to represent the number `n` (between 1 and `MAX_SIZE`) in text:
+/// the digits 1-9, followed by the letters `A` (for 10) onwards.
+pub fn digit_char(n: u8) -> char {
+    match n {
+        1..=9 => (b'0' + n) as char,
+        _ => (b'A' + n - 10) as char,
+    }
+}
+
+/// Returns the number represented by the given character (see `digit_char`), if any. Letters are
+/// accepted in either case.
+pub fn parse_digit(c: char) -> Option<u8> {
+    let n = match c {
+        '1'..='9' => c as u8 - b'0',
+        'A'..='Z' => c as u8 - b'A' + 10,
+        'a'..='z' => c as u8 - b'a' + 10,
+        _ => return None,
+    };
+    if n as usize <= MAX_SIZE { Some(n) } else { None }
+}
+
+/// Parses a 1-based position of the form `r1c2` (as used for the cells of a cage), returning it
+/// as a 0-based `(row, column)`.
+pub fn parse_position(s: &str) -> Result<(usize, usize)> {
+    let lower = s.to_lowercase();
+    let mut parts = lower.trim_start_matches('r').splitn(2, 'c');
+    let row = parts.next().and_then(|r| r.parse::<usize>().ok());
+    let col = parts.next().and_then(|c| c.parse::<usize>().ok());
+    match (row, col) {
+        (Some(row), Some(col)) if lower.starts_with('r') && row >= 1 && col >= 1 => {
+            Ok((row - 1, col - 1))
+        }
+        _ => bail!(ErrorKind::Parse(format!(
+            "invalid cell `{}` (expected something like `r1c2`)",
+            s
+        ))),
+    }
+}
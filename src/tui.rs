@@ -23,68 +23,177 @@
 //! screen (like `Grid`), but anything coming from a `game::Game` or `Sudoku` follows the usual
 //! convention of `(row, column)`.
 
-use std::char;
+use std::cmp;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
 use std::io::{stdin, stdout, Stdout, Write};
+use std::iter;
 use std::ops::Drop;
+use std::path::PathBuf;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use chan::{self, Receiver};
 use chan_signal::{self, Signal};
+use itertools::Itertools;
+use libc;
 use termion::{self, clear, color, cursor, style};
-use termion::event::Key;
+use termion::event::{Event, Key};
 use termion::input::TermRead;
 use termion::raw::{IntoRawMode, RawTerminal};
 
 use errors::*;
 use game;
+use stats::{self, Stats};
+use sudoku::{self, Shape, Sudoku};
+use sudoku::logic::{Difficulty, Unit};
 use util;
-use Sudoku;
 
-/// The minimum width of the terminal to effectively play the game.
+/// The minimum width of the terminal to effectively play the game (larger boards need more).
 const MIN_WIDTH: u16 = 72;
-/// The minimum height of the terminal to effectively play the game.
+/// The minimum height of the terminal to effectively play the game (larger boards need more).
 const MIN_HEIGHT: u16 = 20;
 
-/// The width of a single cell in the sudoku grid; must be odd.
+/// The width of a single cell in the sudoku grid; must be odd, so that the number in the cell can
+/// be drawn in the middle (`Grid::large_for_board` keeps this true for larger cells too).
 const CELL_WIDTH: u16 = 3;
-/// The height of a single cell in the sudoku grid; must be odd.
-/// Right now, any value other than 1 won't be handled quite correctly.
+/// The height of a single cell in the sudoku grid; must be odd, for the same reason as
+/// `CELL_WIDTH`.
 const CELL_HEIGHT: u16 = 1;
 
 /// The background color to use for highlighting the most recent hint.
 const COLOR_HINT: color::Yellow = color::Yellow;
 /// The background color to use for highlighting the selected cell.
 const COLOR_SELECTION: color::Blue = color::Blue;
+/// The background color to use for highlighting the cells selected to be changed all at once (see
+/// `game::Game::select`).
+const COLOR_MULTI_SELECTION: color::LightBlue = color::LightBlue;
+/// The foreground color to use for numbers which are mistakes (see `:set check`).
+const COLOR_MISTAKE: color::Red = color::Red;
 /// The background color to use for indicating that the board has been solved.
 const COLOR_SOLVED: color::Green = color::Green;
+/// The foreground color to use for corner marks in the annotations window (or inside the cells).
+const COLOR_CORNER_MARK: color::LightGreen = color::LightGreen;
 /// The background color to use for highlighting filled cells in the annotations window.
 const COLOR_FILLED: color::LightBlack = color::LightBlack;
+/// The background color to use for shading the cells of units added by a variant, like the
+/// diagonals of Sudoku X.
+const COLOR_EXTRA_UNIT: color::Magenta = color::Magenta;
+/// The color used to highlight the cell changed by the last move shown in a replay.
+const COLOR_REPLAY: color::Cyan = color::Cyan;
+
+/// The name of the directory in the data directory (see `util::data_dir`) where games in progress
+/// are saved automatically, in a file for each puzzle (see `autosave_path`).
+const AUTOSAVE_DIR: &str = "autosave";
+
+/// The escape sequences sent by the terminal for shift with an arrow key, which `termion` doesn't
+/// recognize, along with the direction they move in as `(rows, columns)`. Both the xterm and the
+/// rxvt sequences are included.
+const SHIFT_ARROWS: &[(&[u8], (isize, isize))] = &[
+    (b"\x1b[1;2A", (-1, 0)),
+    (b"\x1b[1;2B", (1, 0)),
+    (b"\x1b[1;2C", (0, 1)),
+    (b"\x1b[1;2D", (0, -1)),
+    (b"\x1b[a", (-1, 0)),
+    (b"\x1b[b", (1, 0)),
+    (b"\x1b[c", (0, 1)),
+    (b"\x1b[d", (0, -1)),
+];
 
 /// All possible status commands.
-const COMMANDS: &[&str] = &["annot", "help", "hint", "new", "noannot", "solve", "q"];
+const COMMANDS: &[&str] = &[
+    "annot", "autonote", "e", "export", "help", "hint", "new", "noannot", "pause", "redo",
+    "select", "set", "solve", "stats", "q", "undo", "undo-branch", "undolist", "w",
+];
+
+/// The status commands which can be used while replaying a game.
+const REPLAY_COMMANDS: &[&str] = &["annot", "help", "noannot", "q", "set"];
+
+/// The longest pause between two moves of a replay at normal speed, in seconds. Players often stop
+/// to think, and there's no point in watching them do so.
+const REPLAY_MAX_PAUSE: f64 = 3.0;
+
+/// The slowest and fastest speeds at which a game can be replayed, relative to real time.
+const REPLAY_SPEEDS: (f64, f64) = (0.125, 64.0);
+
+/// A documentation string for the replay controls.
+const REPLAY_HELP: &str = "TUI REPLAY CONTROLS
+
+CONTROL              DESCRIPTION
+SPACE, p             pause or continue the replay
+l or right arrow     step forward one move (pausing the replay)
+h or left arrow      step back one move (pausing the replay)
++ or =, -            make the replay twice as fast or twice as slow
+0, g                 go back to the start
+$, G                 go to the end
+q                    quit the replay
+
+COMMANDS             DESCRIPTION
+:q                   quit the replay
+:annot               turn on annotations display (inside the cells if there is room)
+:noannot             turn off annotations display
+:help                show this help
+:set [option...]     change or show the options below
+
+OPTIONS              DESCRIPTION
+check=<mode>         show mistakes in red: off, conflicts (numbers which break the rules) or
+                     solution (numbers which are different from the solution)
+
+Moves are replayed in the order they were made, including those which were later undone.
+";
 
 /// A documentation string for the in-game controls.
 const HELP: &str = "TUI GAME CONTROLS
 
 CONTROL              DESCRIPTION
 hjkl or arrows       movement by cell
-HJKL                 movement by 3x3 box
+HJKL                 movement by box
+SHIFT + arrows       movement by cell, selecting both cells
+v                    start or stop visual mode, where movement selects each cell moved to
+ESC                  clear the selection (and leave visual mode)
 1-9                  fill cell with number
+i <number>           fill cell with <number> (on boards above 9x9, A is 10, B is 11, etc.)
 0, d, x, DELETE      clear number in cell
-a <number>           toggle annotation for <number> in cell
+a <number>           toggle annotation for <number> in cell (A, B, etc. must be uppercase)
 a x, a d             clear all annotations in cell
+c <number>           toggle corner mark for <number> in cell (shown in green with annotations)
+c x, c d             clear all corner marks in cell
 u                    undo last action
+CTRL-R               redo last undone action
+q                    quit the game (asks first if the game isn't solved)
+CTRL-Z               suspend the game (the clock stops until it is continued)
 :                    input an ex-style command (see list below)
 
+While any cells are selected, numbers, annotations and corner marks are changed in all of them
+at once, and the change is undone all at once. Changing them leaves visual mode, but keeps the
+selection.
+
 COMMANDS             DESCRIPTION
-:q                   quit the game
-:annot               turn on annotations display
+:q                   quit the game (asks first if the game isn't solved)
+:annot               turn on annotations display (inside the cells if there is room)
+:autonote            annotate every empty cell with the numbers which could go there
+:e <file>            load a saved game from <file>
+:export <file>       export the moves leading to the current board to <file> for replaying
 :noannot             turn off annotations display
 :help                show this help
-:hint                give a hint
-:new                 start a new game
+:hint                give a hint (again for more: what to look for, the cells, the number)
+:new [difficulty]    start a new game (optionally easy, medium, hard, expert or diabolical)
+:redo [count]        redo the last [count] undone actions (by default, 1)
+:select <what>       select the current row, column or box, or every cell with a number
+:set [option...]     change or show the options below
 :solve               solve the current board
+:stats               show statistics about the games you have finished
+:undo [count]        undo the last [count] actions (by default, 1)
+:undo-branch <move>  go to the board right after <move> (0 for the start), on any branch
+:undolist            list the branches of the undo tree
+:w [file]            save the game to [file] (by default, the last file saved to or loaded)
+
+OPTIONS              DESCRIPTION
+check=<mode>         show mistakes in red: off, conflicts (numbers which break the rules) or
+                     solution (numbers which are different from the solution)
+counter, nocounter   show or hide the number of mistakes made so far next to the clock
+autoprune            remove a number from the annotations of the cells which see it when
+                     it is filled in (noautoprune to turn off)
 ";
 
 /// Contains the state of the TUI game.
@@ -93,38 +202,152 @@ pub struct Game<'a> {
     game: game::Game,
     /// The position of the last hint given (for highlighting).
     hintpos: Option<(usize, usize)>,
+    /// The last hint found, if it hasn't been given in full yet (see `hint`).
+    hint: Option<PartialHint>,
     /// The text to display in the status line.
     status: String,
-    /// Whether to show the annotations window.
+    /// Whether to show the annotations, either in their own window or inside the cells (see
+    /// `large_cells`).
     show_annotations: bool,
+    /// How to check the board for mistakes to highlight.
+    check: game::CheckMode,
+    /// Whether to show the number of mistakes made so far in the status line.
+    show_counter: bool,
+    /// Whether the game is in visual mode, where moving adds cells to the selection.
+    visual: bool,
+    /// The file the game was last saved to or loaded from, if any.
+    file: Option<PathBuf>,
+    /// The time from which the time spent playing has yet to be added to the game.
+    clock: Instant,
+    /// Whether to save the game automatically (to `autosave_path`) after every move.
+    autosave: bool,
+    /// The state of the game when it was last saved automatically or loaded (see
+    /// `autosave_state`), which tells whether it needs to be saved again.
+    autosaved: (usize, Option<usize>, bool),
+    /// The file to record the game to after every move, if any. Unlike the autosave, this is kept
+    /// once the game is solved.
+    record: Option<PathBuf>,
+    /// The state of the replay, if the game is being replayed rather than played.
+    replay: Option<Replay>,
+    /// Whether the game has been added to the statistics file (see `stats::record`), which is
+    /// done once it is solved.
+    recorded: bool,
     /// The underlying terminal output.
     stdout: &'a mut RawTerminal<Stdout>,
-    /// Keyboard input channel.
-    keys: Receiver<Key>,
+    /// Keyboard input channel. Events other than keys, including keys which `termion` doesn't
+    /// recognize, are passed on as they are.
+    events: Receiver<Event>,
     /// Signal input channel.
     signals: Receiver<Signal>,
 }
 
-/// The outline of a grid to be drawn on screen.
-///
-/// The members of a `Grid` are the width in columns and the height in rows (respectively) of a
-/// cell.
-struct Grid(u16, u16);
+/// A hint which is given a little at a time, each time the `:hint` command is used: first the
+/// technique to look for and where, then the cells involved, and finally the number itself.
+struct PartialHint {
+    hint: game::Hint,
+    /// The last move made before the hint was found. Once another move is made (or this one is
+    /// undone), the hint no longer applies.
+    after: Option<usize>,
+    /// Whether the cells involved have been shown yet.
+    shown_cells: bool,
+}
+
+/// The state of a replay of a recorded game, which shows every move in its undo tree in the order
+/// they were made.
+struct Replay {
+    /// The number of moves which have been shown so far.
+    step: usize,
+    /// How many times faster than real time the moves are shown.
+    speed: f64,
+    /// Whether the replay is paused, waiting for the player to step through it.
+    paused: bool,
+}
+
+/// The outline of a grid to be drawn on screen, with a thin line between each box.
+pub(crate) struct Grid {
+    /// The width of a cell, in columns.
+    cell_width: u16,
+    /// The height of a cell, in rows.
+    cell_height: u16,
+    /// The shape of the boxes in the grid.
+    shape: Shape,
+    /// The borders to draw between every pair of cells, if the grid is outlined.
+    outline: Option<Outline>,
+}
+
+/// The borders of an outlined `Grid`, which has room for a line between every pair of neighbouring
+/// cells. This is needed to show the cages of a killer sudoku and the regions of a jigsaw sudoku.
+struct Outline {
+    /// The box (or jigsaw region) containing each cell, row by row.
+    boxes: Vec<usize>,
+    /// Whether to draw the borders between boxes with thick lines, so that irregular regions
+    /// stand out.
+    thick: bool,
+    /// The cage containing each cell (if any), row by row.
+    cages: Vec<Option<usize>>,
+    /// Text to be drawn on the top border of certain cells (such as the sum of the cage whose top
+    /// left cell it is), as `((row, column), text)`.
+    labels: Vec<((usize, usize), String)>,
+}
+
+/// The kind of border between two neighbouring cells (or a cell and the edge) of an outlined
+/// `Grid`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Border {
+    /// The cells are in the same box and cage.
+    Empty,
+    /// The cells are in the same box, but different cages.
+    Cage,
+    /// The cells are in different boxes.
+    Region,
+    /// The edge of the grid.
+    Edge,
+}
 
 impl<'a> Game<'a> {
-    /// Runs the game interactively, using the given `Sudoku` as the initial board.
-    pub fn run(s: Sudoku) -> Result<()> {
+    /// Runs the given game interactively. If the game was loaded from a file, it should be given
+    /// as `file`, so that the `:w` command can save to it by default.
+    ///
+    /// The game is saved automatically (to `autosave_path`) after every move and when the game
+    /// ends, unless it has been solved, in which case the autosave is removed. If `record` is
+    /// given, the game is also saved there after every move, so that it can be replayed later
+    /// (even once it has been solved).
+    pub fn run(game: game::Game, file: Option<PathBuf>, record: Option<PathBuf>) -> Result<()> {
+        Game::start(game, file, record, None)
+    }
+
+    /// Replays every move of the given game, starting from its given board, at the given speed
+    /// relative to the time the moves were originally made. The player can pause the replay and
+    /// step through it, but can't make any moves.
+    pub fn replay(mut game: game::Game, speed: f64) -> Result<()> {
+        game.jump_to(None);
+        let speed = speed.max(REPLAY_SPEEDS.0).min(REPLAY_SPEEDS.1);
+        let replay = Replay {
+            step: 0,
+            speed,
+            paused: false,
+        };
+        Game::start(game, None, None, Some(replay))
+    }
+
+    /// Sets up the terminal and runs the game (see `run` and `replay`).
+    fn start(
+        game: game::Game,
+        file: Option<PathBuf>,
+        record: Option<PathBuf>,
+        replay: Option<Replay>,
+    ) -> Result<()> {
         // Listen for terminal resize signals.
         // NOTE: this MUST be called before any other threads are spawned, per the `chan_signal`
         // documentation.
         let signals = chan_signal::notify(&[Signal::WINCH]);
 
         // Set up keyboard input channel
-        let (keys_send, keys_recv) = chan::async();
+        let (events_send, events_recv) = chan::async();
         thread::spawn(move || {
             let stdin = stdin();
-            for key in stdin.keys() {
-                keys_send.send(key.unwrap());
+            for event in stdin.events() {
+                events_send.send(event.unwrap());
             }
         });
 
@@ -141,13 +364,45 @@ impl<'a> Game<'a> {
         ).unwrap();
         stdout.flush().unwrap();
 
+        let mut status = match game.seed() {
+            _ if replay.is_some() => format!(
+                "Replaying {} moves. Type `:help<RET>` for help.",
+                game.moves().len()
+            ),
+            None => "Welcome to RSudoku! Type `:help<RET>` for help.".to_string(),
+            Some(seed) => format!(
+                "Welcome to RSudoku! Type `:help<RET>` for help. (seed: {})",
+                seed
+            ),
+        };
+        let variants = game.board()
+            .constraints()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect::<Vec<_>>();
+        if !variants.is_empty() {
+            status += &format!(" (variant: {})", variants.join(", "));
+        }
+        let recorded = game.completion().is_some() || replay.is_some();
+        let autosaved = autosave_state(&game);
         let mut game = Game {
-            game: game::Game::from_sudoku(s),
+            game,
             hintpos: None,
-            status: "Welcome to RSudoku! Type `:help<RET>` for help.".into(),
+            hint: None,
+            status,
             show_annotations: false,
+            check: game::CheckMode::Off,
+            show_counter: false,
+            visual: false,
+            file,
+            clock: Instant::now(),
+            autosave: replay.is_none(),
+            autosaved,
+            record,
+            recorded,
+            replay,
             stdout: &mut stdout,
-            keys: keys_recv,
+            events: events_recv,
             signals,
         };
         game.main()?;
@@ -161,12 +416,13 @@ impl<'a> Game<'a> {
         // may be very confused, so it's best to just exit with an error if this is the case
         // initially.
         let (width, height) = termion::terminal_size().unwrap();
-        if width < MIN_WIDTH || height < MIN_HEIGHT {
+        let (min_width, min_height) = self.min_size();
+        if width < min_width || height < min_height {
             bail!(
                 "terminal is too small to play the game; must be at least {} rows by {} columns \
                  (current terminal has {} rows and {} columns)",
-                MIN_HEIGHT,
-                MIN_WIDTH,
+                min_height,
+                min_width,
                 height,
                 width
             );
@@ -178,28 +434,54 @@ impl<'a> Game<'a> {
         loop {
             // I have no idea why the `chan_select` macro doesn't accept anything with `self` in it,
             // but this works just as well I guess...
-            let keys = self.keys.clone();
+            let events = self.events.clone();
             let signals = self.signals.clone();
+            // The next move of a replay is shown when the timer goes off; otherwise, it never does
+            let (_idle, mut timer) = chan::sync(0);
+            if let Some(delay) = self.replay_delay() {
+                timer = chan::after(delay);
+            }
+            // Likewise, the clock in the status line ticks over every second while playing
+            let (_stopped, mut tick) = chan::sync(0);
+            if self.replay.is_none() && !self.game.is_solved() {
+                let millis = self.unrecorded_time().subsec_millis() as u64;
+                tick = chan::after(Duration::from_millis(1000 - millis));
+            }
             chan_select! {
-                keys.recv() -> key => {
-                    match self.input_key(key.unwrap()) {
+                events.recv() -> event => {
+                    // Moves are timed from the moment their key is pressed
+                    self.update_clock();
+                    let res = self.input_event(event.unwrap());
+                    if let Err(e) = self.autosave_if_changed() {
+                        self.set_status(&format!("Error: {}", e.iter().join(": ")));
+                        self.draw_status();
+                        self.stdout.flush().unwrap();
+                    }
+                    match res {
                         Ok(true) => break,
                         Ok(false) => {}
                         Err(e) => {
-                            self.set_status(&format!("Error: {}", e));
+                            // The causes are included, since there is no other way to see them
+                            self.set_status(&format!("Error: {}", e.iter().join(": ")));
                             self.draw_status();
                             self.stdout.flush().unwrap();
                         }
                     }
                 },
+                tick.recv() => {
+                    self.draw_status();
+                    self.stdout.flush().unwrap();
+                },
+                timer.recv() => {
+                    self.replay_step(true);
+                    self.draw_all();
+                    self.stdout.flush().unwrap();
+                },
                 signals.recv() -> signal => {
-                    match signal.unwrap() {
-                        Signal::WINCH => {
-                            write!(self.stdout, "{}", clear::All).unwrap();
-                            self.draw_all();
-                            self.stdout.flush().unwrap();
-                        }
-                        _ => {}
+                    if signal.unwrap() == Signal::WINCH {
+                        write!(self.stdout, "{}", clear::All).unwrap();
+                        self.draw_all();
+                        self.stdout.flush().unwrap();
                     }
                 },
             }
@@ -208,6 +490,25 @@ impl<'a> Game<'a> {
         Ok(())
     }
 
+    /// Processes an input event, returning whether the game should exit. Shift with an arrow key
+    /// extends the selection (see `move_cursor`); any other event besides a key is ignored.
+    fn input_event(&mut self, event: Event) -> Result<bool> {
+        let seq = match event {
+            Event::Key(key) => return self.input_key(key),
+            Event::Unsupported(seq) => seq,
+            Event::Mouse(_) => return Ok(false),
+        };
+        if let Some(&(_, (rows, cols))) = SHIFT_ARROWS.iter().find(|a| a.0 == &seq[..]) {
+            if self.replay.is_none() && !self.game.is_solved() {
+                self.set_status("");
+                self.move_cursor(rows, cols, true);
+                self.draw_all();
+                self.stdout.flush().unwrap();
+            }
+        }
+        Ok(false)
+    }
+
     /// Processes keyboard input for normal mode, returning whether the game should exit.
     fn input_key(&mut self, key: Key) -> Result<bool> {
         // We clear the status on each new iteration of the input loop so that the message doesn't
@@ -219,45 +520,91 @@ impl<'a> Game<'a> {
         if key == Key::Char(':') {
             return self.input_status();
         }
+        if key == Key::Ctrl('z') {
+            self.suspend();
+            self.draw_all();
+            self.stdout.flush().unwrap();
+            return Ok(false);
+        }
+        if self.replay.is_some() {
+            let quit = self.input_replay_key(key);
+            self.draw_all();
+            self.stdout.flush().unwrap();
+            return Ok(quit);
+        }
         if !self.game.is_solved() {
+            let shape = self.game.board().shape();
+            let (box_rows, box_cols) = (shape.box_rows() as isize, shape.box_cols() as isize);
+            let visual = self.visual;
             match key {
-                Key::Char('q') => return Ok(true),
+                Key::Char('q') if self.confirm_quit() => return Ok(true),
                 // Motion in grid
-                Key::Char('h') | Key::Left => self.game.move_by(0, -1),
-                Key::Char('j') | Key::Down => self.game.move_by(1, 0),
-                Key::Char('k') | Key::Up => self.game.move_by(-1, 0),
-                Key::Char('l') | Key::Right => self.game.move_by(0, 1),
-                Key::Char('H') => self.game.move_by(0, -3),
-                Key::Char('J') => self.game.move_by(3, 0),
-                Key::Char('K') => self.game.move_by(-3, 0),
-                Key::Char('L') => self.game.move_by(0, 3),
+                Key::Char('h') | Key::Left => self.move_cursor(0, -1, visual),
+                Key::Char('j') | Key::Down => self.move_cursor(1, 0, visual),
+                Key::Char('k') | Key::Up => self.move_cursor(-1, 0, visual),
+                Key::Char('l') | Key::Right => self.move_cursor(0, 1, visual),
+                Key::Char('H') => self.move_cursor(0, -box_cols, visual),
+                Key::Char('J') => self.move_cursor(box_rows, 0, visual),
+                Key::Char('K') => self.move_cursor(-box_rows, 0, visual),
+                Key::Char('L') => self.move_cursor(0, box_cols, visual),
+                // Selection
+                Key::Char('v') => {
+                    self.visual = !self.visual;
+                    if self.visual {
+                        let (row, col) = self.game.position();
+                        self.game.select(row, col);
+                    }
+                }
+                Key::Esc => {
+                    self.visual = false;
+                    self.game.clear_selection();
+                }
                 // Removal
                 Key::Char('0') | Key::Char('x') | Key::Char('d') | Key::Delete => {
+                    self.visual = false;
                     self.game.remove()
                 }
                 // Insertion
-                Key::Char(c @ '1'...'9') => {
-                    self.game.put(c.to_digit(10).unwrap() as u8);
-                    self.check_solved();
+                Key::Char('1'..='9') => self.put_key(key),
+                Key::Char('i') => {
+                    let key = self.next_key();
+                    self.put_key(key);
+                }
+                // Undo and redo
+                Key::Char('u') => self.undo(1, false),
+                Key::Ctrl('r') => self.undo(1, true),
+                // Annotation and corner marks
+                Key::Char('a') | Key::Char('c') => {
+                    self.visual = false;
+                    let corner = key == Key::Char('c');
+                    match self.next_key() {
+                        Key::Char('x') | Key::Char('d') if corner => {
+                            self.game.clear_corner_marks()
+                        }
+                        Key::Char('x') | Key::Char('d') => self.game.clear_annotations(),
+                        key => match self.key_number(key) {
+                            Some(n) if corner => self.game.mark_corner(n),
+                            Some(n) => self.game.annotate(n),
+                            None => {
+                                let status = format!(
+                                    "Must enter a number ({}) to {}, or 'x' or 'd' to clear",
+                                    self.number_range(),
+                                    if corner { "mark" } else { "annotate" }
+                                );
+                                self.set_status(&status);
+                            }
+                        },
+                    }
                 }
-                // Undo
-                Key::Char('u') => if self.game.undo() {
-                    self.set_status("Undid last move");
-                } else {
-                    self.set_status("Nothing to undo");
-                },
-                // Annotation
-                Key::Char('a') => match self.keys.recv().unwrap() {
-                    Key::Char(c @ '1'...'9') => self.game.annotate(c.to_digit(10).unwrap() as u8),
-                    Key::Char('x') | Key::Char('d') => self.game.clear_annotations(),
-                    _ => self.set_status(
-                        "Must enter a number (1-9) to annotate, or 'x' or 'd' to clear",
-                    ),
-                },
                 _ => {}
             }
         }
 
+        // Like in Vim, the mode is shown until something else needs to be
+        if self.visual && self.status.is_empty() {
+            self.set_status("-- VISUAL --");
+        }
+
         // We clear the last given hint here; the highlighting will take place at the end of
         // `input_status` and should be cleared on the next action (which is now).
         self.hintpos = None;
@@ -280,8 +627,8 @@ impl<'a> Game<'a> {
         ).unwrap();
         self.stdout.flush().unwrap();
 
-        for key in &self.keys {
-            match key {
+        loop {
+            match self.next_key() {
                 Key::Char('\n') => {
                     let res = self.process_command(&command);
                     write!(self.stdout, "{}{}", clear::CurrentLine, cursor::Hide).unwrap();
@@ -307,7 +654,7 @@ impl<'a> Game<'a> {
                     write!(self.stdout, "{0} {0}", cursor::Left(1)).unwrap();
                     self.stdout.flush().unwrap();
                     // Cancel command entry if the user tries to backspace over the leading ':'
-                    if command.pop() == None {
+                    if command.pop().is_none() {
                         write!(self.stdout, "{}{}", clear::CurrentLine, cursor::Hide).unwrap();
                         self.stdout.flush().unwrap();
                         return Ok(false);
@@ -321,48 +668,196 @@ impl<'a> Game<'a> {
                 _ => {}
             }
         }
-
-        Ok(false)
     }
 
     /// Processes the given status command and executes the appropriate function, returning whether
     /// the game should exit.
     fn process_command(&mut self, command: &str) -> Result<bool> {
-        match command {
-            "" => return Ok(false),
-            "q" => return Ok(true),
+        // The first word is the name of the command, and any remaining words are its arguments
+        let mut words = command.split_whitespace();
+        let name = match words.next() {
+            None => return Ok(false),
+            Some(name) => name,
+        };
+        let args = words.collect::<Vec<_>>();
+        if self.replay.is_some() && !REPLAY_COMMANDS.contains(&name) {
+            bail!("can't use `:{}` while replaying a game", name);
+        }
+
+        match name {
+            "q" => if self.confirm_quit() {
+                return Ok(true);
+            },
             "annot" => {
                 self.show_annotations = true;
                 write!(self.stdout, "{}", clear::All).unwrap();
                 self.set_status("Turned on annotations display");
             }
-            "help" => self.show_help()?,
-            "hint" => {
-                match self.game.hint()? {
-                    Some((row, col)) => {
-                        self.hintpos = Some((row, col));
-                        self.set_status(&format!("Hint given at position ({}, {})", row, col))
+            "autonote" => {
+                self.game.fill_annotations();
+                if !self.show_annotations {
+                    self.show_annotations = true;
+                    write!(self.stdout, "{}", clear::All).unwrap();
+                }
+                self.set_status("Annotated every empty cell with its candidates");
+            }
+            "e" => {
+                let file = match args.first() {
+                    None => bail!("must give a file to load the game from"),
+                    Some(file) => PathBuf::from(file),
+                };
+                // The autoprune setting is saved along with the game
+                self.game = game::Game::load(&file)?;
+                self.set_status(&format!("Loaded game from {}", file.display()));
+                // Saving this game automatically will replace any other unfinished game of the
+                // same puzzle, so the player is told where to find it while there's still time
+                if let Some(path) = autosave_path(&self.game) {
+                    match game::Game::load(&path) {
+                        Ok(ref saved) if !saved.is_solved() && *saved != self.game => {
+                            let status = format!(
+                                "Loaded game from {} (the next move replaces the unfinished game \
+                                 of this puzzle in {})",
+                                file.display(),
+                                path.display()
+                            );
+                            self.set_status(&status);
+                        }
+                        _ => {}
                     }
-                    None => self.set_status("Current board is already solved"),
                 }
-                self.check_solved();
+                self.file = Some(file);
+                self.clock = Instant::now();
+                self.recorded = self.game.completion().is_some();
+                self.autosaved = autosave_state(&self.game);
+                self.visual = false;
+                self.hintpos = None;
+                self.hint = None;
+                write!(self.stdout, "{}", clear::All).unwrap();
+            }
+            "export" => {
+                let file = match args.first() {
+                    None => bail!("must give a file to export the moves to"),
+                    Some(file) => PathBuf::from(file),
+                };
+                self.game.export_moves(&file)?;
+                let moves = count(self.game.move_log().len(), "move");
+                self.set_status(&format!("Exported {} to {}", moves, file.display()));
             }
+            "help" if self.replay.is_some() => self.show_page(REPLAY_HELP),
+            "help" => self.show_page(HELP),
+            "hint" => self.hint()?,
             "new" => {
-                self.game = game::Game::new();
+                // The new game has the same shape as the current one
+                let seed = util::random_seed();
+                let template = self.game.given().clone();
+                let autoprune = self.game.autoprune();
+                match args.first() {
+                    None => {
+                        self.game = game::Game::from_seed_like(seed, &template, None)?;
+                        self.set_status(&format!("Started new game (seed: {})", seed));
+                    }
+                    Some(d) => {
+                        let difficulty = d.parse::<Difficulty>()?;
+                        self.game =
+                            game::Game::from_seed_like(seed, &template, Some(difficulty))?;
+                        self.set_status(
+                            &format!("Started new {} game (seed: {})", difficulty, seed),
+                        );
+                    }
+                }
+                self.game.set_autoprune(autoprune);
+                self.clock = Instant::now();
+                self.recorded = false;
+                self.autosaved = autosave_state(&self.game);
+                self.visual = false;
                 self.hintpos = None;
-                self.set_status("Started new game");
+                self.hint = None;
+                write!(self.stdout, "{}", clear::All).unwrap();
             }
             "noannot" => {
                 self.show_annotations = false;
                 write!(self.stdout, "{}", clear::All).unwrap();
                 self.set_status("Turned off annotations display");
             }
+            "pause" => self.pause(),
+            "select" => {
+                let (row, col) = self.game.position();
+                let board = self.game.board();
+                match args.first() {
+                    Some(&"row") => self.game.select_unit(Unit::Row(row)),
+                    Some(&"column") => self.game.select_unit(Unit::Column(col)),
+                    Some(&"box") => {
+                        let region = board.region_index(row, col);
+                        self.game.select_unit(Unit::Box(region));
+                    }
+                    Some(arg) => match arg.parse().ok().and_then(sudoku::parse_digit) {
+                        Some(n) if n as usize <= board.size() => self.game.select_number(n),
+                        _ => bail!("can't select `{}`; must be row, column, box or a number", arg),
+                    },
+                    None => bail!("must give what to select (row, column, box or a number)"),
+                }
+                let selected = self.game.selection().len();
+                self.set_status(&format!("Selected {}", count(selected, "cell")));
+            }
+            "set" => self.set_options(&args)?,
+            "redo" | "undo" => {
+                let count = match args.first() {
+                    None => 1,
+                    Some(n) => match n.parse::<usize>() {
+                        Ok(n) if n > 0 => n,
+                        _ => bail!("invalid count `{}`; must be a positive number", n),
+                    },
+                };
+                self.undo(count, name == "redo");
+            }
+            "stats" => {
+                let stats = match stats::stats_path() {
+                    None => bail!("there is nowhere to keep statistics"),
+                    Some(path) => Stats::load(path)?,
+                };
+                // As many games are listed in each leaderboard as fit on the screen
+                let (_, height) = termion::terminal_size().unwrap();
+                let groups = stats.by_difficulty().len().max(1);
+                let lines = 4 * groups + 7;
+                let top = ((height as usize).saturating_sub(lines) / groups).clamp(1, 10);
+                let text = format!("STATISTICS\n\n{}", stats.report(top, stats::now()));
+                self.show_page(&text);
+            }
+            "undo-branch" => {
+                let moves = self.game.moves().len();
+                let number = match args.first().map(|n| n.parse::<usize>()) {
+                    Some(Ok(n)) if n <= moves => n,
+                    Some(_) => bail!("invalid move; must be a number from 0 to {}", moves),
+                    None => bail!("must give the number of a move (see :undolist)"),
+                };
+                self.game.jump_to(number.checked_sub(1));
+                if number == 0 {
+                    self.set_status("Went back to the start");
+                } else {
+                    let action = self.game.moves()[number - 1].action();
+                    self.set_status(&format!("Went to move {} ({})", number, action));
+                }
+            }
+            "undolist" => self.show_undo_list(),
             "solve" => {
                 if !self.game.solve() {
                     self.set_status("Current board has no solution in this state");
                 }
                 self.check_solved();
             }
+            "w" => {
+                let file = match args.first() {
+                    Some(file) => PathBuf::from(file),
+                    None => match self.file {
+                        Some(ref file) => file.clone(),
+                        None => bail!("must give a file to save the game to"),
+                    },
+                };
+                self.update_clock();
+                self.game.save(&file)?;
+                self.set_status(&format!("Saved game to {}", file.display()));
+                self.file = Some(file);
+            }
             s => self.set_status(&format!("Unknown command '{}'", s)),
         }
 
@@ -372,27 +867,36 @@ impl<'a> Game<'a> {
     /// Draws everything in the TUI.
     fn draw_all(&mut self) {
         self.draw_sudoku();
-        if self.show_annotations {
+        if self.show_annotations && !self.large_cells() {
             self.draw_annotations();
         }
         self.draw_status()
     }
 
     /// Draws the annotations window and its contents. This should only be used if annotations are
-    /// enabled.
+    /// enabled, and aren't already shown inside the cells (see `large_cells`).
     fn draw_annotations(&mut self) {
         assert!(self.show_annotations);
         let (width, height) = termion::terminal_size().unwrap();
-        if width < MIN_WIDTH || height < MIN_HEIGHT {
+        let (min_width, min_height) = self.min_size();
+        if width < min_width || height < min_height {
             return;
         }
-        let grid = Grid(CELL_WIDTH, CELL_HEIGHT);
-        let startpos = (width / 2, height / 2 - grid.height() / 2);
-        // The grid position of the top left corner of the 3x3 block we are currently in
-        let boxpos = (
-            self.game.position().0 / 3 * 3,
-            self.game.position().1 / 3 * 3,
-        );
+        // Each cell of the current box (or jigsaw region) gets its own box in the annotations
+        // window, in the same order as they are found row by row in the grid, with its
+        // annotations arranged in `box_cols` rows of `box_rows` each; this makes the annotations
+        // window exactly the same size as the main grid.
+        let shape = self.game.board().shape();
+        let (box_rows, box_cols) = (shape.box_rows(), shape.box_cols());
+        let grid = Grid::new(shape.transpose());
+        // The top is lined up with the main grid, which may be slightly taller
+        let startpos = (width / 2, height / 2 - Grid::for_board(self.game.board()).height() / 2);
+        // The cells of the region we are currently in
+        let (row, col) = self.game.position();
+        let board = self.game.board();
+        let region = board.region_cells(board.region_index(row, col)).to_vec();
+        let touched = self.replay_touched();
+        let hinted = self.hinted_cells();
 
         // Draw grid
         write!(self.stdout, "{}", cursor::Goto(startpos.0, startpos.1)).unwrap();
@@ -408,48 +912,70 @@ impl<'a> Game<'a> {
             write!(self.stdout, "{}", grid).unwrap();
         }
         // Draw contents
-        for i in 0..9 {
-            for j in 0..9 {
+        for i in 0..shape.size() {
+            for j in 0..shape.size() {
                 // The grid position of the cell whose annotations we should draw
-                let cellpos = (boxpos.0 + i / 3, boxpos.1 + j / 3);
-                // The number of the annotation that we should draw (1-9)
-                let n = (3 * (i % 3) + j % 3 + 1) as u8;
+                let cellpos = region[(i / box_cols) * box_cols + j / box_rows];
+                // The number of the annotation that we should draw
+                let n = ((i % box_cols) * box_rows + j % box_rows + 1) as u8;
 
                 // Highlight filled cell
                 if self.game.board()[cellpos.0][cellpos.1] != 0 {
                     write!(self.stdout, "{}", color::Bg(COLOR_FILLED)).unwrap();
                 }
-                // Highlight selected cell
+                // Highlight selected cells, then the current one
+                if self.game.is_selected(cellpos.0, cellpos.1) {
+                    write!(self.stdout, "{}", color::Bg(COLOR_MULTI_SELECTION)).unwrap();
+                }
                 if cellpos == self.game.position() {
                     write!(self.stdout, "{}", color::Bg(COLOR_SELECTION)).unwrap();
                 }
-                // Highlight hinted cell
-                if Some(cellpos) == self.hintpos {
+                // Highlight hinted cells
+                if Some(cellpos) == self.hintpos || hinted.contains(&cellpos) {
                     write!(self.stdout, "{}", color::Bg(COLOR_HINT)).unwrap();
                 }
+                // Highlight the cell changed by the last move of a replay
+                if Some(cellpos) == touched {
+                    write!(self.stdout, "{}", color::Bg(COLOR_REPLAY)).unwrap();
+                }
                 // Change background color if solved
                 if self.game.is_solved() {
                     write!(self.stdout, "{}", color::Bg(COLOR_SOLVED)).unwrap();
                 }
 
-                if self.game.annotations()[cellpos.0][cellpos.1][n] {
-                    self.draw_in_grid(
-                        char::from_digit(n as u32, 10).unwrap(),
-                        (i as u16, j as u16),
-                        startpos,
-                    );
+                // Corner marks are shown in another color, and in bold if they are also
+                // annotations
+                let annotated = self.game.annotations()[cellpos.0][cellpos.1][n];
+                let marked = self.game.corner_marks()[cellpos.0][cellpos.1][n];
+                if marked {
+                    write!(self.stdout, "{}", color::Fg(COLOR_CORNER_MARK)).unwrap();
+                    if annotated {
+                        write!(self.stdout, "{}", style::Bold).unwrap();
+                    }
+                }
+
+                if annotated || marked {
+                    self.draw_in_grid(sudoku::digit_char(n), (i as u16, j as u16), startpos, &grid);
                 } else {
-                    self.draw_in_grid('.', (i as u16, j as u16), startpos);
+                    self.draw_in_grid('.', (i as u16, j as u16), startpos, &grid);
                 }
 
-                write!(self.stdout, "{}", color::Bg(color::Reset)).unwrap();
+                write!(
+                    self.stdout,
+                    "{}{}{}",
+                    style::Reset,
+                    color::Fg(color::Reset),
+                    color::Bg(color::Reset)
+                ).unwrap();
             }
         }
     }
 
-    /// Draws the status line.
+    /// Draws the status line, with the time spent playing (and the number of mistakes made, if
+    /// the counter is turned on) at the right, except in a replay, where the status gives the time
+    /// of each move instead.
     fn draw_status(&mut self) {
-        let (_, height) = termion::terminal_size().unwrap();
+        let (width, height) = termion::terminal_size().unwrap();
         if height < MIN_HEIGHT {
             return;
         }
@@ -460,16 +986,38 @@ impl<'a> Game<'a> {
             clear::CurrentLine,
             self.status
         ).unwrap();
+        if self.replay.is_none() {
+            let mut clock = util::format_duration(self.game.elapsed() + self.unrecorded_time());
+            if self.show_counter {
+                clock = format!("{}  {}", count(self.game.mistakes() as usize, "mistake"), clock);
+            }
+            // The status takes priority if there isn't room for both
+            if self.status.chars().count() + clock.len() < width as usize {
+                let column = width - clock.len() as u16 + 1;
+                write!(self.stdout, "{}{}", cursor::Goto(column, height), clock).unwrap();
+            }
+        }
     }
 
     /// Draws the Sudoku grid (and its contents) to the correct location.
     fn draw_sudoku(&mut self) {
         let (width, height) = termion::terminal_size().unwrap();
-        if width < MIN_WIDTH || height < MIN_HEIGHT {
+        let (min_width, min_height) = self.min_size();
+        if width < min_width || height < min_height {
             return;
         }
-        let grid = Grid(CELL_WIDTH, CELL_HEIGHT);
-        let startpos = if self.show_annotations {
+        let size = self.game.board().size();
+        let large = self.large_cells();
+        let grid = if large {
+            Grid::large_for_board(self.game.board())
+        } else {
+            Grid::for_board(self.game.board())
+        };
+        // The cells of any units besides the rows, columns and boxes
+        let extra_cells = self.game.board().units()[3 * size..].concat();
+        let touched = self.replay_touched();
+        let hinted = self.hinted_cells();
+        let startpos = if self.show_annotations && !large {
             (width / 2 - grid.width(), height / 2 - grid.height() / 2)
         } else {
             (width / 2 - grid.width() / 2, height / 2 - grid.height() / 2)
@@ -489,65 +1037,579 @@ impl<'a> Game<'a> {
             write!(self.stdout, "{}", grid).unwrap();
         }
         // Draw contents
-        for i in 0..9 {
-            for j in 0..9 {
+        for i in 0..size {
+            for j in 0..size {
                 // Bold given entries
                 if self.game.given()[i][j] != 0 {
                     write!(self.stdout, "{}", style::Bold).unwrap();
                 }
-                // Highlight selection
+                // Shade the cells of extra units
+                if extra_cells.contains(&(i, j)) {
+                    write!(self.stdout, "{}", color::Bg(COLOR_EXTRA_UNIT)).unwrap();
+                }
+                // Highlight selection, with the current position standing out from the rest
+                if self.game.is_selected(i, j) {
+                    write!(self.stdout, "{}", color::Bg(COLOR_MULTI_SELECTION)).unwrap();
+                }
                 if (i, j) == self.game.position() {
                     write!(self.stdout, "{}", color::Bg(COLOR_SELECTION)).unwrap();
                 }
                 // Highlight most recent hint
-                if Some((i, j)) == self.hintpos {
+                if Some((i, j)) == self.hintpos || hinted.contains(&(i, j)) {
                     write!(self.stdout, "{}", color::Bg(COLOR_HINT)).unwrap();
                 }
+                // Highlight the cell changed by the last move of a replay
+                if Some((i, j)) == touched {
+                    write!(self.stdout, "{}", color::Bg(COLOR_REPLAY)).unwrap();
+                }
+                // Mistakes are shown in another color, so that the highlighting above still shows
+                if self.game.is_mistake_at(i, j, self.check) {
+                    write!(self.stdout, "{}", color::Fg(COLOR_MISTAKE)).unwrap();
+                }
                 // Change background color if solved
                 if self.game.is_solved() {
                     write!(self.stdout, "{}", color::Bg(COLOR_SOLVED)).unwrap();
                 }
 
                 if self.game.board()[i][j] != 0 {
-                    let c = char::from_digit(self.game.board()[i][j] as u32, 10).unwrap();
-                    self.draw_in_grid(c, (i as u16, j as u16), startpos);
+                    let c = sudoku::digit_char(self.game.board()[i][j]);
+                    self.draw_in_grid(c, (i as u16, j as u16), startpos, &grid);
+                } else if large {
+                    self.draw_in_grid(' ', (i as u16, j as u16), startpos, &grid);
+                    self.draw_candidates((i, j), startpos, &grid);
                 } else {
-                    self.draw_in_grid('.', (i as u16, j as u16), startpos);
+                    self.draw_in_grid('.', (i as u16, j as u16), startpos, &grid);
                 }
 
-                write!(self.stdout, "{}{}", style::Reset, color::Bg(color::Reset)).unwrap();
+                write!(
+                    self.stdout,
+                    "{}{}{}",
+                    style::Reset,
+                    color::Fg(color::Reset),
+                    color::Bg(color::Reset)
+                ).unwrap();
             }
         }
     }
 
-    /// Draws the given character at cell position `position` (relative to a `Grid`) with the given
-    /// offset.
-    fn draw_in_grid(&mut self, c: char, position: (u16, u16), offset: (u16, u16)) {
+    /// Draws the annotations and corner marks of the empty cell at the given grid position inside
+    /// the cell itself, which must be large enough to hold them (see `Grid::large_for_board`). They
+    /// are arranged in the same way as in the annotations window, with corner marks in another
+    /// color (and in bold if they are also annotations), and the rest of the style is left as it
+    /// is.
+    fn draw_candidates(&mut self, position: (usize, usize), offset: (u16, u16), grid: &Grid) {
+        let (row, col) = position;
+        let size = self.game.board().size();
+        let relpos = grid.cell_position((row as u16, col as u16));
+        let annotations = self.game.annotations()[row][col];
+        let corner_marks = self.game.corner_marks()[row][col];
+        // Plain annotations are drawn first, then corner marks, then numbers which are both, so
+        // that each only adds to the style of the one before and it never has to be reset
+        let kinds = [(true, false), (false, true), (true, true)];
+        for &(annotated, marked) in &kinds {
+            if marked {
+                write!(self.stdout, "{}", color::Fg(COLOR_CORNER_MARK)).unwrap();
+                if annotated {
+                    write!(self.stdout, "{}", style::Bold).unwrap();
+                }
+            }
+            for n in 1..=size as u8 {
+                if annotations[n] != annotated || corner_marks[n] != marked {
+                    continue;
+                }
+                let (x, y) = grid.candidate_position(n);
+                write!(
+                    self.stdout,
+                    "{}{}",
+                    cursor::Goto(offset.0 + relpos.0 + x, offset.1 + relpos.1 + y),
+                    sudoku::digit_char(n)
+                ).unwrap();
+            }
+        }
+    }
+
+    /// Draws the given character in the middle of the cell at position `position` (relative to
+    /// `grid`) with the given offset.
+    fn draw_in_grid(&mut self, c: char, position: (u16, u16), offset: (u16, u16), grid: &Grid) {
         // Compute the position of this cell, relative to `offset`
-        let relpos = (
-            CELL_WIDTH * position.1 + position.1 / 3 + 1,
-            CELL_HEIGHT * position.0 + position.0 / 3 + 1,
-        );
-        write!(
-            self.stdout,
-            "{}",
-            cursor::Goto(offset.0 + relpos.0, offset.1 + relpos.1)
-        ).unwrap();
-        // We pad with spaces so that background colors can be applied.
-        for _ in 0..CELL_WIDTH / 2 {
-            write!(self.stdout, " ").unwrap();
+        let relpos = grid.cell_position(position);
+        // We pad every line of the cell with spaces so that background colors can be applied.
+        for line in 0..grid.cell_height {
+            write!(
+                self.stdout,
+                "{}",
+                cursor::Goto(offset.0 + relpos.0, offset.1 + relpos.1 + line)
+            ).unwrap();
+            for k in 0..grid.cell_width {
+                if line == grid.cell_height / 2 && k == grid.cell_width / 2 {
+                    write!(self.stdout, "{}", c).unwrap();
+                } else {
+                    write!(self.stdout, " ").unwrap();
+                }
+            }
+        }
+    }
+
+    /// Moves the current position by the given amount in each direction (see
+    /// `game::Game::move_by`). If `extend` is set, both the old and new positions are added to the
+    /// selection.
+    fn move_cursor(&mut self, rows: isize, cols: isize, extend: bool) {
+        if extend {
+            let (row, col) = self.game.position();
+            self.game.select(row, col);
+        }
+        self.game.move_by(rows, cols);
+        if extend {
+            let (row, col) = self.game.position();
+            self.game.select(row, col);
+        }
+    }
+
+    /// Returns the next key pressed, ignoring any other events.
+    fn next_key(&self) -> Key {
+        loop {
+            if let Event::Key(key) = self.events.recv().unwrap() {
+                return key;
+            }
+        }
+    }
+
+    /// Returns the number given by the key, if it is a valid entry for the current board.
+    fn key_number(&self, key: Key) -> Option<u8> {
+        match key {
+            Key::Char(c) => {
+                sudoku::parse_digit(c).filter(|&n| n as usize <= self.game.board().size())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a description of the numbers which can be entered on the current board, such as
+    /// `1-9` or `1-9, A-G`.
+    fn number_range(&self) -> String {
+        let size = self.game.board().size() as u8;
+        if size <= 9 {
+            format!("1-{}", size)
+        } else {
+            format!("1-9, A-{}", sudoku::digit_char(size))
         }
-        write!(self.stdout, "{}", c).unwrap();
-        for _ in 0..CELL_WIDTH / 2 {
-            write!(self.stdout, " ").unwrap();
+    }
+
+    /// Returns whether the annotations should be shown inside the cells of the main grid rather
+    /// than in a separate window, which is done whenever they are turned on and the terminal is
+    /// big enough for a grid with large cells (see `Grid::large_for_board`).
+    fn large_cells(&self) -> bool {
+        if !self.show_annotations {
+            return false;
+        }
+        let (width, height) = termion::terminal_size().unwrap();
+        let grid = Grid::large_for_board(self.game.board());
+        width >= cmp::max(MIN_WIDTH, grid.width() + 2)
+            && height >= cmp::max(MIN_HEIGHT, grid.height() + 2)
+    }
+
+    /// Returns the minimum size of the terminal, as `(width, height)`, needed to draw the game.
+    fn min_size(&self) -> (u16, u16) {
+        let grid = Grid::for_board(self.game.board());
+        let grids = if self.show_annotations { 2 } else { 1 };
+        (
+            cmp::max(MIN_WIDTH, grids * grid.width() + 2),
+            cmp::max(MIN_HEIGHT, grid.height() + 2),
+        )
+    }
+
+    /// Puts the number given by the key at the current position (or every selected one), if it is
+    /// valid. Like any other change, this leaves visual mode.
+    fn put_key(&mut self, key: Key) {
+        self.visual = false;
+        match self.key_number(key) {
+            Some(n) => {
+                self.game.put(n);
+                self.check_solved();
+            }
+            None => {
+                let status = format!(
+                    "Must enter a number ({}) to fill a cell",
+                    self.number_range()
+                );
+                self.set_status(&status);
+            }
+        }
+    }
+
+    /// Gives the next part of a hint: the technique to look for and where, then the cells involved
+    /// and finally the number, which is placed. A new hint is found once the board changes.
+    fn hint(&mut self) -> Result<()> {
+        let after = self.game.current_move();
+        let mut partial = match self.hint.take() {
+            Some(ref partial) if partial.after != after => None,
+            partial => partial,
+        };
+        match partial {
+            None => match self.game.next_hint()? {
+                None => self.set_status("Current board is already solved"),
+                Some(hint) => {
+                    self.set_status(&format!("Hint: {}", hint.clue()));
+                    partial = Some(PartialHint {
+                        hint,
+                        after,
+                        shown_cells: false,
+                    });
+                }
+            },
+            Some(ref mut partial) if !partial.shown_cells => {
+                partial.shown_cells = true;
+                self.set_status(&format!("Hint: {}", partial.hint.describe_cells()));
+            }
+            Some(partial) => {
+                let (row, col, n) = partial.hint.placement();
+                self.game.perform(game::Action::Hint(n, (row, col)));
+                self.hintpos = Some((row, col));
+                self.set_status(&format!("Hint: {}", partial.hint.explain()));
+                self.check_solved();
+                return Ok(());
+            }
+        }
+        self.hint = partial;
+        Ok(())
+    }
+
+    /// Returns the cells involved in the hint being given, once they have been shown (see `hint`).
+    fn hinted_cells(&self) -> Vec<(usize, usize)> {
+        match self.hint {
+            Some(ref partial)
+                if partial.shown_cells && partial.after == self.game.current_move() => {
+                partial.hint.cells()
+            }
+            _ => Vec::new(),
         }
     }
 
     /// Checks if the current board has been solved and updates the status accordingly if so.
     fn check_solved(&mut self) {
+        if !self.game.is_solved() {
+            return;
+        }
+        let status = match self.game.completion() {
+            Some(c) if c.solver() => format!(
+                "Solved the board using :solve after {}",
+                util::format_duration(c.time())
+            ),
+            Some(c) => format!(
+                "Congratulations, you win! Solved in {} with {}, {} and {}",
+                util::format_duration(c.time()),
+                count(c.hints() as usize, "hint"),
+                count(c.undos() as usize, "undo"),
+                count(c.mistakes() as usize, "mistake")
+            ),
+            None => "Congratulations, you win!".into(),
+        };
+        self.set_status(&status);
+    }
+
+    /// Saves the game to the autosave file, if there is one, or removes the file if the game has
+    /// been solved (since there's nothing left to resume). A game which has just been solved is
+    /// also added to the statistics file.
+    fn autosave(&mut self) -> Result<()> {
+        self.autosaved = autosave_state(&self.game);
+        self.update_clock();
+        if !self.recorded && self.game.completion().is_some() {
+            self.recorded = true;
+            stats::record(&self.game)?;
+        }
+        if let Some(ref file) = self.record {
+            self.game.save(file)?;
+        }
+        let file = match autosave_path(&self.game) {
+            Some(file) if self.autosave => file,
+            _ => return Ok(()),
+        };
+        if self.game.is_solved() {
+            if file.exists() {
+                fs::remove_file(&file).chain_err(|| {
+                    format!("could not remove autosave `{}`", file.display())
+                })?;
+            }
+            return Ok(());
+        }
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).chain_err(|| {
+                format!("could not create directory `{}`", dir.display())
+            })?;
+        }
+        self.game.save(&file)
+    }
+
+    /// Saves the game automatically (see `autosave`) if it has changed since it was last saved or
+    /// loaded, which only happens when a move is made or undone (or a setting which is saved along
+    /// with the game is changed), and not when moving around the board, for example.
+    fn autosave_if_changed(&mut self) -> Result<()> {
+        if autosave_state(&self.game) == self.autosaved {
+            return Ok(());
+        }
+        self.autosave()
+    }
+
+    /// Asks the user whether to quit an unsolved game, returning `true` if the game should exit.
+    /// A solved game (or a replay) is quit without asking.
+    fn confirm_quit(&mut self) -> bool {
+        if self.game.is_solved() || self.replay.is_some() {
+            return true;
+        }
+        let question = if self.autosave && autosave_path(&self.game).is_some() {
+            "Quit without solving? The game will be saved for next time. (y/n)"
+        } else {
+            "Quit without solving? (y/n)"
+        };
+        self.set_status(question);
+        self.draw_status();
+        self.stdout.flush().unwrap();
+        let quit = matches!(self.next_key(), Key::Char('y') | Key::Char('Y'));
+        self.set_status("");
+        quit
+    }
+
+    /// Processes keyboard input while replaying a game, returning whether the replay should exit.
+    fn input_replay_key(&mut self, key: Key) -> bool {
+        let moves = self.game.moves().len();
+        let replay = self.replay.as_mut().unwrap();
+        match key {
+            Key::Char('q') => return true,
+            Key::Char(' ') | Key::Char('p') => {
+                replay.paused = !replay.paused;
+                // Starting again at the end shows the whole replay again
+                if !replay.paused && replay.step == moves {
+                    self.replay_go_to(0);
+                }
+            }
+            Key::Char('l') | Key::Right => {
+                replay.paused = true;
+                self.replay_step(true);
+            }
+            Key::Char('h') | Key::Left => {
+                replay.paused = true;
+                self.replay_step(false);
+            }
+            Key::Char('+') | Key::Char('=') => {
+                replay.speed = (replay.speed * 2.0).min(REPLAY_SPEEDS.1)
+            }
+            Key::Char('-') => replay.speed = (replay.speed / 2.0).max(REPLAY_SPEEDS.0),
+            Key::Char('0') | Key::Char('g') => self.replay_go_to(0),
+            Key::Char('$') | Key::Char('G') => self.replay_go_to(moves),
+            _ => {}
+        }
+        self.replay_status();
+        false
+    }
+
+    /// Returns how long to wait before showing the next move of the replay, or `None` if there is
+    /// no replay in progress.
+    fn replay_delay(&self) -> Option<Duration> {
+        let replay = match self.replay {
+            Some(ref replay) if !replay.paused => replay,
+            _ => return None,
+        };
+        let moves = self.game.moves();
+        let next = moves.get(replay.step)?.time();
+        let last = match replay.step {
+            0 => Duration::from_secs(0),
+            step => moves[step - 1].time(),
+        };
+        // The moves of a game which was saved and resumed might not be in order of time
+        let pause = next.checked_sub(last).unwrap_or_default().as_secs_f64();
+        Some(Duration::from_secs_f64(pause.min(REPLAY_MAX_PAUSE) / replay.speed))
+    }
+
+    /// Shows the replay right after the given number of moves.
+    fn replay_go_to(&mut self, step: usize) {
+        self.game.jump_to(step.checked_sub(1));
+        self.replay.as_mut().unwrap().step = step;
+        if let Some(pos) = self.replay_touched() {
+            self.game.set_position(pos.0, pos.1);
+        }
+        self.replay_status();
+    }
+
+    /// Shows the next move of the replay, or goes back one move if `forward` isn't set.
+    fn replay_step(&mut self, forward: bool) {
+        let step = self.replay.as_ref().unwrap().step;
+        if forward && step < self.game.moves().len() {
+            self.replay_go_to(step + 1);
+        } else if !forward && step > 0 {
+            self.replay_go_to(step - 1);
+        }
+    }
+
+    /// Describes the last move shown in the replay in the status line.
+    fn replay_status(&mut self) {
+        let (step, speed, paused) = match self.replay {
+            Some(ref r) => (r.step, r.speed, r.paused),
+            None => return,
+        };
+        let moves = self.game.moves();
+        let mut status = match step {
+            0 => format!("Start of replay ({} moves)", moves.len()),
+            _ => {
+                let m = &moves[step - 1];
+                let mut s = format!(
+                    "Move {}/{} ({}): {}",
+                    step,
+                    moves.len(),
+                    util::format_duration(m.time()),
+                    m.action()
+                );
+                // Point out where the player undid some moves before making this one
+                match m.parent() {
+                    p if p == (step - 1).checked_sub(1) => {}
+                    None => s += ", after undoing to the start",
+                    Some(p) => s += &format!(", after undoing to move {}", p + 1),
+                }
+                s
+            }
+        };
+        status += &format!(" [{}x", speed);
+        if paused {
+            status += ", paused";
+        } else if step == moves.len() {
+            status += ", finished";
+        }
+        status += "]";
+        self.set_status(&status);
+    }
+
+    /// Returns the cell changed by the last move shown in the replay, if any.
+    fn replay_touched(&self) -> Option<(usize, usize)> {
+        match self.replay {
+            Some(Replay { step, .. }) if step > 0 => {
+                self.game.moves()[step - 1].action().position()
+            }
+            _ => None,
+        }
+    }
+
+    /// Shows the branches of the undo tree, each as the number of the last move on it, the number
+    /// of moves leading to it and the last action, until a key is pressed.
+    fn show_undo_list(&mut self) {
+        let (_, height) = termion::terminal_size().unwrap();
+        let moves = self.game.moves();
+        let mut text = String::from("UNDO TREE BRANCHES\n\nMOVE    MOVES   LAST ACTION\n");
+        // Only the most recent branches are shown if there are too many to fit on the screen
+        let branches = self.game.branches();
+        let shown = (height as usize).saturating_sub(9).max(1);
+        for &i in &branches[branches.len().saturating_sub(shown)..] {
+            let depth = iter::successors(Some(i), |&m| moves[m].parent()).count();
+            text += &format!("{:<8}{:<8}{}\n", i + 1, depth, moves[i].action());
+        }
+        text += &match self.game.current_move() {
+            None => "\nThe board is at the start".to_string(),
+            Some(i) => format!(
+                "\nThe board is right after move {} ({})",
+                i + 1,
+                moves[i].action()
+            ),
+        };
+        text += "\nUse :undo-branch <move> to go to the board right after any move\n";
+        self.show_page(&text);
+    }
+
+    /// Undoes (or redoes, if `redo` is set) up to `count` actions, and describes what was done in
+    /// the status line.
+    fn undo(&mut self, count: usize, redo: bool) {
+        let mut done = Vec::new();
+        while done.len() < count {
+            let action = if redo {
+                self.game.redo_action()
+            } else {
+                self.game.undo_action()
+            };
+            match action {
+                None => break,
+                Some(action) => done.push(action),
+            }
+            if redo {
+                self.game.redo();
+            } else {
+                self.game.undo();
+            }
+        }
+
+        let (verb, noun) = if redo { ("Redid", "redo") } else { ("Undid", "undo") };
+        let mut status = match done.len() {
+            0 => format!("Nothing to {}", noun),
+            1 => format!("{} {}", verb, done[0]),
+            n => format!("{} {} actions (the last was {})", verb, n, done[n - 1]),
+        };
+        if !done.is_empty() && done.len() < count {
+            status += &format!("; nothing more to {}", noun);
+        }
+        self.set_status(&status);
+    }
+
+    /// Adds the time spent playing since the clock was last updated to the game. The clock stops
+    /// once the game is solved.
+    fn update_clock(&mut self) {
+        self.game.add_elapsed(self.unrecorded_time());
+        self.clock = Instant::now();
+    }
+
+    /// Returns the time spent playing since the clock was last updated, which hasn't been added
+    /// to the game yet.
+    fn unrecorded_time(&self) -> Duration {
         if self.game.is_solved() {
-            self.set_status("Congratulations, you win!");
+            Duration::from_secs(0)
+        } else {
+            self.clock.elapsed()
+        }
+    }
+
+    /// Hides the board and stops the clock until a key is pressed.
+    fn pause(&mut self) {
+        self.update_clock();
+        self.show_page("GAME PAUSED\n\nThe board is hidden and the clock is stopped.\n");
+        self.clock = Instant::now();
+        self.set_status("Continued the game");
+    }
+
+    /// Suspends the program, as CTRL-Z normally would outside of raw mode. The clock is stopped
+    /// until the program is continued.
+    fn suspend(&mut self) {
+        self.update_clock();
+        write!(self.stdout, "{}{}{}", clear::All, cursor::Goto(1, 1), cursor::Show).unwrap();
+        self.stdout.flush().unwrap();
+        // This doesn't return until the program is continued (such as by `fg` in the shell),
+        // which restores the terminal settings of the game as well
+        unsafe {
+            libc::raise(libc::SIGTSTP);
+        }
+        self.clock = Instant::now();
+        write!(self.stdout, "{}{}", clear::All, cursor::Hide).unwrap();
+    }
+
+    /// Sets the options given to the `:set` command, each of which is either `name=value` or (for
+    /// an option which is on or off) `name` or `noname`, and then shows all the options.
+    fn set_options(&mut self, args: &[&str]) -> Result<()> {
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("check", mode)) => self.check = mode.parse()?,
+                Some(_) => bail!("unknown option `{}`", arg),
+                None if *arg == "counter" => self.show_counter = true,
+                None if *arg == "nocounter" => self.show_counter = false,
+                None if *arg == "autoprune" => self.game.set_autoprune(true),
+                None if *arg == "noautoprune" => self.game.set_autoprune(false),
+                None => bail!("unknown option `{}`", arg),
+            }
+        }
+        let mut status = format!(
+            "Options: check={} {} {}",
+            self.check,
+            if self.show_counter { "counter" } else { "nocounter" },
+            if self.game.autoprune() { "autoprune" } else { "noautoprune" }
+        );
+        if self.check == game::CheckMode::Solution && self.game.solution().is_none() {
+            status += " (the board has no unique solution, so only conflicts are checked)";
         }
+        self.set_status(&status);
+        Ok(())
     }
 
     /// Sets the current game status.
@@ -555,34 +1617,35 @@ impl<'a> Game<'a> {
         self.status = status.into();
     }
 
-    /// Shows the game help.
-    fn show_help(&mut self) -> Result<()> {
+    /// Shows the given text (such as the game help) in place of the game until a key is pressed.
+    fn show_page(&mut self, text: &str) {
         let (_, height) = termion::terminal_size().unwrap();
         write!(
             self.stdout,
-            "{}{}{}{}{}{}",
+            "{}{}{}{}{}(press any key to close)",
             cursor::Hide,
             clear::All,
             cursor::Goto(1, 1),
             // Since we're in raw mode, '\n' only means "move one row down"
-            HELP.replace('\n', "\r\n"),
-            cursor::Goto(1, height),
-            "(press any key to close help)"
+            text.replace('\n', "\r\n"),
+            cursor::Goto(1, height)
         ).unwrap();
         self.stdout.flush().unwrap();
 
         // Wait for a key
-        self.keys.recv().unwrap();
+        self.next_key();
 
         write!(self.stdout, "{}{}", clear::All, cursor::Show).unwrap();
         self.draw_all();
         self.stdout.flush().unwrap();
-        Ok(())
     }
 }
 
 impl<'a> Drop for Game<'a> {
     fn drop(&mut self) {
+        // There's no way to report an error at this point, but the game was saved after the last
+        // move anyway, so at worst the time spent since then is lost
+        let _ = self.autosave();
         // Clean up the terminal display
         write!(
             self.stdout,
@@ -595,82 +1658,347 @@ impl<'a> Drop for Game<'a> {
     }
 }
 
+/// Returns the path of the file where the given game is saved automatically while it is in
+/// progress, or `None` if there is nowhere to put it (see `util::data_dir`). Each puzzle has its
+/// own file, named after its `game::Game::puzzle_id`, so that starting another game never replaces
+/// an unfinished one.
+pub fn autosave_path(game: &game::Game) -> Option<PathBuf> {
+    util::data_dir().map(|d| d.join(AUTOSAVE_DIR).join(format!("{}.txt", game.puzzle_id())))
+}
+
+/// Returns the parts of the given game which change when it needs to be saved automatically again,
+/// as `(number of moves, current move, autoprune setting)`.
+fn autosave_state(game: &game::Game) -> (usize, Option<usize>, bool) {
+    (game.moves().len(), game.current_move(), game.autoprune())
+}
+
+/// Returns the path of the game which was saved automatically most recently (see
+/// `autosave_path`), if there are any.
+pub fn latest_autosave() -> Option<PathBuf> {
+    let dir = util::data_dir()?.join(AUTOSAVE_DIR);
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            Some((entry.metadata().ok()?.modified().ok()?, entry.path()))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Returns a count of something with the given name, such as `1 hint` or `3 hints`.
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
 impl Grid {
+    /// Returns the grid for a sudoku of the given shape, using the default cell dimensions.
+    pub fn new(shape: Shape) -> Self {
+        Grid {
+            cell_width: CELL_WIDTH,
+            cell_height: CELL_HEIGHT,
+            shape,
+            outline: None,
+        }
+    }
+
+    /// Returns the grid to use for drawing the given board: an outlined grid if it has cages or
+    /// jigsaw regions, since they need a border between every cell, and an ordinary grid
+    /// otherwise.
+    pub fn for_board(board: &Sudoku) -> Self {
+        let mut grid = Grid::new(board.shape());
+        if board.cages().is_empty() && !board.is_jigsaw() {
+            return grid;
+        }
+
+        let size = board.size();
+        let cells = iproduct!(0..size, 0..size).collect::<Vec<_>>();
+        grid.outline = Some(Outline {
+            boxes: cells.iter().map(|&(i, j)| board.region_index(i, j)).collect(),
+            thick: board.is_jigsaw(),
+            cages: cells.iter().map(|&(i, j)| board.cage_index(i, j)).collect(),
+            labels: board
+                .cages()
+                .iter()
+                .map(|cage| (cage.cells()[0], cage.sum().to_string()))
+                .collect(),
+        });
+        grid
+    }
+
+    /// Returns the grid to use for drawing the given board (see `for_board`), with cells large
+    /// enough to hold all the numbers which can go in them: each number has its own place, in
+    /// `box_cols` lines of `box_rows` numbers (so the cells of a 9x9 board are 3x3 grids). If
+    /// there is an even number of lines, a blank one is added in the middle, so that the cells
+    /// still have a middle line for the number which fills them (see `candidate_position`).
+    pub fn large_for_board(board: &Sudoku) -> Self {
+        let shape = board.shape();
+        let mut grid = Grid::for_board(board);
+        grid.cell_width = 2 * shape.box_rows() as u16 + 1;
+        grid.cell_height = shape.box_cols() as u16 | 1;
+        grid
+    }
+
+    /// Returns the position (as `(column, row)`, relative to the top left corner of a cell) of
+    /// the given number within a cell of a grid with large cells (see `large_for_board`).
+    pub fn candidate_position(&self, n: u8) -> (u16, u16) {
+        let box_rows = self.shape.box_rows() as u16;
+        let (line, place) = ((u16::from(n) - 1) / box_rows, (u16::from(n) - 1) % box_rows);
+        // Skip the blank middle line, if there is one
+        let lines = self.shape.box_cols() as u16;
+        let line = if lines.is_multiple_of(2) && line >= lines / 2 {
+            line + 1
+        } else {
+            line
+        };
+        (2 * place + 1, line)
+    }
+
     pub fn height(&self) -> u16 {
-        4 + 9 * self.1
+        let (size, box_rows) = (self.shape.size() as u16, self.shape.box_rows() as u16);
+        match self.outline {
+            Some(_) => 1 + size * (self.cell_height + 1),
+            None => 1 + size / box_rows + size * self.cell_height,
+        }
     }
 
     pub fn width(&self) -> u16 {
-        4 + 9 * self.0
+        let (size, box_cols) = (self.shape.size() as u16, self.shape.box_cols() as u16);
+        match self.outline {
+            Some(_) => 1 + size * (self.cell_width + 1),
+            None => 1 + size / box_cols + size * self.cell_width,
+        }
+    }
+
+    /// Returns the screen position (as `(column, row)`, relative to the top left corner of the
+    /// grid) of the cell at the given grid position.
+    pub fn cell_position(&self, position: (u16, u16)) -> (u16, u16) {
+        let (box_rows, box_cols) = (self.shape.box_rows() as u16, self.shape.box_cols() as u16);
+        match self.outline {
+            Some(_) => (
+                (self.cell_width + 1) * position.1 + 1,
+                (self.cell_height + 1) * position.0 + 1,
+            ),
+            None => (
+                self.cell_width * position.1 + position.1 / box_cols + 1,
+                self.cell_height * position.0 + position.0 / box_rows + 1,
+            ),
+        }
+    }
+
+    /// Writes an outlined grid (see `Outline`), one line at a time.
+    fn fmt_outlined(&self, f: &mut Formatter, outline: &Outline) -> FmtResult {
+        let size = self.shape.size();
+        for row in 0..size + 1 {
+            // The border above the row
+            let mut line = String::new();
+            for col in 0..size + 1 {
+                line.push(outline.junction(size, row, col));
+                if col < size {
+                    let c = match outline.horizontal(size, row, col) {
+                        Border::Empty => ' ',
+                        Border::Cage => '┄',
+                        Border::Region if outline.thick => '━',
+                        Border::Region => '─',
+                        Border::Edge => '═',
+                    };
+                    for _ in 0..self.cell_width {
+                        line.push(c);
+                    }
+                }
+            }
+            // Labels replace part of the border
+            let mut line = line.chars().collect::<Vec<_>>();
+            for &((i, j), ref label) in &outline.labels {
+                if i == row {
+                    let start = j * (self.cell_width as usize + 1) + 1;
+                    for (k, c) in label.chars().enumerate() {
+                        line[start + k] = c;
+                    }
+                }
+            }
+            write!(f, "{}", line.into_iter().collect::<String>())?;
+            if row == size {
+                break;
+            }
+            write!(f, "{}{}", cursor::Down(1), cursor::Left(self.width()))?;
+
+            // The row itself, whose cells are left alone
+            for _ in 0..self.cell_height {
+                for col in 0..size + 1 {
+                    let c = match outline.vertical(size, row, col) {
+                        Border::Empty => ' ',
+                        Border::Cage => '┆',
+                        Border::Region if outline.thick => '┃',
+                        Border::Region => '│',
+                        Border::Edge => '║',
+                    };
+                    write!(f, "{}", c)?;
+                    if col < size {
+                        write!(f, "{}", cursor::Right(self.cell_width))?;
+                    }
+                }
+                write!(f, "{}{}", cursor::Down(1), cursor::Left(self.width()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a horizontal line across the whole grid, using `fill` for the line itself and
+    /// `cross` where it meets the line between two boxes.
+    fn fmt_line(
+        &self,
+        f: &mut Formatter,
+        left: char,
+        fill: char,
+        cross: char,
+        right: char,
+    ) -> FmtResult {
+        let boxes = self.shape.size() / self.shape.box_cols();
+        let box_width = self.shape.box_cols() as u16 * self.cell_width;
+        write!(f, "{}", left)?;
+        for b in 0..boxes {
+            for _ in 0..box_width {
+                write!(f, "{}", fill)?;
+            }
+            write!(f, "{}", if b == boxes - 1 { right } else { cross })?;
+        }
+        Ok(())
     }
 }
 
 impl Display for Grid {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        // Top row
-        write!(f, "╔")?;
-        for _ in 0..2 {
-            for _ in 0..3 * self.0 {
-                write!(f, "═")?;
-            }
-            write!(f, "╤")?;
-        }
-        for _ in 0..3 * self.0 {
-            write!(f, "═")?;
+        if let Some(ref outline) = self.outline {
+            return self.fmt_outlined(f, outline);
         }
-        write!(f, "╗")?;
+        let (box_rows, box_cols) = (self.shape.box_rows(), self.shape.box_cols());
+        // The number of boxes across and down the grid
+        let (across, down) = (self.shape.size() / box_cols, self.shape.size() / box_rows);
+        let box_width = box_cols as u16 * self.cell_width;
+
+        // Top row
+        self.fmt_line(f, '╔', '═', '╤', '╗')?;
         write!(f, "{}{}", cursor::Down(1), cursor::Left(self.width()))?;
 
         // Middle section
-        for _ in 0..2 {
-            for _ in 0..3 * self.1 {
+        for band in 0..down {
+            for _ in 0..box_rows as u16 * self.cell_height {
                 write!(f, "║")?;
-                for _ in 0..2 {
-                    write!(f, "{}", cursor::Right(3 * self.0))?;
-                    write!(f, "│")?;
+                for b in 0..across {
+                    write!(f, "{}", cursor::Right(box_width))?;
+                    write!(f, "{}", if b == across - 1 { '║' } else { '│' })?;
                 }
-                write!(f, "{}", cursor::Right(3 * self.0))?;
-                write!(f, "║")?;
                 write!(f, "{}{}", cursor::Down(1), cursor::Left(self.width()))?;
             }
             // Divider row
-            write!(f, "╟")?;
-            for _ in 0..2 {
-                for _ in 0..3 * self.0 {
-                    write!(f, "─")?;
-                }
-                write!(f, "┼")?;
-            }
-            for _ in 0..3 * self.0 {
-                write!(f, "─")?;
-            }
-            write!(f, "╢")?;
-            write!(f, "{}{}", cursor::Down(1), cursor::Left(self.width()))?;
-        }
-        for _ in 0..3 * self.1 {
-            write!(f, "║")?;
-            for _ in 0..2 {
-                write!(f, "{}", cursor::Right(3 * self.0))?;
-                write!(f, "│")?;
+            if band != down - 1 {
+                self.fmt_line(f, '╟', '─', '┼', '╢')?;
+                write!(f, "{}{}", cursor::Down(1), cursor::Left(self.width()))?;
             }
-            write!(f, "{}", cursor::Right(3 * self.0))?;
-            write!(f, "║")?;
-            write!(f, "{}{}", cursor::Down(1), cursor::Left(self.width()))?;
         }
 
         // Bottom row
-        write!(f, "╚")?;
-        for _ in 0..2 {
-            for _ in 0..3 * self.0 {
-                write!(f, "═")?;
+        self.fmt_line(f, '╚', '═', '╧', '╝')?;
+
+        Ok(())
+    }
+}
+
+impl Outline {
+    /// Returns the border between the two given cells, either of which may be `None` to stand
+    /// for the edge of a grid of the given size.
+    fn border(
+        &self,
+        size: usize,
+        a: Option<(usize, usize)>,
+        b: Option<(usize, usize)>,
+    ) -> Border {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                let (a, b) = (a.0 * size + a.1, b.0 * size + b.1);
+                if self.boxes[a] != self.boxes[b] {
+                    Border::Region
+                } else if self.cages[a] != self.cages[b] {
+                    Border::Cage
+                } else {
+                    Border::Empty
+                }
             }
-            write!(f, "╧")?;
-        }
-        for _ in 0..3 * self.0 {
-            write!(f, "═")?;
+            _ => Border::Edge,
         }
-        write!(f, "╝")?;
+    }
 
-        Ok(())
+    /// Returns the border above the cell at the given position (where `row` may be `size`, for
+    /// the bottom edge).
+    fn horizontal(&self, size: usize, row: usize, col: usize) -> Border {
+        let above = if row > 0 { Some((row - 1, col)) } else { None };
+        let below = if row < size { Some((row, col)) } else { None };
+        self.border(size, above, below)
+    }
+
+    /// Returns the border to the left of the cell at the given position (where `col` may be
+    /// `size`, for the right edge).
+    fn vertical(&self, size: usize, row: usize, col: usize) -> Border {
+        let left = if col > 0 { Some((row, col - 1)) } else { None };
+        let right = if col < size { Some((row, col)) } else { None };
+        self.border(size, left, right)
+    }
+
+    /// Returns the character to draw where the borders meet at the top left corner of the given
+    /// position.
+    fn junction(&self, size: usize, row: usize, col: usize) -> char {
+        use self::Border::*;
+
+        let up = if row > 0 { self.vertical(size, row - 1, col) } else { Empty };
+        let down = if row < size { self.vertical(size, row, col) } else { Empty };
+        let left = if col > 0 { self.horizontal(size, row, col - 1) } else { Empty };
+        let right = if col < size { self.horizontal(size, row, col) } else { Empty };
+        // Cage borders are too faint to need joining up with the others
+        let solid = |b| if b == Cage { Empty } else { b };
+        let c = match (solid(up), solid(down), solid(left), solid(right)) {
+            (Empty, Edge, Empty, Edge) => '╔',
+            (Empty, Edge, Edge, Empty) => '╗',
+            (Edge, Empty, Empty, Edge) => '╚',
+            (Edge, Empty, Edge, Empty) => '╝',
+            (Empty, Region, Edge, Edge) => '╤',
+            (Region, Empty, Edge, Edge) => '╧',
+            (Edge, Edge, Empty, Region) => '╟',
+            (Edge, Edge, Region, Empty) => '╢',
+            (_, _, Edge, Edge) => '═',
+            (Edge, Edge, _, _) => '║',
+            (Region, Region, Region, Region) => '┼',
+            (Empty, Region, Region, Region) => '┬',
+            (Region, Empty, Region, Region) => '┴',
+            (Region, Region, Empty, Region) => '├',
+            (Region, Region, Region, Empty) => '┤',
+            (Empty, Region, Empty, Region) => '┌',
+            (Empty, Region, Region, Empty) => '┐',
+            (Region, Empty, Empty, Region) => '└',
+            (Region, Empty, Region, Empty) => '┘',
+            (Region, _, _, _) | (_, Region, _, _) => '│',
+            (_, _, Region, _) | (_, _, _, Region) => '─',
+            _ if [up, down, left, right].contains(&Cage) => '·',
+            _ => ' ',
+        };
+        if !self.thick {
+            return c;
+        }
+        // There are no characters joining thick lines to the double lines of the edge, so only
+        // the junctions inside the grid are thickened
+        match c {
+            '┼' => '╋',
+            '┬' => '┳',
+            '┴' => '┻',
+            '├' => '┣',
+            '┤' => '┫',
+            '┌' => '┏',
+            '┐' => '┓',
+            '└' => '┗',
+            '┘' => '┛',
+            '│' => '┃',
+            '─' => '━',
+            c => c,
+        }
     }
 }
@@ -16,6 +16,13 @@
 // along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
 //! Various utility functions.
 
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rand::{self, Rng, SeedableRng, XorShiftRng};
+
 /// Completes `cmd` using the given list (`commands`) of possible commands and returns a `String`
 /// containing the common completion for `cmd`.
 ///
@@ -50,3 +57,78 @@ pub fn complete(commands: &[&str], cmd: &str) -> String {
         )
     }
 }
+
+/// Returns the directory where RSudoku keeps its data (such as autosaved games), following the
+/// XDG Base Directory Specification: `$XDG_DATA_HOME/rsudoku`, or `~/.local/share/rsudoku` if
+/// `XDG_DATA_HOME` isn't set. Returns `None` if the home directory can't be found either. The
+/// directory may not exist yet.
+pub fn data_dir() -> Option<PathBuf> {
+    data_dir_in(env::var_os("XDG_DATA_HOME"), env::var_os("HOME"))
+}
+
+/// Returns the data directory (see `data_dir`) for the given values of `XDG_DATA_HOME` and
+/// `HOME`.
+pub(crate) fn data_dir_in(
+    xdg_data_home: Option<OsString>,
+    home: Option<OsString>,
+) -> Option<PathBuf> {
+    // Relative paths must be ignored, according to the specification
+    let base = xdg_data_home
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| {
+            home.filter(|h| !h.is_empty())
+                .map(|h| PathBuf::from(h).join(".local").join("share"))
+        });
+    base.map(|b| b.join("rsudoku"))
+}
+
+/// Formats a duration as a clock time, such as `4:05`, or `1:02:03` if it's over an hour (rounding
+/// down to the second).
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 3600 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// Returns a new random seed, suitable for passing to `seeded_rng`.
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+/// Returns a random number generator seeded with the given seed. Generators created with the same
+/// seed will always produce the same sequence of numbers, so this can be used to reproduce a
+/// particular puzzle.
+///
+/// # Examples
+///
+/// ```
+/// use rsudoku::Sudoku;
+/// use rsudoku::util::seeded_rng;
+///
+/// assert_eq!(Sudoku::generate_with_rng(&mut seeded_rng(42)),
+///            Sudoku::generate_with_rng(&mut seeded_rng(42)));
+/// ```
+pub fn seeded_rng(seed: u64) -> XorShiftRng {
+    // Similar seeds give very similar initial output from an xorshift generator, so we spread the
+    // seed out using the SplitMix64 mixing function first. This also guarantees that the generator
+    // is never seeded with all zeros (which is not allowed).
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    let (a, b) = (next(), next());
+    XorShiftRng::from_seed([
+        a as u32,
+        (a >> 32) as u32,
+        b as u32,
+        (b >> 32) as u32 | 1,
+    ])
+}
@@ -17,6 +17,7 @@
 extern crate rsudoku;
 
 use rsudoku::Sudoku;
+use rsudoku::sudoku::Engine;
 
 const PROBLEMS: &str = include_str!("euler96.txt");
 const SOLUTIONS: &str = include_str!("euler96solutions.txt");
@@ -24,12 +25,48 @@ const SOLUTIONS: &str = include_str!("euler96solutions.txt");
 /// Checks the sols to the sudokus given in Project Euler problem 96.
 #[test]
 fn solve_euler96() {
+    check_solutions(Engine::Backtrack);
+}
+
+/// Checks the sols to the sudokus given in Project Euler problem 96 using the Dancing Links engine.
+#[test]
+fn solve_euler96_dlx() {
+    check_solutions(Engine::Dlx);
+}
+
+/// Checks that every logical deduction made for the Project Euler problems agrees with the known
+/// solution.
+#[test]
+fn deduce_euler96() {
+    let probs = read(PROBLEMS);
+    let sols = read(SOLUTIONS);
+
+    for (n, (p, s)) in probs.iter().zip(sols.iter()).enumerate() {
+        let mut deductions = p.deductions();
+        for step in deductions.by_ref() {
+            for &(row, col, num) in &step.placements {
+                assert_eq!(s[row][col], num, "problem {}: bad placement in {:?}", n, step);
+            }
+            for &(row, col, num) in &step.eliminations {
+                assert_ne!(s[row][col], num, "problem {}: bad elimination in {:?}", n, step);
+            }
+        }
+        // Whatever we managed to fill in must be part of the solution
+        for (row, col) in (0..9).flat_map(|i| (0..9).map(move |j| (i, j))) {
+            let num = deductions.board()[row][col];
+            assert!(num == 0 || num == s[row][col]);
+        }
+    }
+}
+
+/// Checks that each of the problems has exactly the expected solution using the given engine.
+fn check_solutions(engine: Engine) {
     let probs = read(PROBLEMS);
     let sols = read(SOLUTIONS);
 
     assert_eq!(probs.len(), sols.len());
     for (n, (p, s)) in probs.iter().zip(sols.iter()).enumerate() {
-        let psols = p.solutions().collect::<Vec<_>>();
+        let psols = p.solutions_with(engine).collect::<Vec<_>>();
         assert_eq!(
             psols.len(),
             1,
@@ -19,8 +19,14 @@
 
 extern crate rsudoku;
 
-use rsudoku::game::Game;
+use std::time::Duration;
+
+use rsudoku::errors::Result;
+use rsudoku::game::{Action, Game};
 use rsudoku::Sudoku;
+use rsudoku::sudoku::Shape;
+use rsudoku::sudoku::logic::Difficulty;
+use rsudoku::util::seeded_rng;
 
 /// The example problem from Project Euler problem 96.
 const EULER: &str = "003020600
@@ -60,12 +66,18 @@ fn interactive_game() {
     // This move is correct, so the board should still have a solution
     assert!(game.board().has_unique_solution());
 
-    // 5. Undo that move
+    // 5. Undo that move, redo it and undo it again
+    assert_eq!(game.undo_action(), Some(Action::Put(8, (1, 6))));
+    game.undo();
+    assert_undone(&game, &old, "undo did not work correctly");
+    assert_eq!(game.redo_action(), Some(Action::Put(8, (1, 6))));
+    assert!(game.redo());
+    assert_eq!(game.board()[1][6], 8);
+    assert!(!game.redo(), "was able to redo further than expected");
     game.undo();
-    assert_eq!(game, old, "undo did not work correctly");
 
     // 6. Trying to undo further should do nothing
-    assert_eq!(game.undo(), false, "was able to undo further than expected");
+    assert!(!game.undo(), "was able to undo further than expected");
 
     // 7. Make a wrong move and then replace it with the right one.
     game.put(6);
@@ -73,10 +85,11 @@ fn interactive_game() {
     game.put(8);
     assert!(game.board().has_unique_solution());
 
-    // 8. Undo both moves
+    // 8. Undo both moves, after which the first of them is the next to be redone
     game.undo();
     game.undo();
-    assert_eq!(game, old, "undo did not work correctly");
+    assert_undone(&game, &old, "undo did not work correctly");
+    assert_eq!(game.redo_action(), Some(Action::Put(6, (1, 6))));
 
     // 9. Use a hint
     game.hint().expect("could not give hint");
@@ -84,7 +97,7 @@ fn interactive_game() {
 
     // 10. Undo the hint
     game.undo();
-    assert_eq!(game, old, "did not properly undo hint");
+    assert_undone(&game, &old, "did not properly undo hint");
 
     // 11. Try moving around a bit
     game.set_position(0, 0);
@@ -97,7 +110,7 @@ fn interactive_game() {
     assert_eq!(game.position(), (8, 8));
 
     // 12. Make sure that moving around wasn't captured in the undo history
-    assert_eq!(game.undo(), false);
+    assert!(!game.undo());
 
     // 13. Completely solve the game
     game.solve();
@@ -108,7 +121,580 @@ fn interactive_game() {
     assert_eq!(game.given(), &s);
 
     // 15. Undo the solve action
-    assert_eq!(game.undo(), true);
+    assert_eq!(game.undo_action(), Some(Action::Solve));
+    assert!(game.undo());
+    game.set_position(1, 6);
+    assert_undone(&game, &old, "did not properly undo solve");
+}
+
+/// Asserts that `game` is in the same state as `old`, after undoing everything done since then.
+/// The two games may only differ in the actions which can be redone.
+fn assert_undone(game: &Game, old: &Game, message: &str) {
+    assert_eq!(game.board(), old.board(), "{}", message);
+    assert_eq!(game.annotations(), old.annotations(), "{}", message);
+    assert_eq!(game.position(), old.position(), "{}", message);
+    assert_eq!(game.is_solved(), old.is_solved(), "{}", message);
+    assert_eq!(game.undo_action(), old.undo_action(), "{}", message);
+    assert!(game.redo_action().is_some(), "{}", message);
+}
+
+/// Asserts that `load` rejects `saved` once any one of the given substitutions is made to it.
+fn assert_rejected(saved: &str, load: fn(&str) -> Result<Game>, substitutions: &[(&str, &str)]) {
+    for &(from, to) in substitutions {
+        assert!(saved.contains(from), "{:?} not found", from);
+        let broken = saved.replacen(from, to, 1);
+        assert!(load(&broken).is_err(), "loaded {:?}", to);
+    }
+}
+
+#[test]
+fn seeded_game() {
+    // Games generated from the same seed should be identical
+    let mut game = Game::from_seed(1234);
+    assert_eq!(game.seed(), Some(1234));
+    assert_eq!(game, Game::from_seed(1234));
+    assert_ne!(game.given(), Game::from_seed(4321).given());
+    assert_eq!(
+        Game::from_seed_with_difficulty(1234, Difficulty::Medium).unwrap(),
+        Game::from_seed_with_difficulty(1234, Difficulty::Medium).unwrap()
+    );
+
+    // Hints given using the same seed should be in the same place
+    let mut game2 = game.clone();
+    assert_eq!(
+        game.hint_with_rng(&mut seeded_rng(5)).unwrap(),
+        game2.hint_with_rng(&mut seeded_rng(5)).unwrap()
+    );
+    assert_eq!(game, game2);
+
+    // Games loaded from a file don't have a seed
+    assert_eq!(Game::from_sudoku(game.given().clone()).seed(), None);
+}
+
+#[test]
+fn small_game() {
+    // New games can be generated with the same shape as an existing board
+    let template = Sudoku::new(Shape::for_size(4).unwrap());
+    let mut game = Game::from_seed_like(1234, &template, None).unwrap();
+    assert_eq!(game.board().size(), 4);
+    assert_eq!(game.annotations().len(), 4);
+
+    // Movement is limited by the edges of the smaller grid
+    game.move_by(3, 3);
+    assert_eq!(game.position(), (3, 3));
+    game.move_by(1, -1);
+    assert_eq!(game.position(), (3, 2));
+
+    game.solve();
+    assert!(game.is_solved());
+    assert!(game.board().is_solved());
+}
+
+#[test]
+fn save_game() {
+    // Everything about a game, including its history, survives being saved and loaded
+    let template = Sudoku::new(Shape::new(3, 2).unwrap());
+    let mut game = Game::from_seed_killer(1234, &template, None).unwrap();
+    game.set_position(4, 1);
+    game.put(3);
+    game.annotate(2);
+    game.annotate(6);
+    game.move_by(1, 1);
+    game.put(5);
+    game.add_elapsed(Duration::from_millis(83_250));
+    let saved = game.to_save_string();
+    let mut loaded = Game::from_save_string(&saved).unwrap();
+    assert_eq!(loaded, game);
+    assert_eq!(loaded.board().shape(), Shape::new(3, 2).unwrap());
+    assert_eq!(loaded.elapsed(), Duration::from_millis(83_250));
+    assert_eq!(loaded.to_save_string(), saved);
+    assert!(loaded.undo());
+    assert_eq!(loaded.redo_action(), Some(Action::Put(5, (5, 2))));
+    let saved = loaded.to_save_string();
+    assert_eq!(Game::from_save_string(&saved).unwrap(), loaded);
+    for _ in 0..2 {
+        assert!(loaded.undo());
+    }
+    assert_eq!(loaded.annotations()[4][1].list(), Vec::<u8>::new());
+    assert_eq!(loaded.board()[4][1], 3);
+
+    // A board which breaks the rules can still be saved
+    let mut game = Game::from_sudoku(EULER.parse::<Sudoku>().unwrap());
+    game.set_position(0, 0);
+    game.put(3);
+    let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+
+    let saved = game.to_save_string();
+    assert_rejected(
+        &saved,
+        Game::from_save_string,
+        &[
+            ("rsudoku save 1", "rsudoku save 2"),
+            ("rsudoku save 1", "sudoku"),
+            ("mistakes: 1", "mistakes: -1"),
+            ("box: 3x3\n", ""),
+            ("position: r1c1", "position: r1c10"),
+            ("current: 1", "current: 2"),
+            ("redo: 1", "redo: 2"),
+            ("[moves]", "[board]"),
+            ("[moves]", "[moves]\n0"),
+            ("1 after 0", "2 after 0"),
+            ("1 after 0", "1 after 1"),
+            ("1 after 0 at 0.000", "1 after 0"),
+            ("at 0.000", "at -1"),
+            ("put 3 at r1c1", "put 3 at r1c10"),
+            ("put 3 at r1c1", "put 3 at r1c3"),
+            ("put 3", "pot 3"),
+        ],
+    );
+}
+
+#[test]
+fn undo_tree() {
+    let mut game = Game::from_sudoku(EULER.parse::<Sudoku>().unwrap());
+    let start = game.clone();
+
+    // Try a guess, undo it and try another, which starts a new branch
+    game.set_position(1, 6);
+    game.put(6);
+    game.undo();
+    game.put(8);
+    game.set_position(0, 0);
+    game.annotate(4);
+    assert_eq!(game.moves().len(), 3);
+    assert_eq!(game.branches(), vec![0, 2]);
+    assert_eq!(game.moves()[2].parent(), Some(1));
+    assert_eq!(game.current_move(), Some(2));
+    assert_eq!(
+        game.move_log(),
+        vec![Action::Put(8, (1, 6)), Action::Annotate(4, (0, 0))]
+    );
+
+    // Jump back to the first guess, and back again
+    let second = game.clone();
+    game.jump_to(Some(0));
+    assert_eq!(game.board()[1][6], 6);
+    assert!(!game.annotations()[0][0][4]);
+    assert_eq!(game.move_log(), vec![Action::Put(6, (1, 6))]);
+    assert_eq!(game.redo_action(), None);
+    game.jump_to(Some(2));
+    assert_eq!(game.board(), second.board());
+    assert_eq!(game.annotations(), second.annotations());
+
+    // Undoing to the start redoes the branch which was last visited
+    game.jump_to(None);
+    assert_undone(&game, &start, "did not go back to the start");
+    assert_eq!(game.redo_action(), Some(Action::Put(8, (1, 6))));
+    game.jump_to(Some(0));
+    game.undo();
+    assert_eq!(game.redo_action(), Some(Action::Put(6, (1, 6))));
+
+    // Solving makes a single move which can be undone, even on top of another branch
+    game.jump_to(Some(1));
+    assert!(game.solve());
+    assert!(game.is_solved());
+    assert_eq!(game.branches(), vec![0, 2, 3]);
+    game.undo();
+    assert_eq!(game.board()[1][6], 8);
+    assert_eq!(game.board()[0][0], 0);
+    game.redo();
+    assert!(game.is_solved());
+
+    // The whole tree is saved, and the move log can be exported
+    let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+    game.jump_to(Some(2));
+    let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+    let log = game.to_move_log_string();
+    assert!(log.starts_with("rsudoku moves 1\n"));
+    assert!(log.ends_with("[moves]\n0.000: put 8 at r2c7\n0.000: annotate 4 at r1c1\n"));
+}
+
+#[test]
+fn replay_game() {
+    // Every move remembers when it was made
+    let mut game = Game::from_sudoku(EULER.parse::<Sudoku>().unwrap());
     game.set_position(1, 6);
-    assert_eq!(game, old, "did not properly undo solve");
+    game.add_elapsed(Duration::from_millis(1_500));
+    game.put(6);
+    game.add_elapsed(Duration::from_millis(2_250));
+    game.undo();
+    game.put(8);
+    game.add_elapsed(Duration::from_secs(62));
+    game.solve();
+    let times = game.moves().iter().map(|m| m.time()).collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        vec![
+            Duration::from_millis(1_500),
+            Duration::from_millis(3_750),
+            Duration::from_millis(65_750),
+        ]
+    );
+
+    // The times survive saving, and the moves can be stepped through in the order they were made
+    let mut loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+    loaded.jump_to(Some(0));
+    assert_eq!(loaded.board()[1][6], 6);
+    loaded.jump_to(Some(1));
+    assert_eq!(loaded.board()[1][6], 8);
+    assert!(!loaded.is_solved());
+
+    // A move log only has the moves leading to the current board, but they can still be replayed
+    let log = game.to_move_log_string();
+    assert!(log.contains("\n3.750: put 8 at r2c7\n65.750: solve\n"));
+    let replayed = Game::from_move_log_string(&log).unwrap();
+    assert_eq!(replayed.board(), game.board());
+    assert_eq!(replayed.move_log(), game.move_log());
+    assert_eq!(replayed.elapsed(), Duration::from_millis(65_750));
+    let times = replayed.moves().iter().map(|m| m.time()).collect::<Vec<_>>();
+    assert_eq!(times, &game.moves().iter().map(|m| m.time()).collect::<Vec<_>>()[1..]);
+
+    assert_rejected(
+        &log,
+        Game::from_move_log_string,
+        &[
+            ("rsudoku moves 1", "rsudoku moves 2"),
+            ("box: 3x3\n", ""),
+            ("3.750: ", ""),
+            ("3.750", "x"),
+            ("put 8 at r2c7", "put 8 at r1c3"),
+            ("[moves]", "[move]"),
+        ],
+    );
+}
+
+#[test]
+fn game_statistics() {
+    let s = EULER.parse::<Sudoku>().unwrap();
+    let solution = s.solutions().next().unwrap();
+    let mut game = Game::from_sudoku(s.clone());
+
+    // A number which breaks the rules is a mistake, even if it is undone
+    game.set_position(0, 0);
+    game.put(3);
+    game.put(4);
+    assert_eq!(game.mistakes(), 1);
+    game.undo();
+    game.undo();
+    assert_eq!(game.undos(), 2);
+    game.hint_with_rng(&mut seeded_rng(1)).unwrap();
+    assert_eq!(game.hints(), 1);
+    assert_eq!(game.completion(), None);
+
+    // Everything is recorded once the player solves the board
+    game.add_elapsed(Duration::from_secs(300));
+    for (i, j) in (0..9).flat_map(|i| (0..9).map(move |j| (i, j))) {
+        if game.board()[i][j] == 0 {
+            game.set_position(i, j);
+            game.put(solution[i][j]);
+        }
+    }
+    assert!(game.is_solved());
+    let completion = game.completion().expect("no completion recorded");
+    assert_eq!(completion.time(), Duration::from_secs(300));
+    assert_eq!(
+        (completion.hints(), completion.undos(), completion.mistakes()),
+        (1, 2, 1)
+    );
+    assert!(!completion.solver());
+
+    // Solving the board again later doesn't change the record
+    game.undo();
+    game.add_elapsed(Duration::from_secs(10));
+    game.redo();
+    assert_eq!(game.completion(), Some(completion));
+    assert_eq!(game.undos(), 3);
+    let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+
+    // Using the solver is recorded as well
+    let mut game = Game::from_sudoku(s);
+    game.solve();
+    assert!(game.completion().unwrap().solver());
+    let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+}
+
+#[test]
+fn mistake_checking() {
+    use rsudoku::game::CheckMode;
+
+    let s = EULER.parse::<Sudoku>().unwrap();
+    let solution = s.solutions().next().unwrap();
+    let mut game = Game::from_sudoku(s);
+    assert_eq!(game.solution(), Some(&solution));
+    assert_eq!("Solution".parse::<CheckMode>().unwrap(), CheckMode::Solution);
+    assert!("wrong".parse::<CheckMode>().is_err());
+
+    // The top left cell is 4; 3 clashes with the 3 in the same row, but 5 doesn't clash with
+    // anything, so it is only caught by checking against the solution
+    game.set_position(0, 0);
+    game.put(3);
+    assert!(game.is_mistake_at(0, 0, CheckMode::Conflicts));
+    assert!(game.is_mistake_at(0, 0, CheckMode::Solution));
+    assert!(!game.is_mistake_at(0, 0, CheckMode::Off));
+    game.put(5);
+    assert!(!game.is_mistake_at(0, 0, CheckMode::Conflicts));
+    assert!(game.is_mistake_at(0, 0, CheckMode::Solution));
+    game.put(4);
+    assert!(!game.is_mistake_at(0, 0, CheckMode::Solution));
+    // Given numbers and empty cells are never mistakes
+    assert!(!game.is_mistake_at(0, 2, CheckMode::Solution));
+    assert!(!game.is_mistake_at(0, 1, CheckMode::Solution));
+    assert_eq!(game.mistakes(), 2);
+
+    // Without a unique solution, only conflicts can be checked
+    let mut game = Game::from_sudoku(Sudoku::new(Shape::default()));
+    assert_eq!(game.solution(), None);
+    game.put(1);
+    game.set_position(0, 1);
+    game.put(2);
+    assert!(!game.is_mistake_at(0, 1, CheckMode::Solution));
+    game.put(1);
+    assert!(game.is_mistake_at(0, 1, CheckMode::Solution));
+    assert_eq!(game.mistakes(), 1);
+}
+
+#[test]
+fn explained_hints() {
+    use rsudoku::sudoku::logic::Technique;
+
+    let s = EULER.parse::<Sudoku>().unwrap();
+    let solution = s.solutions().next().unwrap();
+    let mut game = Game::from_sudoku(s);
+
+    // The first number which can be found logically is the 4 at r5c6
+    let hint = game.next_hint().unwrap().expect("no hint found");
+    assert_eq!(hint.placement(), (4, 5, 4));
+    assert_eq!(hint.steps()[0].technique, Technique::NakedSingle);
+    assert_eq!(hint.clue(), "Look at row 5 for a naked single");
+    assert_eq!(hint.cells(), vec![(4, 5)]);
+    assert_eq!(hint.explain(), "naked single on 4: place 4 at r5c6");
+
+    // Annotations are taken into account, unless they rule out the right number
+    game.annotate(5);
+    assert_eq!(game.next_hint().unwrap(), Some(hint.clone()));
+    game.annotate(5);
+    game.annotate(4);
+    let annotated = game.next_hint().unwrap().expect("no hint found");
+    assert_eq!(annotated.placement(), (0, 0, 4));
+    assert_eq!(annotated.clue(), "Look at row 1 for a naked single");
+
+    // Giving a hint places the number
+    game.clear_annotations();
+    assert_eq!(game.hint().unwrap(), Some((4, 5)));
+    assert_eq!(game.board()[4][5], 4);
+    assert_eq!(game.hints(), 1);
+    while game.hint().unwrap().is_some() {}
+    assert_eq!(game.board(), &solution);
+    assert_eq!(game.next_hint().unwrap(), None);
+}
+
+#[test]
+fn candidate_annotations() {
+    let mut game = Game::from_sudoku(EULER.parse::<Sudoku>().unwrap());
+    let candidates = game.board().candidates();
+    assert_eq!(candidates[0][0].list(), vec![4, 5]);
+    assert_eq!(candidates[0][1].list(), vec![4, 5, 7, 8]);
+    assert_eq!(candidates[0][2].count(), 0);
+
+    // Filling in the annotations is a single move
+    game.annotate(9);
+    game.fill_annotations();
+    assert_eq!(game.annotations(), &candidates[..]);
+    assert!(game.undo());
+    assert_eq!(game.annotations()[0][0].list(), vec![9]);
+    assert!(game.redo());
+
+    // Putting a number prunes it from the annotations which see it, and undoing brings them back
+    game.set_autoprune(true);
+    game.put(4);
+    assert_eq!(game.annotations()[0][1].list(), vec![5, 7, 8]);
+    assert_eq!(game.annotations()[3][0].list(), vec![3, 5]);
+    assert!(game.moves()[2].pruned());
+    let log = game.to_move_log_string();
+    assert!(log.ends_with("0.000: fill annotations\n0.000 pruning: put 4 at r1c1\n"));
+    assert!(game.undo());
+    assert_eq!(game.annotations(), &candidates[..]);
+    assert!(game.redo());
+
+    // Loading a game makes its moves in the same way, whatever the setting
+    let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+    game.set_autoprune(false);
+    let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+    assert_eq!(loaded, game);
+    let replayed = Game::from_move_log_string(&log).unwrap();
+    assert_eq!(replayed.annotations(), game.annotations());
+    game.undo();
+    game.put(4);
+    assert!(!game.moves()[3].pruned());
+    assert_eq!(game.annotations()[0][1].list(), vec![4, 5, 7, 8]);
+}
+
+#[test]
+fn autoprune_undo() {
+    let mut game = Game::from_sudoku(EULER.parse::<Sudoku>().unwrap());
+    game.set_autoprune(true);
+    // Annotations in the same row, column and box as r1c1 (and one elsewhere, which is kept)
+    for &(row, col) in &[(0, 1), (0, 3), (3, 0), (2, 1), (4, 4)] {
+        game.set_position(row, col);
+        game.annotate(4);
+        game.annotate(5);
+    }
+    let annotated = game.annotations().to_vec();
+    game.set_position(0, 0);
+    game.put(4);
+    for &(row, col) in &[(0, 1), (0, 3), (3, 0), (2, 1)] {
+        assert_eq!(game.annotations()[row][col].list(), vec![5]);
+    }
+    assert_eq!(game.annotations()[4][4].list(), vec![4, 5]);
+    let pruned = game.annotations().to_vec();
+
+    // The annotations come back all at once, and only those which were pruned
+    assert!(game.undo());
+    assert_eq!(game.annotations(), &annotated[..]);
+    assert_eq!(game.board()[0][0], 0);
+    assert!(game.redo());
+    assert_eq!(game.annotations(), &pruned[..]);
+
+    // Replacing the number prunes the new one, and undoing restores the first pruning
+    game.put(5);
+    assert_eq!(game.annotations()[0][1].count(), 0);
+    assert!(game.undo());
+    assert_eq!(game.annotations(), &pruned[..]);
+    assert_eq!(game.board()[0][0], 4);
+
+    // The setting is saved with the game
+    let saved = game.to_save_string();
+    assert!(saved.contains("\nautoprune: on\n"));
+    let loaded = Game::from_save_string(&saved).unwrap();
+    assert!(loaded.autoprune());
+    assert_eq!(loaded, game);
+    assert_rejected(
+        &saved,
+        Game::from_save_string,
+        &[("autoprune: on", "autoprune: yes"), ("autoprune: on", "autoprune")],
+    );
+}
+
+#[test]
+fn selection() {
+    use rsudoku::sudoku::logic::Unit;
+
+    // Changing several positions at once is a single move, which skips the given numbers
+    let mut game = Game::from_sudoku(EULER.parse::<Sudoku>().unwrap());
+    for col in 0..3 {
+        game.select(0, col);
+    }
+    assert!(game.is_selected(0, 2));
+    game.put(4);
+    assert_eq!(&game.board()[0][..3], &[4, 4, 3]);
+    assert_eq!(game.moves().len(), 2);
+    assert!(game.moves()[1].joined());
+    assert!(game.undo());
+    assert_eq!(&game.board()[0][..3], &[0, 0, 3]);
+    assert_eq!(game.undos(), 1);
+    assert!(!game.undo());
+    assert!(game.redo());
+    assert_eq!(&game.board()[0][..3], &[4, 4, 3]);
+    // Jumping back over them counts as a single undo too
+    game.jump_to(None);
+    assert_eq!(game.undos(), 2);
+    game.jump_to(Some(1));
+    assert_eq!(&game.board()[0][..3], &[4, 4, 3]);
+    game.remove();
+    assert_eq!(game.moves().len(), 4);
+
+    // An annotation is added to every empty position, unless they all have it
+    game.clear_selection();
+    game.set_position(2, 0);
+    game.annotate(2);
+    game.select_unit(Unit::Row(2));
+    assert_eq!(game.selection().len(), 9);
+    game.annotate(2);
+    let annotated = (0..9)
+        .filter(|&col| game.annotations()[2][col][2])
+        .collect::<Vec<_>>();
+    assert_eq!(annotated, vec![0, 1, 4, 7, 8]);
+    assert_eq!(game.moves().len(), 9);
+    game.annotate(2);
+    assert!((0..9).all(|col| game.annotations()[2][col].count() == 0));
+    game.undo();
+    game.clear_annotations();
+    assert!((0..9).all(|col| game.annotations()[2][col].count() == 0));
+
+    // The selection and the moves made together survive saving
+    game.clear_selection();
+    game.select_number(8);
+    assert_eq!(game.selection().len(), 5);
+    assert!(game.is_selected(2, 3));
+    let saved = game.to_save_string();
+    assert!(saved.contains("selection: r3c4 r4c3 r5c9 r6c6 r8c1\n"));
+    assert!(saved.contains(" joined: put 4 at r1c2\n"));
+    assert_eq!(Game::from_save_string(&saved).unwrap(), game);
+    let log = game.to_move_log_string();
+    let replayed = Game::from_move_log_string(&log).unwrap();
+    assert_eq!(replayed.annotations(), game.annotations());
+    assert!(log.contains("\n0.000 joined: clear r1c2\n"));
+    assert_rejected(
+        &saved,
+        Game::from_save_string,
+        &[
+            ("1 after 0 at 0.000", "1 after 0 at 0.000 joined"),
+            ("2 after 1 at 0.000 joined", "2 after 1 at 0.000 joined joined"),
+            ("2 after 1 at 0.000 joined", "2 after 1 at 0.000 jointed"),
+            ("selection: r3c4", "selection: r3c10"),
+        ],
+    );
+}
+
+#[test]
+fn corner_marks() {
+    // Corner marks are kept apart from annotations, but work in the same way
+    let mut game = Game::from_sudoku(EULER.parse::<Sudoku>().unwrap());
+    game.annotate(4);
+    game.mark_corner(4);
+    game.mark_corner(5);
+    assert_eq!(game.annotations()[0][0].list(), vec![4]);
+    assert_eq!(game.corner_marks()[0][0].list(), vec![4, 5]);
+    game.clear_corner_marks();
+    assert_eq!(game.corner_marks()[0][0].count(), 0);
+    assert_eq!(game.annotations()[0][0].list(), vec![4]);
+    assert!(game.undo());
+    assert_eq!(game.corner_marks()[0][0].list(), vec![4, 5]);
+
+    // Several selected positions are marked at once, and pruned like annotations
+    game.select(0, 1);
+    game.select(1, 1);
+    game.mark_corner(4);
+    assert!(game.moves()[5].joined());
+    assert_eq!(game.corner_marks()[1][1].list(), vec![4]);
+    game.clear_selection();
+    game.set_autoprune(true);
+    game.move_by(1, 1);
+    game.put(4);
+    assert_eq!(game.corner_marks()[0][0].list(), vec![5]);
+    assert_eq!(game.corner_marks()[0][1].count(), 0);
+    assert_eq!(game.annotations()[0][0].count(), 0);
+    assert!(game.moves()[6].pruned());
+    assert!(game.undo());
+    assert_eq!(game.corner_marks()[0][0].list(), vec![4, 5]);
+    assert!(game.redo());
+
+    // Both layers survive saving
+    let saved = game.to_save_string();
+    assert!(saved.contains("\n2 after 1 at 0.000: corner mark 4 at r1c1\n"));
+    assert!(saved.contains(": clear corner marks at r1c1\n"));
+    let loaded = Game::from_save_string(&saved).unwrap();
+    assert_eq!(loaded, game);
+    assert_eq!(loaded.corner_marks(), game.corner_marks());
+    let log = game.to_move_log_string();
+    let replayed = Game::from_move_log_string(&log).unwrap();
+    assert_eq!(replayed.corner_marks(), game.corner_marks());
+    assert_rejected(&saved, Game::from_save_string, &[("corner mark 4", "corner mark 0")]);
 }
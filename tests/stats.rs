@@ -0,0 +1,134 @@
+// Copyright (C) 2017 Ian Johnson
+//
+// This file is part of RSudoku.
+//
+// RSudoku is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// RSudoku is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with RSudoku.  If not, see <http://www.gnu.org/licenses/>.
+//! Tests of the statistics kept about finished games.
+
+extern crate rsudoku;
+
+use std::time::Duration;
+
+use rsudoku::Sudoku;
+use rsudoku::game::Game;
+use rsudoku::stats::{Record, Stats};
+use rsudoku::sudoku::logic::Difficulty;
+
+/// The number of seconds in a day.
+const DAY: u64 = 24 * 60 * 60;
+
+/// The first day of the statistics below (2024-10-04), in seconds since the Unix epoch.
+const START: u64 = 20_000 * DAY;
+
+const STATS: &str = "rsudoku stats 1
+1728000000 3x3 easy 0000000000000001 300.000 0 0 0
+1728090000 3x3 easy 0000000000000002 200.500 2 1 0
+1728090000 3x3 hard 0000000000000003 200.500 0 0 1
+1728176400 2x2 easy 0000000000000004 30.000 0 0 0
+1728349200 3x3 medium 0000000000000005 10.000 0 0 0 solve
+1728435600 3x3 hard 0000000000000006 600.000 1 0 0
+1728435600 3x3 hard 0000000000000007 200.500 2 0 0
+";
+
+#[test]
+fn player_statistics() {
+    let stats = Stats::from_stats_string(STATS).unwrap();
+    assert_eq!(stats.records().len(), 7);
+    assert_eq!(stats.to_stats_string(), STATS);
+
+    // Games finished using the solver don't count towards anything
+    let summaries = stats.by_difficulty();
+    let groups = summaries
+        .iter()
+        .map(|s| (s.size, s.difficulty, s.solved))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        groups,
+        vec![
+            (4, Difficulty::Easy, 1),
+            (9, Difficulty::Easy, 2),
+            (9, Difficulty::Hard, 3),
+        ]
+    );
+    assert_eq!(summaries[1].best, Duration::from_millis(200_500));
+    assert_eq!(summaries[1].average, Duration::from_millis(250_250));
+
+    // Games are only ranked against others of the same size and difficulty, with ties broken by
+    // the number of hints
+    let leaderboard = |size, difficulty, n| {
+        stats
+            .leaderboard(size, difficulty, n)
+            .into_iter()
+            .map(|r| r.puzzle())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        leaderboard(9, Difficulty::Hard, 2),
+        vec!["0000000000000003", "0000000000000007"]
+    );
+    assert_eq!(
+        leaderboard(9, Difficulty::Easy, 3),
+        vec!["0000000000000002", "0000000000000001"]
+    );
+    assert!(leaderboard(9, Difficulty::Medium, 3).is_empty());
+
+    // Using the solver on 2024-10-08 doesn't count, so the current streak starts the next day and
+    // lasts until the end of the day after
+    assert_eq!(stats.streaks(START + 5 * DAY), (1, 3));
+    assert_eq!(stats.streaks(START + 6 * DAY), (1, 3));
+    assert_eq!(stats.streaks(START + 7 * DAY), (0, 3));
+    assert_eq!(Stats::new().streaks(START), (0, 0));
+
+    let report = stats.report(1, START + 5 * DAY);
+    assert!(report.contains("Games solved: 6 (and 1 finished using :solve)"));
+    assert!(report.contains("Current streak: 1 day (longest: 3 days)"));
+    assert!(report.contains("Fastest 4x4 easy games:\nRANK  DATE        TIME"));
+    assert!(report.contains("\n1     2024-10-06  0:30      0      0      0\n"));
+    assert!(report.contains("\n1     2024-10-05  3:20      2      1      0\n"));
+    assert!(!report.contains("2024-10-04"));
+
+    // Only solved games have a record
+    let s = "003020600
+900305001
+001806400
+008102900
+700000008
+006708200
+002609500
+800203009
+005010300"
+        .parse::<Sudoku>()
+        .unwrap();
+    let mut game = Game::from_sudoku(s);
+    assert_eq!(Record::new(&game, START), None);
+    game.solve();
+    let record = Record::new(&game, START).unwrap();
+    assert_eq!(record.puzzle(), game.puzzle_id());
+    assert!(record.completion().solver());
+    let mut stats = Stats::new();
+    stats.add(record.clone());
+    let loaded = Stats::from_stats_string(&stats.to_stats_string()).unwrap();
+    assert_eq!(loaded.records(), &[record]);
+
+    for bad in &[
+        "",
+        "rsudoku stats 2\n",
+        "rsudoku stats 1\n1728000000 3x3 easy 01 300.000 0 0\n",
+        "rsudoku stats 1\n1728000000 3x3 easy 01 -1 0 0 0\n",
+        "rsudoku stats 1\n1728000000 3x3 tricky 01 300 0 0 0\n",
+        "rsudoku stats 1\n1728000000 3x3 easy 01 300 0 0 0 solved\n",
+    ] {
+        assert!(Stats::from_stats_string(bad).is_err(), "parsed {:?}", bad);
+    }
+}